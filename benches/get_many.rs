@@ -0,0 +1,61 @@
+/* Compares batched lookups through `get_many` against looking up the same keys one by one.
+ *
+ * The map is made large enough that its bins do not fit in cache, and every iteration looks up a
+ * different batch of keys, since cold lookups are where prefetching pays off.
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flurry::HashMap;
+
+const SIZE: usize = 1 << 20;
+const BATCHES: &[usize] = &[8, 32, 128];
+
+/// Keys spread over the whole map (and beyond it, so some lookups miss).
+fn lookup_keys() -> Vec<usize> {
+    (0..SIZE)
+        .map(|i| i.wrapping_mul(2_654_435_761) % (SIZE + SIZE / 4))
+        .collect()
+}
+
+fn get_many(c: &mut Criterion) {
+    let map: HashMap<usize, usize> = (0..SIZE).map(|i| (i, i)).collect();
+    let keys = lookup_keys();
+    let keys: Vec<&usize> = keys.iter().collect();
+
+    let mut group = c.benchmark_group("get_many");
+    for &batch in BATCHES {
+        group.throughput(Throughput::Elements(batch as u64));
+
+        group.bench_with_input(BenchmarkId::new("get", batch), &batch, |b, &batch| {
+            let guard = map.guard();
+            let mut batches = keys.chunks_exact(batch).cycle();
+            b.iter(|| {
+                for key in batches.next().unwrap() {
+                    black_box(map.get(*key, &guard));
+                }
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("get_many", batch), &batch, |b, &batch| {
+            let guard = map.guard();
+            let mut batches = keys.chunks_exact(batch).cycle();
+            b.iter(|| black_box(map.get_many(batches.next().unwrap(), &guard)));
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("get_many_array");
+    group.throughput(Throughput::Elements(32));
+    group.bench_function("get_many_array", |b| {
+        let guard = map.guard();
+        let mut batches = keys.chunks_exact(32).cycle();
+        b.iter(|| {
+            let batch: [&usize; 32] = batches.next().unwrap().try_into().unwrap();
+            black_box(map.get_many_array(batch, &guard))
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, get_many);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+/* Compares bulk loading a map through `insert_many` against calling `insert` for every entry. */
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flurry::HashMap;
+
+const SIZES: &[usize] = &[1 << 10, 1 << 16];
+
+fn insert_many(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_many");
+    for &size in SIZES {
+        group.throughput(Throughput::Elements(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("insert", size), &size, |b, &size| {
+            b.iter(|| {
+                let map = HashMap::<usize, usize>::new();
+                let guard = map.guard();
+                for i in 0..size {
+                    map.insert(i, i, &guard);
+                }
+                black_box(&map);
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("insert_many", size), &size, |b, &size| {
+            b.iter(|| {
+                let map = HashMap::<usize, usize>::new();
+                let guard = map.guard();
+                black_box(map.insert_many((0..size).map(|i| (i, i)), &guard));
+                black_box(&map);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert_many);
+criterion_main!(benches);
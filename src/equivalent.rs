@@ -0,0 +1,86 @@
+//! Traits for looking up keys by types other than the key type itself.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// Key equivalence, used by lookups to match a query against a key in the map.
+///
+/// This generalizes [`Borrow`]: every type `Q` that a key type `K` can be borrowed as is
+/// `Equivalent<K>` through a blanket implementation, but other types may also implement it. This
+/// allows looking up a composite key, such as `(String, u32)`, without first allocating a value of
+/// the key type.
+///
+/// A query must hash the same way as the keys it is equivalent to.
+pub trait Equivalent<K: ?Sized> {
+    /// Returns `true` if `self` is equivalent to `key`.
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q, K> Equivalent<K> for Q
+where
+    Q: ?Sized + Eq,
+    K: ?Sized + Borrow<Q>,
+{
+    #[inline]
+    fn equivalent(&self, key: &K) -> bool {
+        PartialEq::eq(self, key.borrow())
+    }
+}
+
+/// Key ordering, used by lookups to navigate the red-black trees that large bins are organized
+/// into.
+///
+/// Like [`Equivalent`], this is implemented for every type `Q` that a key type `K` can be
+/// borrowed as. `compare` must be consistent with [`Equivalent::equivalent`] and with the
+/// ordering of the keys: it returns [`Ordering::Equal`] exactly when `self` is equivalent to
+/// `key`, and otherwise orders `self` where an equivalent key would be ordered.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::{Comparable, Equivalent, HashMap};
+/// use std::cmp::Ordering;
+/// use std::hash::{Hash, Hasher};
+///
+/// // a borrowed form of `(String, u32)`
+/// struct Query<'a>(&'a str, u32);
+///
+/// impl Hash for Query<'_> {
+///     fn hash<H: Hasher>(&self, state: &mut H) {
+///         // hash the same way as (String, u32)
+///         (self.0, self.1).hash(state);
+///     }
+/// }
+///
+/// impl Equivalent<(String, u32)> for Query<'_> {
+///     fn equivalent(&self, key: &(String, u32)) -> bool {
+///         self.0 == key.0 && self.1 == key.1
+///     }
+/// }
+///
+/// impl Comparable<(String, u32)> for Query<'_> {
+///     fn compare(&self, key: &(String, u32)) -> Ordering {
+///         (self.0, self.1).cmp(&(key.0.as_str(), key.1))
+///     }
+/// }
+///
+/// let map = HashMap::new();
+/// map.pin().insert((String::from("a"), 1), "x");
+/// assert_eq!(map.pin().get(&Query("a", 1)), Some(&"x"));
+/// assert_eq!(map.pin().get(&Query("a", 2)), None);
+/// ```
+pub trait Comparable<K: ?Sized>: Equivalent<K> {
+    /// Compares `self` to `key`.
+    fn compare(&self, key: &K) -> Ordering;
+}
+
+impl<Q, K> Comparable<K> for Q
+where
+    Q: ?Sized + Ord,
+    K: ?Sized + Borrow<Q>,
+{
+    #[inline]
+    fn compare(&self, key: &K) -> Ordering {
+        Ord::cmp(self, key.borrow())
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The kind of change a [`ChangeEvent`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// A key that was absent was inserted.
+    Insert,
+    /// The value of a key was replaced.
+    Replace,
+    /// A key was removed.
+    Remove,
+    /// A key was removed by [`HashMap::clear`](crate::HashMap::clear).
+    Clear,
+}
+
+/// A change to a [`HashMap`](crate::HashMap), as recorded by its change feed.
+///
+/// A sink receives events whose keys and values are references into the map, so `K` and `V` are
+/// references there. Use [`ChangeEvent::cloned`] to keep an event beyond the call to the sink.
+///
+/// Events are numbered by `seq`, without gaps and starting at 0, in an order that is consistent
+/// with the order in which the changes took effect on each key. Concurrent changes may reach the
+/// sink out of order, so a follower that applies events with
+/// [`HashMap::apply_event`](crate::HashMap::apply_event) must apply them in order of `seq`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChangeEvent<K, V> {
+    /// The position of this change in the change feed.
+    pub seq: u64,
+    /// The kind of change.
+    pub kind: ChangeKind,
+    /// The key that changed.
+    pub key: K,
+    /// The value of the key before the change, if it was present.
+    pub old: Option<V>,
+    /// The value of the key after the change, if it is present.
+    pub new: Option<V>,
+}
+
+impl<K, V> ChangeEvent<&K, &V>
+where
+    K: Clone,
+    V: Clone,
+{
+    /// Returns an event that owns clones of this event's key and values.
+    pub fn cloned(&self) -> ChangeEvent<K, V> {
+        ChangeEvent {
+            seq: self.seq,
+            kind: self.kind,
+            key: self.key.clone(),
+            old: self.old.cloned(),
+            new: self.new.cloned(),
+        }
+    }
+}
+
+/// A destination for the change feed of a [`HashMap`](crate::HashMap), set with
+/// [`HashMap::with_change_sink`](crate::HashMap::with_change_sink).
+///
+/// Every successful change to the map is passed to [`record`](ChangeSink::record) once the bin
+/// lock for the change has been released, on the thread that made the change. Changes made
+/// through a [`KeyLock`](crate::KeyLock) are recorded right away instead, while the key is still
+/// locked, so the sink must not wait for updates to the map. Since changes may be recorded
+/// concurrently, see [`ChangeEvent`] for how they are ordered.
+///
+/// This is implemented for closures that take a [`ChangeEvent`].
+pub trait ChangeSink<K, V>: Send + Sync {
+    /// Records a change to the map.
+    fn record(&self, event: ChangeEvent<&K, &V>);
+}
+
+impl<K, V, F> ChangeSink<K, V> for F
+where
+    F: Fn(ChangeEvent<&K, &V>) + Send + Sync,
+{
+    fn record(&self, event: ChangeEvent<&K, &V>) {
+        self(event)
+    }
+}
+
+/// The change feed of a map: a sink, and the sequence number of the next change.
+pub(crate) struct ChangeFeed<K, V> {
+    next_seq: AtomicU64,
+    sink: Box<dyn ChangeSink<K, V>>,
+}
+
+impl<K, V> ChangeFeed<K, V> {
+    pub(crate) fn new(sink: Box<dyn ChangeSink<K, V>>) -> Self {
+        Self {
+            next_seq: AtomicU64::new(0),
+            sink,
+        }
+    }
+
+    /// Assigns the next sequence number to a change.
+    ///
+    /// This must be called while holding the lock of the bin the change is made to, and before
+    /// the change can let other writers past that lock (for example, by emptying the bin), so
+    /// that later changes to the same key are numbered after it.
+    pub(crate) fn sequence(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub(crate) fn record(&self, event: ChangeEvent<&K, &V>) {
+        self.sink.record(event);
+    }
+}
+
+impl<K, V> Debug for ChangeFeed<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChangeFeed")
+            .field("next_seq", &self.next_seq.load(Ordering::Relaxed))
+            .finish()
+    }
+}
@@ -0,0 +1,127 @@
+//! Maps and sets that share a single collector.
+//!
+//! See `MapGroup` for details.
+
+use crate::reclaim::{self, Collector, Guard};
+use crate::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// A handle to a [`seize::Collector`] that is shared by several maps and sets.
+///
+/// Every map and set created through the same `MapGroup` (or a clone of it) uses the same
+/// collector, so a single `Guard` from [`MapGroup::guard`] (or from the `guard` method of any
+/// member) may be passed to all of them. Normally, passing a guard from one map to another panics,
+/// since the two maps do not share a collector.
+///
+/// Since garbage retired by one member may be reclaimed only after that member has been dropped,
+/// members of a group must have `'static` keys and values.
+///
+/// Clones of a member are members of the same group as well.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::MapGroup;
+///
+/// let group = MapGroup::new();
+/// let map = group.map();
+/// let set = group.set();
+///
+/// let guard = group.guard();
+/// map.insert(1, "a", &guard);
+/// set.insert(1, &guard);
+/// assert_eq!(map.get(&1, &guard), Some(&"a"));
+/// assert!(set.contains(&1, &guard));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MapGroup {
+    collector: Arc<Collector>,
+}
+
+impl Default for MapGroup {
+    fn default() -> Self {
+        Self::with_collector(reclaim::collector())
+    }
+}
+
+impl MapGroup {
+    /// Creates a new group with its own collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new group that uses the given `collector`.
+    pub fn with_collector(collector: Collector) -> Self {
+        Self {
+            collector: Arc::new(collector),
+        }
+    }
+
+    /// Pin a `Guard` for use with any map or set in this group.
+    ///
+    /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
+    /// collection of garbage generated by every member of the group.
+    pub fn guard(&self) -> Guard<'_> {
+        reclaim::enter(&self.collector)
+    }
+
+    /// Creates an empty map in this group.
+    ///
+    /// See also [`HashMap::new`].
+    pub fn map<K, V>(&self) -> HashMap<K, V>
+    where
+        K: 'static,
+        V: 'static,
+    {
+        self.map_with_capacity_and_hasher(0, crate::DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty map in this group with the specified `capacity`, using `hash_builder` to
+    /// hash the keys.
+    ///
+    /// See also [`HashMap::with_capacity_and_hasher`].
+    pub fn map_with_capacity_and_hasher<K, V, S>(
+        &self,
+        capacity: usize,
+        hash_builder: S,
+    ) -> HashMap<K, V, S>
+    where
+        K: 'static,
+        V: 'static,
+    {
+        HashMap::with_capacity_and_shared_collector(
+            capacity,
+            hash_builder,
+            Arc::clone(&self.collector),
+        )
+    }
+
+    /// Creates an empty set in this group.
+    ///
+    /// See also [`HashSet::new`].
+    pub fn set<T>(&self) -> HashSet<T>
+    where
+        T: 'static,
+    {
+        self.set_with_capacity_and_hasher(0, crate::DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty set in this group with the specified `capacity`, using `hash_builder` to
+    /// hash the values.
+    ///
+    /// See also [`HashSet::with_capacity_and_hasher`].
+    pub fn set_with_capacity_and_hasher<T, S>(
+        &self,
+        capacity: usize,
+        hash_builder: S,
+    ) -> HashSet<T, S>
+    where
+        T: 'static,
+    {
+        HashSet::with_capacity_and_shared_collector(
+            capacity,
+            hash_builder,
+            Arc::clone(&self.collector),
+        )
+    }
+}
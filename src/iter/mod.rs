@@ -1,7 +1,9 @@
 mod traverser;
 pub(crate) use traverser::NodeIter;
 
-use crate::reclaim::{Guard, Shared};
+use crate::reclaim::{self, Guard, Shared};
+use crate::HashMap;
+use std::fmt;
 use std::sync::atomic::Ordering;
 
 /// An iterator over a map's entries.
@@ -66,6 +68,97 @@ impl<'g, K, V> Iterator for Values<'g, K, V> {
     }
 }
 
+/// The number of bins a [`RefreshingIter`] visits between refreshes of its guard by default.
+const DEFAULT_REFRESH_INTERVAL: usize = 64;
+
+/// An iterator over a map's entries that periodically refreshes its guard.
+///
+/// See [`HashMap::iter_refreshing`] for details.
+pub struct RefreshingIter<'m, K, V, S> {
+    map: &'m HashMap<K, V, S>,
+    guard: Guard<'m>,
+    /// The cursor of the next bin to visit.
+    cursor: usize,
+    /// Set once the cursor has wrapped around to 0.
+    done: bool,
+    /// Entries of the last visited bin that have not yet been yielded.
+    buffer: Vec<(K, V)>,
+    bins_since_refresh: usize,
+    refresh_interval: usize,
+}
+
+impl<'m, K, V, S> RefreshingIter<'m, K, V, S> {
+    pub(crate) fn new(map: &'m HashMap<K, V, S>, guard: Guard<'m>) -> Self {
+        Self {
+            map,
+            guard,
+            cursor: 0,
+            done: false,
+            buffer: Vec::new(),
+            bins_since_refresh: 0,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
+    /// Sets the number of bins to visit between refreshes of the iterator's guard.
+    ///
+    /// Lower values allow garbage to be reclaimed sooner, but make iteration slower. An interval
+    /// of 0 is treated as 1.
+    #[must_use]
+    pub fn refresh_every(mut self, bins: usize) -> Self {
+        self.refresh_interval = bins.max(1);
+        self
+    }
+}
+
+impl<K, V, S> Iterator for RefreshingIter<'_, K, V, S>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffer.pop() {
+                return Some(entry);
+            }
+            if self.done {
+                return None;
+            }
+
+            if self.bins_since_refresh >= self.refresh_interval {
+                // no references into the map are held at this point, since all entries in
+                // `buffer` are owned
+                reclaim::refresh(&mut self.guard);
+                self.bins_since_refresh = 0;
+            }
+
+            let guard = &self.guard;
+            let (nodes, next) = self.map.bin_at_cursor(self.cursor, guard);
+            self.buffer.extend(nodes.map(|node| {
+                let value = node.value.load(Ordering::SeqCst, guard);
+                // safety: flurry does not drop or move until after guard drop
+                let value = unsafe { value.deref() };
+                (node.key.clone(), V::clone(value))
+            }));
+            self.bins_since_refresh += 1;
+            self.cursor = next;
+            self.done = next == 0;
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for RefreshingIter<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshingIter")
+            .field("cursor", &self.cursor)
+            .field("done", &self.done)
+            .field("buffered", &self.buffer.len())
+            .field("refresh_interval", &self.refresh_interval)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::HashMap;
@@ -116,4 +209,43 @@ mod tests {
             HashSet::from_iter(vec![&42, &84])
         );
     }
+
+    #[test]
+    fn iter_refreshing() {
+        let map = HashMap::<usize, usize>::new();
+        assert_eq!(map.iter_refreshing().count(), 0);
+
+        let guard = map.guard();
+        for i in 0..100 {
+            map.insert(i, i + 1, &guard);
+        }
+
+        let entries: HashSet<(usize, usize)> = map.iter_refreshing().refresh_every(1).collect();
+        assert_eq!(
+            entries,
+            (0..100).map(|i| (i, i + 1)).collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn iter_refreshing_across_resize() {
+        let map = HashMap::<usize, usize>::with_capacity(16);
+        let guard = map.guard();
+        for i in 0..8 {
+            map.insert(i, i, &guard);
+        }
+
+        let mut iter = map.iter_refreshing().refresh_every(1);
+        let mut seen: Vec<_> = iter.by_ref().take(3).map(|(k, _)| k).collect();
+
+        // force the table to grow a number of times while the iterator is suspended
+        for i in 8..1024 {
+            map.insert(i, i, &guard);
+        }
+        seen.extend(iter.map(|(k, _)| k));
+
+        for i in 0..8 {
+            assert_eq!(seen.iter().filter(|&&k| k == i).count(), 1, "key {}", i);
+        }
+    }
 }
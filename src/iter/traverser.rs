@@ -1,6 +1,7 @@
 use crate::node::{BinEntry, Node, TreeNode};
 use crate::raw::Table;
 use crate::reclaim::{Guard, Linked, Shared};
+use std::ops::Range;
 use std::sync::atomic::Ordering;
 
 #[derive(Debug)]
@@ -31,6 +32,18 @@ pub(crate) struct NodeIter<'g, K, V> {
 
 impl<'g, K, V> NodeIter<'g, K, V> {
     pub(crate) fn new(table: Shared<'g, Table<K, V>>, guard: &'g Guard<'_>) -> Self {
+        Self::for_bins(table, 0..usize::MAX, guard)
+    }
+
+    /// Iterate only over the nodes whose bin in `table` falls within `bins`.
+    ///
+    /// Since forwarded bins are followed into the next table, this also yields nodes that have
+    /// been moved to other bins in a larger table, as long as they originated from `bins`.
+    pub(crate) fn for_bins(
+        table: Shared<'g, Table<K, V>>,
+        bins: Range<usize>,
+        guard: &'g Guard<'_>,
+    ) -> Self {
         let (table, len) = if table.is_null() {
             (None, 0)
         } else {
@@ -46,9 +59,9 @@ impl<'g, K, V> NodeIter<'g, K, V> {
             spare: None,
             prev: None,
             base_size: len,
-            base_index: 0,
-            index: 0,
-            base_limit: len,
+            base_index: bins.start,
+            index: bins.start,
+            base_limit: bins.end.min(len),
             guard,
         }
     }
@@ -128,7 +141,7 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
                     BinEntry::TreeNode(ref tree_node) => {
                         e = Some(&tree_node.node);
                     }
-                    BinEntry::Moved | BinEntry::Tree(_) => {
+                    BinEntry::Moved | BinEntry::Tree(_) | BinEntry::Reservation(_) => {
                         unreachable!("Nodes can only point to Nodes or TreeNodes")
                     }
                 }
@@ -188,6 +201,8 @@ impl<'g, K, V> Iterator for NodeIter<'g, K, V> {
                             .node,
                         );
                     }
+                    // a reserved bin is empty
+                    BinEntry::Reservation(_) => {}
                     BinEntry::TreeNode(_) => unreachable!(
                         "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
                     ),
@@ -218,9 +233,9 @@ struct TableStack<'g, K, V> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::node::BinLock;
     use crate::raw::Table;
     use crate::reclaim::Atomic;
-    use parking_lot::Mutex;
 
     #[test]
     fn iter_new() {
@@ -253,7 +268,7 @@ mod tests {
                 key: 0usize,
                 value: Atomic::from(Shared::boxed(0usize, &collector)),
                 next: Atomic::null(),
-                lock: Mutex::new(()),
+                lock: BinLock::new(),
             }),
             &collector,
         ));
@@ -283,7 +298,7 @@ mod tests {
                 key: 0usize,
                 value: Atomic::from(Shared::boxed(0usize, &collector)),
                 next: Atomic::null(),
-                lock: Mutex::new(()),
+                lock: BinLock::new(),
             }),
             &collector,
         ));
@@ -31,6 +31,11 @@
 //! may accumulate much garbage which will take up valuable free memory on your system. Use your
 //! best judgement in deciding whether or not to re-use a `Guard`.
 //!
+//! Every `Guard` passed to a map must come from that map's collector, which the map checks (and
+//! panics on) at runtime. If you would rather not pass guards around at all, [`HashMap::scope`]
+//! gives you a [`HashMapScope`] that holds its own guard, and [`HashMap::get_cloned`] and friends
+//! pin the current thread only for the duration of a single call.
+//!
 //! # Consistency
 //!
 //! Retrieval operations (including [`get`](HashMap::get)) generally do not block, so may
@@ -96,13 +101,10 @@
 //! However, some other types of nodes exist: `BinEntry::TreeNode`s are arranged in balanced trees
 //! instead of linear lists. Bins of type `BinEntry::Tree` hold the roots of sets of `BinEntry::TreeNode`s.
 //! Some nodes are of type `BinEntry::Moved`; these "forwarding nodes" are placed at the
-//! heads of bins during resizing. The Java version also has other special node types, but these
-//! have not yet been implemented in this port. These special nodes are all either uncommon or
+//! heads of bins during resizing. `BinEntry::Reservation`s are placeholders that lock empty bins
+//! for the duration of a `HashMap::transaction`. These special nodes are all either uncommon or
 //! transient.
 //!
-/*
-//! TODO: TreeNodes, ReservationNodes
-*/
 //! The table is lazily initialized to a power-of-two size upon the first insertion.  Each bin in
 //! the table normally contains a list of nodes (most often, the list has only zero or one
 //! `BinEntry`). Table accesses require atomic reads, writes, and CASes.
@@ -249,13 +251,21 @@
 #![warn(rust_2018_idioms)]
 #![allow(clippy::cognitive_complexity)]
 
+mod equivalent;
+mod group;
 mod map;
 mod map_ref;
+mod map_scope;
 mod node;
 mod raw;
 mod reclaim;
 mod set;
 mod set_ref;
+mod single_flight;
+mod sync;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
 
 #[cfg(feature = "rayon")]
 mod rayon_impls;
@@ -263,13 +273,40 @@ mod rayon_impls;
 #[cfg(feature = "serde")]
 mod serde_impls;
 
+#[cfg(feature = "persist")]
+mod persist;
+
+/// Types for recording the changes made to a map.
+pub mod feed;
+
 /// Iterator types.
 pub mod iter;
 
-pub use map::{HashMap, TryInsertError};
+/// Strategies for generating maps and sets in property tests.
+#[cfg(feature = "proptest")]
+pub mod proptest;
+
+/// Zero-copy archives of maps and sets.
+#[cfg(feature = "rkyv")]
+pub mod rkyv;
+
+/// Options for deserializing maps and sets.
+#[cfg(feature = "serde")]
+pub mod serde;
+
+/// Types for subscribing to changes to keys.
+pub mod watch;
+
+pub use equivalent::{Comparable, Equivalent};
+pub use group::MapGroup;
+pub use map::{HashMap, InvariantViolation, KeyLock, TransactionEntry, TryInsertError};
 pub use map_ref::HashMapRef;
+pub use map_scope::HashMapScope;
+#[cfg(feature = "persist")]
+pub use persist::DurableHashMap;
 pub use set::HashSet;
 pub use set_ref::HashSetRef;
+pub use single_flight::SingleFlight;
 
 pub use seize::Guard;
 
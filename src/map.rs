@@ -1,14 +1,19 @@
 use seize::Linked;
 
+use crate::feed::{ChangeEvent, ChangeFeed, ChangeKind, ChangeSink};
 use crate::iter::*;
 use crate::node::*;
 use crate::raw::*;
-use crate::reclaim::{Atomic, Collector, Guard, RetireShared, Shared};
-use std::borrow::Borrow;
+use crate::reclaim::{self, Atomic, Collector, Guard, RetireShared, Shared};
+use crate::sync::{self, AtomicIsize, Mutex, MutexGuard};
+use crate::watch::{Receiver, Watchers};
+use crate::Comparable;
 use std::error::Error;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::{BuildHasher, Hash};
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 const ISIZE_BITS: usize = core::mem::size_of::<isize>() * 8;
 
@@ -36,8 +41,8 @@ const TREEIFY_THRESHOLD: usize = 8;
 const UNTREEIFY_THRESHOLD: usize = 6;
 
 /// The smallest table capacity for which bins may be treeified. (Otherwise the
-/// table is resized if too many nodes in a bin.) The value should be at least 4
-/// * TREEIFY_THRESHOLD to avoid conflicts between resizing and treeification
+/// table is resized if too many nodes in a bin.) The value should be at least
+/// 4 * TREEIFY_THRESHOLD to avoid conflicts between resizing and treeification
 /// thresholds.
 const MIN_TREEIFY_CAPACITY: usize = 64;
 
@@ -48,6 +53,9 @@ const MIN_TREEIFY_CAPACITY: usize = 64;
 /// `DEFAULT_CAPACITY`.
 const MIN_TRANSFER_STRIDE: isize = 16;
 
+/// The number of keys [`HashMap::get_many`] hashes and prefetches before resolving any of them.
+const GET_MANY_CHUNK: usize = 16;
+
 /// The number of bits used for generation stamp in `size_ctl`.
 /// Must be at least 6 for 32bit arrays.
 const RESIZE_STAMP_BITS: usize = ISIZE_BITS / 2;
@@ -128,13 +136,48 @@ pub struct HashMap<K, V, S = crate::DefaultHashBuilder> {
     /// We avoid that by checking that every external guard that is passed in is associated with
     /// the `Collector` that was specified when the map was created (which may be the global
     /// collector).
-    collector: Collector,
+    ///
+    /// The collector is reference-counted so that maps and sets created from the same
+    /// [`MapGroup`](crate::MapGroup) can share it, and thereby accept each other's guards.
+    collector: Arc<Collector>,
+
+    /// Whether `collector` is shared with a `MapGroup`. The members of a group have `'static`
+    /// keys and values, so clones of a member can share the collector and stay in the group.
+    grouped: bool,
 
     build_hasher: S,
+
+    /// Subscriptions to changes to individual keys, see [`HashMap::subscribe`].
+    watchers: Watchers,
+
+    /// Where changes to the map are recorded, if anywhere. See [`HashMap::with_change_sink`].
+    change_feed: Option<ChangeFeed<K, V>>,
+
+    /// The bins that are locked by [`KeyLock`]s.
+    key_locks: Mutex<KeyLocks<K, V>>,
+}
+
+/// A change that was sequenced for the change feed while its bin was locked, and that is recorded
+/// once the bin has been unlocked (or right away, by a [`KeyLock`]).
+struct SequencedChange<'g, K, V> {
+    seq: u64,
+    kind: ChangeKind,
+    key: &'g K,
+    old: Option<&'g V>,
+    new: Option<&'g V>,
+}
+
+/// An entry waiting to be inserted by [`HashMap::insert_many`].
+struct BatchEntry<'g, K, V> {
+    /// The position of the entry in the input, so its previous value can be reported there.
+    index: usize,
+    hash: u64,
+    key: K,
+    value: Shared<'g, V>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
-enum PutResult<'a, T> {
+pub(crate) enum PutResult<'a, T> {
     Inserted {
         new: &'a T,
     },
@@ -149,7 +192,7 @@ enum PutResult<'a, T> {
 }
 
 impl<'a, T> PutResult<'a, T> {
-    fn before(&self) -> Option<&'a T> {
+    pub(crate) fn before(&self) -> Option<&'a T> {
         match *self {
             PutResult::Inserted { .. } => None,
             PutResult::Replaced { old, .. } => Some(old),
@@ -199,6 +242,517 @@ where
     }
 }
 
+/// The error type for the [`HashMap::validate`] method, describing the first broken invariant it
+/// found.
+///
+/// Bins are identified by their index in the table they are in. While the map is being resized,
+/// that may be either the current table or the one it is being resized into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvariantViolation {
+    /// An entry is in a bin that its hash does not map to.
+    MisplacedEntry {
+        /// The index of the bin.
+        bin: usize,
+        /// The hash of the entry.
+        hash: u64,
+    },
+    /// A bin holds two entries with equal keys.
+    DuplicateKey {
+        /// The index of the bin.
+        bin: usize,
+    },
+    /// The red-black tree of a tree bin is malformed.
+    MalformedTree {
+        /// The index of the bin.
+        bin: usize,
+        /// What is wrong with the tree.
+        reason: &'static str,
+    },
+    /// The `prev` and `next` links of the nodes of a tree bin are inconsistent, or do not link
+    /// the same nodes as its tree.
+    MalformedTreeList {
+        /// The index of the bin.
+        bin: usize,
+        /// What is wrong with the links.
+        reason: &'static str,
+    },
+    /// The map's count of its entries disagrees with the number of entries in its bins.
+    CountMismatch {
+        /// The map's count.
+        count: isize,
+        /// The number of entries in the bins.
+        entries: usize,
+    },
+    /// The map's `size_ctl`, which holds the size at which the table grows, or the state of an
+    /// ongoing initialization or resize of the table, does not fit the table.
+    SizeCtl {
+        /// The value of `size_ctl`.
+        size_ctl: isize,
+        /// The number of bins in the table, or 0 if it has not been allocated.
+        bins: usize,
+    },
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            InvariantViolation::MisplacedEntry { bin, hash } => {
+                write!(f, "entry with hash {:#x} is in bin {}", hash, bin)
+            }
+            InvariantViolation::DuplicateKey { bin } => {
+                write!(f, "bin {} holds two entries with equal keys", bin)
+            }
+            InvariantViolation::MalformedTree { bin, reason } => {
+                write!(f, "the tree of bin {} is malformed: {}", bin, reason)
+            }
+            InvariantViolation::MalformedTreeList { bin, reason } => {
+                write!(
+                    f,
+                    "the node list of tree bin {} is malformed: {}",
+                    bin, reason
+                )
+            }
+            InvariantViolation::CountMismatch { count, entries } => write!(
+                f,
+                "the map counts {} entries, but its bins hold {}",
+                count, entries
+            ),
+            InvariantViolation::SizeCtl { size_ctl, bins } => write!(
+                f,
+                "size_ctl is {}, which does not fit a table of {} bins",
+                size_ctl, bins
+            ),
+        }
+    }
+}
+
+impl Error for InvariantViolation {}
+
+/// A view of one of the entries involved in a [`HashMap::transaction`].
+///
+/// Changes made through the view only take effect if the transaction commits.
+pub struct TransactionEntry<'t, K, V> {
+    key: &'t K,
+    hash: u64,
+    bini: usize,
+    node: Option<&'t Node<K, V>>,
+    current: Option<&'t V>,
+    pending: Option<Option<V>>,
+}
+
+impl<K, V> TransactionEntry<'_, K, V> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        self.key
+    }
+
+    /// Returns the value of this entry, including any change made in this transaction.
+    pub fn get(&self) -> Option<&V> {
+        match self.pending {
+            Some(ref pending) => pending.as_ref(),
+            None => self.current,
+        }
+    }
+
+    /// Sets the value of this entry, inserting it into the map if it is absent.
+    pub fn insert(&mut self, value: V) {
+        self.pending = Some(Some(value));
+    }
+
+    /// Removes this entry from the map.
+    pub fn remove(&mut self) {
+        self.pending = Some(None);
+    }
+}
+
+impl<K, V> Debug for TransactionEntry<'_, K, V>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransactionEntry")
+            .field("key", self.key)
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
+/// The bins locked by a [`HashMap::transaction`], in ascending order of their index.
+///
+/// Dropping this unlocks the bins. Reservations that are still in the table are emptied first,
+/// since a reservation must never outlive its lock.
+struct TransactionLocks<'g, 'c, K, V> {
+    table: &'g Table<K, V>,
+    bins: Vec<LockedBin<'g, K, V>>,
+    guard: &'g Guard<'c>,
+}
+
+/// A bin locked by a [`HashMap::transaction`] or a [`KeyLock`].
+struct LockedBin<'g, K, V> {
+    index: usize,
+    bin: Shared<'g, BinEntry<K, V>>,
+    /// The guard of the bin's lock, or `None` if the lock is held by a [`KeyLock`]. Those leak
+    /// their guards, so that a resize can move the lock into the next table; see [`KeyLocks`].
+    lock: Option<MutexGuard<'g, ()>>,
+}
+
+impl<K, V> LockedBin<'_, K, V> {
+    /// Unlocks the bin.
+    fn unlock(self) {
+        match self.lock {
+            Some(lock) => drop(lock),
+            // safety: the `KeyLock` that holds the lock is giving it up, and we read the bin
+            // under our guard
+            None => unsafe { unlock_leaked(self.bin) },
+        }
+    }
+}
+
+/// Unlocks the lock of `bin`, whose guard was leaked for a [`KeyLock`].
+///
+/// parking_lot's mutexes do not care which thread unlocks them, so this may happen on a different
+/// thread than the one that locked the bin.
+///
+/// # Safety
+///
+/// The lock must be held for a `KeyLock` that no longer needs it, and `bin` must be valid.
+unsafe fn unlock_leaked<K, V>(bin: Shared<'_, BinEntry<K, V>>) {
+    bin.deref().bin_lock().force_unlock();
+}
+
+/// A bin that is about to be stored in a table, along with the guard of its lock.
+type NewBin<'g, K, V> = (Shared<'g, BinEntry<K, V>>, MutexGuard<'g, ()>);
+
+/// A change to an entry of a locked bin, prepared by `prepare_bin` so that committing it cannot
+/// panic.
+enum PreparedChange<'g, K, V> {
+    /// Swaps a new value into a node of the bin.
+    Replace(&'g Node<K, V>, Shared<'g, V>),
+    /// Unlinks the node `p` from the bin.
+    Remove(Shared<'g, BinEntry<K, V>>, &'g Node<K, V>),
+    /// Links a new `BinEntry::Node`, which holds the value, into the bin. The node is null if the
+    /// bin is rebuilt, since the rebuilt bin already holds the key.
+    Insert(Shared<'g, BinEntry<K, V>>, Shared<'g, V>),
+}
+
+/// The changes to a locked bin, prepared by `prepare_bin`.
+///
+/// Everything that committing the changes needs is allocated here, and keys are cloned here, so
+/// that a panic leaves the map unchanged. Dropping the changes without committing them frees
+/// what has not been committed.
+struct PreparedBin<'g, K, V> {
+    changes: Vec<PreparedChange<'g, K, V>>,
+    /// The bin that replaces the locked bin if it is rebuilt, or else a reservation to replace it
+    /// with should it become empty. Holds the lock of the bin.
+    replacement: Option<NewBin<'g, K, V>>,
+    /// Whether the bin is replaced by `replacement` rather than changed in place. This is the
+    /// case for tree bins that gain or lose nodes.
+    rebuilt: bool,
+}
+
+impl<K, V> Drop for PreparedBin<'_, K, V> {
+    fn drop(&mut self) {
+        // safety: nothing that is left has been shared with other threads
+        unsafe {
+            for change in self.changes.drain(..) {
+                match change {
+                    PreparedChange::Replace(_, value) => drop(value.into_box()),
+                    PreparedChange::Remove(..) => {}
+                    PreparedChange::Insert(node, value) => {
+                        if !node.is_null() {
+                            drop(node.into_box());
+                        }
+                        drop(value.into_box());
+                    }
+                }
+            }
+            if let Some((bin, lock)) = self.replacement.take() {
+                drop(lock);
+                // the values of a rebuilt bin are either freed above, or still in the locked bin
+                match bin.into_box().value {
+                    BinEntry::Node(node) => {
+                        let guard = Guard::unprotected();
+                        let mut p = node.next.load(Ordering::Relaxed, &guard);
+                        while !p.is_null() {
+                            let next = p
+                                .deref()
+                                .as_node()
+                                .unwrap()
+                                .next
+                                .load(Ordering::Relaxed, &guard);
+                            drop(p.into_box());
+                            p = next;
+                        }
+                    }
+                    BinEntry::Tree(mut tree_bin) => tree_bin.drop_fields(false),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl<K, V> TransactionLocks<'_, '_, K, V> {
+    /// Unlocks the bins. This is a no-op if they have already been unlocked.
+    fn release(&mut self) {
+        for locked in self.bins.drain(..) {
+            let (bini, bin) = (locked.index, locked.bin);
+            // safety: we read the bin under our guard, so it has not been dropped.
+            if let BinEntry::Reservation(_) = **unsafe { bin.deref() } {
+                if self.table.bin(bini, self.guard) == bin {
+                    // the transaction did not put anything into the bin
+                    self.table.store_bin(bini, Shared::null());
+                }
+                locked.unlock();
+                // safety: the reservation has been replaced, so no thread that executes after
+                // this can get a reference to it. threads that read it before did so while
+                // holding a guard, so it will not be dropped until they release it.
+                unsafe { self.guard.retire_shared(bin) };
+            } else {
+                locked.unlock();
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for TransactionLocks<'_, '_, K, V> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// A lock on a single key of a [`HashMap`], returned by [`HashMap::lock_key`].
+///
+/// While the lock is held, other updates to the key (and to any other key in the same bin) wait
+/// for it to be released, while lookups proceed as usual. The key can be read and updated through
+/// the lock. Dropping it releases the lock.
+pub struct KeyLock<'g, K, V, S = crate::DefaultHashBuilder> {
+    map: &'g HashMap<K, V, S>,
+    key: K,
+    hash: u64,
+    /// The id of the locked bin in the map's [`KeyLocks`].
+    id: usize,
+    guard: &'g Guard<'g>,
+    changed: bool,
+    /// Checks the invariants of the key's bins once the lock is released. `Drop` cannot require
+    /// the bounds that the check needs, so it is stored when the lock is taken.
+    #[cfg(feature = "debug-invariants")]
+    invariants: fn(&HashMap<K, V, S>, Option<u64>, &Guard<'_>),
+}
+
+/// The bins that are locked by [`KeyLock`]s.
+///
+/// A `KeyLock` may be held for a long time, so a resize does not wait for it. Instead, the
+/// `KeyLock` leaks the guard of its bin's lock and registers the bin here, and `transfer` moves
+/// the lock along with the bin: it locks the bin of the next table that the key ends up in on
+/// behalf of the `KeyLock`, and unlocks the old one. The bin's lock is marked as well (see
+/// `BinLock`), so `transfer` only looks here for bins that are key-locked. A `KeyLock` only uses
+/// its bin while it holds the lock on this registry, so the two never modify the bin at the same
+/// time.
+struct KeyLocks<K, V> {
+    next_id: usize,
+    held: Vec<HeldBin<K, V>>,
+}
+
+impl<K, V> Default for KeyLocks<K, V> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            held: Vec::new(),
+        }
+    }
+}
+
+/// A bin that is locked by a [`KeyLock`].
+struct HeldBin<K, V> {
+    id: usize,
+    /// The hash of the locked key.
+    hash: u64,
+    table: Atomic<Table<K, V>>,
+    index: usize,
+    bin: Atomic<BinEntry<K, V>>,
+}
+
+impl<K, V> KeyLocks<K, V> {
+    /// Registers a bin locked by a new [`KeyLock`], and returns its id.
+    fn register(
+        &mut self,
+        hash: u64,
+        table: Shared<'_, Table<K, V>>,
+        index: usize,
+        bin: Shared<'_, BinEntry<K, V>>,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.held.push(HeldBin {
+            id,
+            hash,
+            table: Atomic::from(table),
+            index,
+            bin: Atomic::from(bin),
+        });
+        id
+    }
+
+    fn position(&self, id: usize) -> usize {
+        self.held
+            .iter()
+            .position(|held| held.id == id)
+            .expect("a KeyLock is registered until it is dropped")
+    }
+}
+
+/// How [`HashMap::transfer`] holds a bin while it moves it into the next table.
+enum TransferLock<'g, K, V> {
+    /// The bin's own lock.
+    Bin(MutexGuard<'g, ()>),
+    /// The lock on the map's [`KeyLocks`], and the position of the bin, which is held by a
+    /// [`KeyLock`].
+    KeyLock(MutexGuard<'g, KeyLocks<K, V>>, usize),
+}
+
+impl<'g, K, V> HeldBin<K, V> {
+    /// Returns the table and the locked bin.
+    ///
+    /// The caller must hold the lock on the [`KeyLocks`], so that the bin is not moved by a
+    /// resize while they use it.
+    fn get(&self, guard: &'g Guard<'_>) -> (&'g Table<K, V>, LockedBin<'g, K, V>) {
+        let table = self.table.load(Ordering::SeqCst, guard);
+        // safety: the table held the bin when it was stored, while we held our guard. it is only
+        // retired once it has been replaced, which, as long as the `KeyLock` exists, happens
+        // after that, so it will not be dropped until after we drop our guard.
+        let table = unsafe { table.deref() };
+        let locked = LockedBin {
+            index: self.index,
+            bin: self.bin.load(Ordering::SeqCst, guard),
+            lock: None,
+        };
+        (table, locked)
+    }
+}
+
+impl<'g, K, V, S> KeyLock<'g, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    /// Returns the locked key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the value of the locked key, if it is present.
+    pub fn get(&self) -> Option<&'g V> {
+        let key_locks = self.map.key_locks.lock();
+        let (t, locked) = key_locks.held[key_locks.position(self.id)].get(self.guard);
+        self.value(t, &locked)
+    }
+
+    /// Sets the value of the locked key, returning the old value if it was present.
+    pub fn insert(&mut self, value: V) -> Option<&'g V> {
+        self.update(Some(value))
+    }
+
+    /// Removes the locked key from the map, returning its value if it was present.
+    pub fn remove(&mut self) -> Option<&'g V> {
+        self.update(None)
+    }
+
+    fn node(&self, t: &'g Table<K, V>, locked: &LockedBin<'g, K, V>) -> Option<&'g Node<K, V>> {
+        HashMap::<K, V, S>::find_node(t, locked.bin, self.hash, &self.key, self.guard)
+    }
+
+    fn value(&self, t: &'g Table<K, V>, locked: &LockedBin<'g, K, V>) -> Option<&'g V> {
+        self.node(t, locked).map(|node| {
+            let value = node.value.load(Ordering::SeqCst, self.guard);
+            // safety: the value was read under our guard, so it cannot be dropped until after we
+            // drop our guard.
+            &**unsafe { value.deref() }
+        })
+    }
+
+    fn update(&mut self, value: Option<V>) -> Option<&'g V> {
+        let (map, guard) = (self.map, self.guard);
+        let key_locks = map.key_locks.lock();
+        let k = key_locks.position(self.id);
+        let (t, mut locked) = key_locks.held[k].get(guard);
+        let node = self.node(t, &locked);
+        let old = self.value(t, &locked);
+        if node.is_none() && value.is_none() {
+            return None;
+        }
+        let entry = TransactionEntry {
+            key: &self.key,
+            hash: self.hash,
+            bini: locked.index,
+            node,
+            current: old,
+            pending: Some(value),
+        };
+        let prepared = map.prepare_bin(&locked, std::iter::once(entry), guard);
+        let mut changes = Vec::with_capacity(1);
+        let (delta, bin_count, _) = map.commit_bin(t, &mut locked, prepared, &mut changes, guard);
+        key_locks.held[k].bin.store(locked.bin, Ordering::SeqCst);
+        drop(key_locks);
+
+        // the change is recorded right away rather than when the lock is released, since the lock
+        // may be held for a long time (or never be released), and the feed must not have gaps
+        for change in changes {
+            map.record(change);
+        }
+
+        // a resize moves our lock along with the bin, so growing the map may start one. the bin
+        // is never treeified, though, since that would have to wait for our own lock.
+        if delta > 0 {
+            map.add_count(delta, Some(bin_count), guard);
+        } else if delta < 0 {
+            map.add_count(delta, None, guard);
+        }
+        self.changed = true;
+        old
+    }
+}
+
+impl<K, V, S> Drop for KeyLock<'_, K, V, S> {
+    fn drop(&mut self) {
+        let mut key_locks = self.map.key_locks.lock();
+        let k = key_locks.position(self.id);
+        let held = key_locks.held.swap_remove(k);
+        let (table, locked) = held.get(self.guard);
+        // safety: the bin is locked, so it is not retired before the guard is dropped
+        unsafe { locked.bin.deref() }.bin_lock().unmark_key_locked();
+        drop(TransactionLocks {
+            table,
+            bins: vec![locked],
+            guard: self.guard,
+        });
+        drop(key_locks);
+
+        if self.changed {
+            self.map.watchers.notify(self.hash);
+            #[cfg(feature = "debug-invariants")]
+            (self.invariants)(self.map, Some(self.hash), self.guard);
+        }
+    }
+}
+
+impl<K, V, S> Debug for KeyLock<'_, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord + Debug,
+    V: Sync + Send + Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyLock")
+            .field("key", &self.key)
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
 // ===
 // the following methods only see Ks and Vs if there have been inserts.
 // modifications to the map are all guarded by thread-safety bounds (Send + Sync ).
@@ -282,7 +836,11 @@ impl<K, V, S> HashMap<K, V, S> {
             count: AtomicIsize::new(0),
             size_ctl: AtomicIsize::new(0),
             build_hasher: hash_builder,
-            collector: Collector::new(),
+            collector: Arc::new(reclaim::collector()),
+            grouped: false,
+            watchers: Watchers::default(),
+            change_feed: None,
+            key_locks: Mutex::new(KeyLocks::default()),
         }
     }
 
@@ -324,18 +882,184 @@ impl<K, V, S> HashMap<K, V, S> {
     ///
     /// Note that _all_ `Guard` references provided to access the returned map _must_ be
     /// constructed using guards produced by `collector`.
+    ///
+    /// To share one collector between several maps and sets, use a [`MapGroup`](crate::MapGroup)
+    /// instead.
     #[must_use]
     pub fn with_collector(mut self, collector: Collector) -> Self {
-        self.collector = collector;
+        self.collector = Arc::new(collector);
+        self.grouped = false;
+        self
+    }
+
+    /// Records every change to this map in `sink`.
+    ///
+    /// Every successful insert, replace and remove, and every entry removed by
+    /// [`HashMap::clear`], is passed to `sink` as a [`ChangeEvent`] that is numbered with its
+    /// position in the map's history. A follower can replay the events with
+    /// [`HashMap::apply_event`] to build a copy of the map. Clones of the map do not inherit the
+    /// sink.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::feed::{ChangeEvent, ChangeKind};
+    /// use flurry::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let map = HashMap::new().with_change_sink({
+    ///     let log = Arc::clone(&log);
+    ///     move |event: ChangeEvent<&u32, &u32>| log.lock().unwrap().push(event.cloned())
+    /// });
+    /// map.pin().insert(1, 10);
+    /// map.pin().insert(1, 20);
+    /// map.pin().remove(&1);
+    ///
+    /// let kinds: Vec<_> = log.lock().unwrap().iter().map(|e| (e.seq, e.kind)).collect();
+    /// assert_eq!(
+    ///     kinds,
+    ///     [(0, ChangeKind::Insert), (1, ChangeKind::Replace), (2, ChangeKind::Remove)]
+    /// );
+    ///
+    /// // replay the log into a follower
+    /// let follower = HashMap::new();
+    /// for event in log.lock().unwrap().drain(..) {
+    ///     follower.apply_event(event, &follower.guard());
+    /// }
+    /// assert_eq!(map, follower);
+    /// ```
+    #[must_use]
+    pub fn with_change_sink<T>(mut self, sink: T) -> Self
+    where
+        T: ChangeSink<K, V> + 'static,
+    {
+        self.change_feed = Some(ChangeFeed::new(Box::new(sink)));
         self
     }
 
+    /// Like [`HashMap::with_collector`], but shares `collector` with other maps.
+    ///
+    /// The table is allocated only after the collector has been set, so that it is linked to
+    /// `collector` rather than to the map's default collector.
+    pub(crate) fn with_capacity_and_shared_collector(
+        capacity: usize,
+        hash_builder: S,
+        collector: Arc<Collector>,
+    ) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.collector = collector;
+        map.grouped = true;
+        if capacity != 0 {
+            map.presize(capacity);
+        }
+        map
+    }
+
     /// Pin a `Guard` for use with this map.
     ///
     /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
     /// collection of garbage generated by the map.
     pub fn guard(&self) -> Guard<'_> {
-        self.collector.enter()
+        reclaim::enter(&self.collector)
+    }
+
+    /// Assigns a sequence number to a change, if the map has a change feed.
+    ///
+    /// This must be called while holding the lock of the bin the change is made to. See
+    /// `ChangeFeed::sequence`.
+    #[inline]
+    fn sequence(&self) -> Option<u64> {
+        self.change_feed.as_ref().map(ChangeFeed::sequence)
+    }
+
+    /// Records a sequenced change in the change feed, after its bin has been unlocked (or, for a
+    /// [`KeyLock`], right after the change).
+    fn record(&self, change: SequencedChange<'_, K, V>) {
+        if let Some(ref feed) = self.change_feed {
+            feed.record(ChangeEvent {
+                seq: change.seq,
+                kind: change.kind,
+                key: change.key,
+                old: change.old,
+                new: change.new,
+            });
+        }
+    }
+
+    /// Locks a node that is about to be put into an empty bin, if the map has a change feed.
+    ///
+    /// The insert of the node can then be sequenced once the node is in the bin, but before any
+    /// later change to its key.
+    fn lock_for_feed<'g>(&'g self, node: Shared<'g, BinEntry<K, V>>) -> Option<MutexGuard<'g, ()>> {
+        self.change_feed.as_ref()?;
+        // safety: the node has not been retired, since it has not even been shared yet
+        match **unsafe { node.deref() } {
+            BinEntry::Node(ref node) => Some(node.lock.lock()),
+            _ => unreachable!("only nodes are put into empty bins"),
+        }
+    }
+
+    /// Sequences the removal of every entry of a locked bin by `clear`, if the map has a change
+    /// feed. `first` is the first node of the bin.
+    fn sequence_clear<'g>(
+        &'g self,
+        first: Shared<'g, BinEntry<K, V>>,
+        guard: &'g Guard<'_>,
+    ) -> Vec<SequencedChange<'g, K, V>> {
+        let mut changes = Vec::new();
+        if self.change_feed.is_none() {
+            return changes;
+        }
+        let mut p = first;
+        while !p.is_null() {
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            let node = match **unsafe { p.deref() } {
+                BinEntry::Node(ref node) => node,
+                BinEntry::TreeNode(ref tree_node) => &tree_node.node,
+                _ => unreachable!("bins only ever link Nodes or TreeNodes"),
+            };
+            let value = node.value.load(Ordering::SeqCst, guard);
+            changes.push(SequencedChange {
+                seq: self.sequence().expect("the map has a change feed"),
+                kind: ChangeKind::Clear,
+                key: &node.key,
+                // safety: the value is present while we hold the bin lock, and cannot be dropped
+                // until after we drop our guard.
+                old: Some(unsafe { value.deref() }),
+                new: None,
+            });
+            p = node.next.load(Ordering::SeqCst, guard);
+        }
+        changes
+    }
+
+    /// Returns the key of the node in the list starting at `first` that holds `value`.
+    ///
+    /// This is used to find the key of a node that was just put into a locked tree bin.
+    fn key_with_value<'g>(
+        first: Shared<'g, BinEntry<K, V>>,
+        value: Shared<'g, V>,
+        guard: &'g Guard<'_>,
+    ) -> &'g K
+    where
+        K: 'g,
+        V: 'g,
+    {
+        let mut p = first;
+        loop {
+            assert!(!p.is_null(), "the value is in the bin");
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            let node = match **unsafe { p.deref() } {
+                BinEntry::Node(ref node) => node,
+                BinEntry::TreeNode(ref tree_node) => &tree_node.node,
+                _ => unreachable!("bins only ever link Nodes or TreeNodes"),
+            };
+            if node.value.load(Ordering::SeqCst, guard) == value {
+                return &node.key;
+            }
+            p = node.next.load(Ordering::SeqCst, guard);
+        }
     }
 
     #[inline]
@@ -410,6 +1134,12 @@ impl<K, V, S> HashMap<K, V, S> {
     /// The iterator element type is `(&'g K, &'g V)`.
     pub fn iter<'g>(&'g self, guard: &'g Guard<'_>) -> Iter<'g, K, V> {
         self.check_guard(guard);
+        self.iter_unchecked(guard)
+    }
+
+    /// Like [`HashMap::iter`], but without checking that `guard` belongs to this map's
+    /// collector. Callers must guarantee that it does.
+    pub(crate) fn iter_unchecked<'g>(&'g self, guard: &'g Guard<'_>) -> Iter<'g, K, V> {
         let table = self.table.load(Ordering::SeqCst, guard);
         let node_iter = NodeIter::new(table, guard);
         Iter { node_iter, guard }
@@ -420,6 +1150,12 @@ impl<K, V, S> HashMap<K, V, S> {
     /// The iterator element type is `&'g K`.
     pub fn keys<'g>(&'g self, guard: &'g Guard<'_>) -> Keys<'g, K, V> {
         self.check_guard(guard);
+        self.keys_unchecked(guard)
+    }
+
+    /// Like [`HashMap::keys`], but without checking that `guard` belongs to this map's
+    /// collector. Callers must guarantee that it does.
+    pub(crate) fn keys_unchecked<'g>(&'g self, guard: &'g Guard<'_>) -> Keys<'g, K, V> {
         let table = self.table.load(Ordering::SeqCst, guard);
         let node_iter = NodeIter::new(table, guard);
         Keys { node_iter }
@@ -430,26 +1166,154 @@ impl<K, V, S> HashMap<K, V, S> {
     /// The iterator element type is `&'g V`.
     pub fn values<'g>(&'g self, guard: &'g Guard<'_>) -> Values<'g, K, V> {
         self.check_guard(guard);
+        self.values_unchecked(guard)
+    }
+
+    /// Like [`HashMap::values`], but without checking that `guard` belongs to this map's
+    /// collector. Callers must guarantee that it does.
+    pub(crate) fn values_unchecked<'g>(&'g self, guard: &'g Guard<'_>) -> Values<'g, K, V> {
         let table = self.table.load(Ordering::SeqCst, guard);
         let node_iter = NodeIter::new(table, guard);
         Values { node_iter, guard }
     }
 
-    fn init_table<'g>(&'g self, guard: &'g Guard<'_>) -> Shared<'g, Table<K, V>> {
-        loop {
-            let table = self.table.load(Ordering::SeqCst, guard);
-            // safety: we loaded the table while the thread was marked as active.
-            // table won't be deallocated until the guard is dropped at the earliest.
-            if !table.is_null() && !unsafe { table.deref() }.is_empty() {
-                break table;
-            }
-            // try to allocate the table
-            let mut sc = self.size_ctl.load(Ordering::SeqCst);
-            if sc < 0 {
-                // we lost the initialization race; just spin
-                std::thread::yield_now();
-                continue;
-            }
+    /// An iterator visiting all key-value pairs in arbitrary order, yielding clones of each entry.
+    ///
+    /// Unlike [`HashMap::iter`], this iterator holds its own `Guard`, which it refreshes
+    /// every so often between bins (see [`RefreshingIter::refresh_every`]). This allows values
+    /// that are removed or replaced while a long scan is in progress to be reclaimed before the
+    /// scan completes, at the cost of having to clone every entry.
+    ///
+    /// Since the iterator does not hold on to a single table for the whole scan, it re-reads the
+    /// current table for every bin it visits. Bins are visited in reverse-binary order, so that
+    /// even if the table is resized during iteration, every entry that is present for the
+    /// entire duration of the iteration is yielded at least once. Entries inserted or removed
+    /// during iteration may or may not be yielded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..100).map(|i| (i, i * 2)).collect();
+    /// let mut entries: Vec<_> = map.iter_refreshing().refresh_every(4).collect();
+    /// entries.sort();
+    /// assert_eq!(entries, (0..100).map(|i| (i, i * 2)).collect::<Vec<_>>());
+    /// ```
+    pub fn iter_refreshing(&self) -> RefreshingIter<'_, K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        RefreshingIter::new(self, self.guard())
+    }
+
+    /// Incrementally iterates over the map's entries, starting at the bin that `cursor` points
+    /// to.
+    ///
+    /// Visits whole bins until at least `count` entries have been collected (or the map has been
+    /// fully walked), and returns those entries along with the cursor to pass to the next call.
+    /// A walk is started by passing a cursor of 0, and is complete when the returned cursor is 0.
+    /// Since a bin is never split across calls, more than `count` entries may be returned.
+    ///
+    /// No state is kept between calls, so the `Guard` need not be held in between. The cursor
+    /// encodes bins in reverse-binary order, as in Redis' `SCAN`: even if the table is resized
+    /// between calls, every entry that is present for the entire walk is returned at least once.
+    /// Entries inserted or removed during the walk may or may not be returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// let mut keys = Vec::new();
+    /// let mut cursor = 0;
+    /// loop {
+    ///     let guard = map.guard();
+    ///     let (next, batch) = map.scan(cursor, 10, &guard);
+    ///     keys.extend(batch.into_iter().map(|(&k, _)| k));
+    ///     cursor = next;
+    ///     if cursor == 0 {
+    ///         break;
+    ///     }
+    /// }
+    /// keys.sort();
+    /// assert_eq!(keys, (0..100).collect::<Vec<_>>());
+    /// ```
+    pub fn scan<'g>(
+        &'g self,
+        mut cursor: usize,
+        count: usize,
+        guard: &'g Guard<'_>,
+    ) -> (usize, Vec<(&'g K, &'g V)>) {
+        self.check_guard(guard);
+        // a `count` of `usize::MAX` asks for everything, so only reserve as much as there is
+        let mut entries = Vec::with_capacity(count.min(self.len()));
+        loop {
+            let (nodes, next) = self.bin_at_cursor(cursor, guard);
+            entries.extend(nodes.map(|node| {
+                let value = node.value.load(Ordering::SeqCst, guard);
+                // safety: the lifetime of the reference is bound to the guard
+                // supplied which means that the memory will not be modified
+                // until at least after the guard goes out of scope
+                (&node.key, &**unsafe { value.deref() })
+            }));
+            cursor = next;
+            if cursor == 0 || entries.len() >= count {
+                return (cursor, entries);
+            }
+        }
+    }
+
+    /// Returns an iterator over the nodes in the bin of the current table that `cursor` points
+    /// to, along with the cursor for the bin to visit next.
+    ///
+    /// Cursors enumerate the bins of a table in reverse-binary order: the cursor is incremented
+    /// starting from its highest masked bit rather than its lowest. Since the table only ever
+    /// doubles in size, and a bin `i` of a table with `n` bins is split into bins `i` and `i + n`
+    /// of the next table, this order ensures that a walk that is continued on a larger table
+    /// neither skips nor re-visits bins. Forwarded bins are followed into the next table, so the
+    /// returned iterator also covers nodes that have already been transferred.
+    ///
+    /// A walk starts at cursor 0, and is complete when the returned cursor is 0 again.
+    pub(crate) fn bin_at_cursor<'g>(
+        &'g self,
+        cursor: usize,
+        guard: &'g Guard<'_>,
+    ) -> (NodeIter<'g, K, V>, usize) {
+        let table = self.table.load(Ordering::SeqCst, guard);
+        // safety: we loaded the table while holding a guard, so it won't be deallocated until
+        // we drop our guard at the earliest.
+        if table.is_null() || unsafe { table.deref() }.is_empty() {
+            return (NodeIter::new(Shared::null(), guard), 0);
+        }
+
+        // safety: same as above
+        let mask = unsafe { table.deref() }.len() - 1;
+        let bini = cursor & mask;
+        let next = (cursor | !mask)
+            .reverse_bits()
+            .wrapping_add(1)
+            .reverse_bits();
+        (NodeIter::for_bins(table, bini..bini + 1, guard), next)
+    }
+
+    fn init_table<'g>(&'g self, guard: &'g Guard<'_>) -> Shared<'g, Table<K, V>> {
+        loop {
+            let table = self.table.load(Ordering::SeqCst, guard);
+            // safety: we loaded the table while the thread was marked as active.
+            // table won't be deallocated until the guard is dropped at the earliest.
+            if !table.is_null() && !unsafe { table.deref() }.is_empty() {
+                break table;
+            }
+            // try to allocate the table
+            let mut sc = self.size_ctl.load(Ordering::SeqCst);
+            if sc < 0 {
+                // we lost the initialization race; just spin
+                sync::yield_now();
+                continue;
+            }
 
             if self
                 .size_ctl
@@ -487,15 +1351,7 @@ impl<K, V, S> HashMap<K, V, S> {
         // while we are initializing it.
         let guard = unsafe { Guard::unprotected() };
 
-        let requested_capacity = if size >= MAXIMUM_CAPACITY / 2 {
-            MAXIMUM_CAPACITY
-        } else {
-            // round the requested_capacity to the next power of to from 1.5 * size + 1
-            // TODO: find out if this is neccessary
-            let size = size + (size >> 1) + 1;
-
-            std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
-        } as usize;
+        let requested_capacity = table_size_for(size);
 
         // sanity check that the map has indeed not been set up already
         assert_eq!(self.size_ctl.load(Ordering::SeqCst), 0);
@@ -520,6 +1376,184 @@ impl<K, V, S> HashMap<K, V, S> {
         // and thus release the initialization "lock"
         self.size_ctl.store(new_load_to_resize_at, Ordering::SeqCst);
     }
+
+    /// Moves every entry out of the map, passing each key and value to `f`.
+    ///
+    /// This uses the exclusive access to the map to take the bins apart directly, without any
+    /// locking or atomic read-modify-write operations. The map is left without a table.
+    fn drain_owned<F>(&mut self, mut f: F)
+    where
+        F: FnMut(K, V),
+    {
+        // safety: we have &mut self _and_ all references we have returned are bound to the
+        // lifetime of their borrow of self, so there cannot be any outstanding references to
+        // anything in the map.
+        let guard = unsafe { Guard::unprotected() };
+
+        assert!(self.next_table.load(Ordering::SeqCst, &guard).is_null());
+        let table = self.table.swap(Shared::null(), Ordering::SeqCst, &guard);
+        self.count.store(0, Ordering::SeqCst);
+        self.size_ctl.store(0, Ordering::SeqCst);
+        if table.is_null() {
+            return;
+        }
+
+        // safety: same as above + we own the table
+        let table = unsafe { table.into_box() };
+        for i in 0..table.len() {
+            let bin = table.bin(i, &guard);
+            if bin.is_null() {
+                continue;
+            }
+            // the table must be empty (or forwarding) by the time it is dropped
+            table.store_bin(i, Shared::null());
+
+            // safety: same as above + we own the bin, and have just made it unreachable
+            match unsafe { bin.into_box() }.value {
+                BinEntry::Moved => {}
+                // a reservation is only left behind by a `KeyLock` that was forgotten, and holds
+                // no entries
+                BinEntry::Reservation(_) => {}
+                BinEntry::Node(mut node) => {
+                    loop {
+                        // safety: we own all the nodes in the list, and their values
+                        let value = unsafe { node.value.into_box() }.value;
+                        let next = node.next.load(Ordering::SeqCst, &guard);
+                        f(node.key, value);
+                        if next.is_null() {
+                            break;
+                        }
+                        // safety: same as above
+                        let BinEntry::Node(next) = unsafe { next.into_box() }.value else {
+                            unreachable!("linked bins can only ever contain Nodes");
+                        };
+                        node = next;
+                    }
+                }
+                BinEntry::Tree(tree_bin) => {
+                    // take the nodes out of the bin so that dropping it does not free them again.
+                    // it is sufficient to follow the `next` pointers, since the tree pointers
+                    // point to the same nodes.
+                    let mut p = tree_bin
+                        .first
+                        .swap(Shared::null(), Ordering::Relaxed, &guard);
+                    while !p.is_null() {
+                        // safety: same as for BinEntry::Node
+                        let BinEntry::TreeNode(tree_node) = unsafe { p.into_box() }.value else {
+                            unreachable!("Trees can only ever contain TreeNodes");
+                        };
+                        let node = tree_node.node;
+                        let value = unsafe { node.value.into_box() }.value;
+                        p = node.next.load(Ordering::SeqCst, &guard);
+                        f(node.key, value);
+                    }
+                    drop(tree_bin);
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            }
+        }
+        drop(table);
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Ord,
+{
+    /// Gives the map a table whose `i`th bin holds the `(hash, key, value)` entries in `bins[i]`.
+    ///
+    /// Every hash in `bins[i]` must map to bin `i`, and `bins.len()` must be a power of two. Bins
+    /// with enough entries are built as tree bins directly. Since we have `&mut self` and the
+    /// map has no table yet, the table is assembled without any locking or atomic
+    /// read-modify-write operations.
+    fn install_bins(&mut self, bins: Vec<Vec<(u64, K, V)>>) {
+        // safety: we have &mut self, so no other thread can access the map while we build it.
+        let guard = unsafe { Guard::unprotected() };
+
+        let n = bins.len();
+        debug_assert!(n.is_power_of_two());
+        assert!(self.table.load(Ordering::SeqCst, &guard).is_null());
+
+        let mut count = 0;
+        let collector = &self.collector;
+        let bins: Vec<_> = bins
+            .into_iter()
+            .map(|entries| {
+                count += entries.len();
+                let tree = entries.len() >= TREEIFY_THRESHOLD && n >= MIN_TREEIFY_CAPACITY;
+                let entries = entries.into_iter().map(|(hash, key, value)| {
+                    (hash, key, Atomic::from(Shared::boxed(value, collector)))
+                });
+                if tree {
+                    Atomic::from(Self::build_tree_bin(entries, collector, &guard))
+                } else {
+                    Atomic::from(Self::build_linked_bin(entries, collector))
+                }
+            })
+            .collect();
+
+        let table = Shared::boxed(Table::from(bins, collector), collector);
+        self.table.store(table, Ordering::SeqCst);
+        self.count.store(count as isize, Ordering::SeqCst);
+        self.size_ctl
+            .store(load_factor!(n as isize), Ordering::SeqCst);
+    }
+
+    /// Builds a linked bin holding the given entries, in order.
+    ///
+    /// Returns `Shared::null()` if there are no entries.
+    fn build_linked_bin<'g, I>(entries: I, collector: &Collector) -> Shared<'g, BinEntry<K, V>>
+    where
+        I: IntoIterator<Item = (u64, K, Atomic<V>)>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut head = Shared::null();
+        for (hash, key, value) in entries.into_iter().rev() {
+            let node = Node::with_next(hash, key, value, Atomic::from(head));
+            head = Shared::boxed(BinEntry::Node(node), collector);
+        }
+        head
+    }
+
+    /// Builds a `BinEntry::Tree` holding the given entries.
+    ///
+    /// This mirrors what `treeify_bin` does for an existing bin.
+    fn build_tree_bin<'g, I>(
+        entries: I,
+        collector: &Collector,
+        guard: &'g Guard<'_>,
+    ) -> Shared<'g, BinEntry<K, V>>
+    where
+        I: IntoIterator<Item = (u64, K, Atomic<V>)>,
+    {
+        let mut head = Shared::null();
+        let mut tail = Shared::null();
+        for (hash, key, value) in entries {
+            let new_tree_node = TreeNode::new(hash, key, value, Atomic::null(), Atomic::null());
+            new_tree_node.prev.store(tail, Ordering::Relaxed);
+            let new_tree_node = Shared::boxed(BinEntry::TreeNode(new_tree_node), collector);
+            if tail.is_null() {
+                head = new_tree_node;
+            } else {
+                // safety: if `tail` is not `null`, we have just created
+                // it in the last iteration, thus the pointer is valid
+                unsafe { tail.deref() }
+                    .as_tree_node()
+                    .unwrap()
+                    .node
+                    .next
+                    .store(new_tree_node, Ordering::Relaxed);
+            }
+            tail = new_tree_node;
+        }
+
+        // safety: we have just created `head` and its `next` nodes using `Shared::boxed`
+        // and have never shared them
+        let tree_bin = unsafe { TreeBin::new(head, guard) };
+        Shared::boxed(BinEntry::Tree(tree_bin), collector)
+    }
 }
 
 // ===
@@ -536,15 +1570,7 @@ where
 {
     /// Tries to presize table to accommodate the given number of elements.
     fn try_presize(&self, size: usize, guard: &Guard<'_>) {
-        let requested_capacity = if size >= MAXIMUM_CAPACITY / 2 {
-            MAXIMUM_CAPACITY
-        } else {
-            // round the requested_capacity to the next power of to from 1.5 * size + 1
-            // TODO: find out if this is neccessary
-            let size = size + (size >> 1) + 1;
-
-            std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
-        } as isize;
+        let requested_capacity = table_size_for(size) as isize;
 
         loop {
             let size_ctl = self.size_ctl.load(Ordering::SeqCst);
@@ -817,9 +1843,28 @@ where
                     // already processed
                     advance = true;
                 }
+                BinEntry::Reservation(_) => {
+                    let mut lock = self.lock_for_transfer(bin, guard);
+                    if let TransferLock::Bin(_) = lock {
+                        // the reservation is replaced before its lock is released, so try again
+                        // from the start.
+                        continue;
+                    }
+
+                    // the bin is reserved by a KeyLock, so it stays empty in the next table,
+                    // apart from the reservation that takes over the lock.
+                    self.move_key_lock(&mut lock, next_table_ptr, i, n, guard);
+                    table.store_bin(i, table.get_moved(next_table_ptr, guard));
+                    Self::unlock_transferred(lock, bin, guard);
+                    // safety: the reservation has been replaced, so no thread that executes after
+                    // this can get a reference to it. threads that read it before did so while
+                    // holding a guard, so it will not be dropped until they release it.
+                    unsafe { guard.retire_shared(bin) };
+                    advance = true;
+                }
                 BinEntry::Node(ref head) => {
                     // bin is non-empty, need to link into it, so we must take the lock
-                    let head_lock = head.lock.lock();
+                    let mut head_lock = self.lock_for_transfer(bin, guard);
 
                     // need to check that this is _still_ the head
                     let current_head = table.bin(i, guard);
@@ -905,6 +1950,7 @@ where
 
                     next_table.store_bin(i, low_bin);
                     next_table.store_bin(i + n, high_bin);
+                    self.move_key_lock(&mut head_lock, next_table_ptr, i, n, guard);
                     table.store_bin(i, table.get_moved(next_table_ptr, guard));
 
                     // everything up to last_run in the _old_ bin linked list is now garbage.
@@ -931,10 +1977,10 @@ where
 
                     advance = true;
 
-                    drop(head_lock);
+                    Self::unlock_transferred(head_lock, bin, guard);
                 }
                 BinEntry::Tree(ref tree_bin) => {
-                    let bin_lock = tree_bin.lock.lock();
+                    let mut bin_lock = self.lock_for_transfer(bin, guard);
 
                     // need to check that this is _still_ the correct bin
                     let current_head = table.bin(i, guard);
@@ -1060,6 +2106,7 @@ where
 
                     next_table.store_bin(i, low_bin);
                     next_table.store_bin(i + n, high_bin);
+                    self.move_key_lock(&mut bin_lock, next_table_ptr, i, n, guard);
                     table.store_bin(i, table.get_moved(next_table_ptr, guard));
 
                     // if we did not re-use the old bin, it is now garbage,
@@ -1076,7 +2123,7 @@ where
                     }
 
                     advance = true;
-                    drop(bin_lock);
+                    Self::unlock_transferred(bin_lock, bin, guard);
                 }
                 BinEntry::TreeNode(_) => unreachable!(
                     "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
@@ -1085,6 +2132,116 @@ where
         }
     }
 
+    /// Creates a reservation, which is locked before anyone else can see it.
+    fn new_reservation<'g>(&'g self) -> NewBin<'g, K, V> {
+        let reservation = Shared::boxed(BinEntry::Reservation(BinLock::new()), &self.collector);
+        // safety: we just created the reservation, and it will not be dropped until after we
+        // drop our guard once it is shared.
+        let reservation_entry = unsafe { reservation.deref() };
+        let BinEntry::Reservation(ref lock) = **reservation_entry else {
+            unreachable!("we declared the reservation");
+        };
+        (reservation, lock.lock())
+    }
+
+    /// Locks `bin` so that `transfer` can move it.
+    ///
+    /// If the bin is held by a [`KeyLock`], this does not wait for it to be released. It locks
+    /// the map's [`KeyLocks`] instead, so that the `KeyLock` cannot use the bin while it is moved.
+    fn lock_for_transfer<'g>(
+        &'g self,
+        bin: Shared<'g, BinEntry<K, V>>,
+        guard: &'g Guard<'_>,
+    ) -> TransferLock<'g, K, V> {
+        // safety: see the argument for `bin` in `transfer`
+        let lock = unsafe { bin.deref() }.bin_lock();
+        loop {
+            // see `BinLock` for why a `KeyLock` cannot take the lock while we wait for it
+            let held = if lock.start_transfer() {
+                // the `KeyLock` marks the bin and registers it while holding the `KeyLocks`, so
+                // the bin is registered, unless the `KeyLock` has since let go of it
+                let key_locks = self.key_locks.lock();
+                key_locks
+                    .held
+                    .iter()
+                    .position(|held| held.bin.load(Ordering::SeqCst, guard) == bin)
+                    .map(|k| TransferLock::KeyLock(key_locks, k))
+            } else {
+                Some(TransferLock::Bin(lock.lock()))
+            };
+            lock.end_transfer();
+            if let Some(held) = held {
+                return held;
+            }
+        }
+    }
+
+    /// If bin `i` of the table of length `n` that is being resized is held by a [`KeyLock`],
+    /// moves the lock to the bin of `next_table` that now holds its key.
+    ///
+    /// This must be called after the bin's entries have been stored in `next_table`, but before
+    /// the bin is replaced by a `Moved` entry, so that nobody has seen the new bin yet. The new
+    /// bin is locked on behalf of the `KeyLock`, unless it is the old bin itself, or, if the key's
+    /// new bin is empty, reserved.
+    fn move_key_lock<'g>(
+        &'g self,
+        lock: &mut TransferLock<'g, K, V>,
+        next_table: Shared<'g, Table<K, V>>,
+        i: usize,
+        n: usize,
+        guard: &'g Guard<'_>,
+    ) {
+        let TransferLock::KeyLock(ref mut key_locks, k) = *lock else {
+            return;
+        };
+        let held = &mut key_locks.held[k];
+        // safety: the next table is not retired until after the resize has finished
+        let nt = unsafe { next_table.deref() };
+        let index = if held.hash & n as u64 == 0 { i } else { i + n };
+        let mut bin = nt.bin(index, guard);
+        if bin != held.bin.load(Ordering::SeqCst, guard) {
+            let new_lock = if bin.is_null() {
+                let (reservation, new_lock) = self.new_reservation();
+                nt.store_bin(index, reservation);
+                bin = reservation;
+                new_lock
+            } else {
+                // safety: we just stored the bin in the next table, which nobody can get to
+                // before the old bin has been replaced by a `Moved` entry
+                unsafe { bin.deref() }.bin_lock().lock()
+            };
+            // the bin stays locked until the `KeyLock` is dropped, see `KeyLocks`
+            std::mem::forget(new_lock);
+            // safety: as above
+            let marked = unsafe { bin.deref() }.bin_lock().mark_key_locked();
+            debug_assert!(marked, "nobody can wait for a bin they cannot see");
+        }
+        held.table.store(next_table, Ordering::SeqCst);
+        held.index = index;
+        held.bin.store(bin, Ordering::SeqCst);
+    }
+
+    /// Unlocks `bin` once `transfer` has replaced it by a `Moved` entry.
+    fn unlock_transferred<'g>(
+        lock: TransferLock<'g, K, V>,
+        bin: Shared<'g, BinEntry<K, V>>,
+        guard: &'g Guard<'_>,
+    ) {
+        match lock {
+            TransferLock::Bin(lock) => drop(lock),
+            TransferLock::KeyLock(key_locks, k) => {
+                if key_locks.held[k].bin.load(Ordering::SeqCst, guard) != bin {
+                    // safety: the `KeyLock` holds the lock of its new bin instead, and `bin` was
+                    // read under our guard
+                    unsafe {
+                        bin.deref().bin_lock().unmark_key_locked();
+                        unlock_leaked(bin);
+                    }
+                }
+            }
+        }
+    }
+
     fn help_transfer<'g>(
         &'g self,
         table: Shared<'g, Table<K, V>>,
@@ -1253,10 +2410,21 @@ where
         self.build_hasher.hash_one(key)
     }
 
-    fn get_node<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<&'g Node<K, V>>
+    pub(crate) fn get_node<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<&'g Node<K, V>>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.get_node_with_hash(self.hash(key), key, guard)
+    }
+
+    fn get_node_with_hash<'g, Q>(
+        &'g self,
+        h: u64,
+        key: &Q,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g Node<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         let table = self.table.load(Ordering::SeqCst, guard);
         if table.is_null() {
@@ -1271,9 +2439,22 @@ where
             return None;
         }
 
-        let h = self.hash(key);
         let bini = table.bini(h);
         let bin = table.bin(bini, guard);
+        Self::find_node(table, bin, h, key, guard)
+    }
+
+    /// Finds the node for `key` in `bin`, which must have been read from `table` under `guard`.
+    fn find_node<'g, Q>(
+        table: &'g Table<K, V>,
+        bin: Shared<'g, BinEntry<K, V>>,
+        h: u64,
+        key: &Q,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g Node<K, V>>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
         if bin.is_null() {
             return None;
         }
@@ -1332,13 +2513,102 @@ where
     /// ```
     pub fn contains_key<Q>(&self, key: &Q, guard: &Guard<'_>) -> bool
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         self.get(key, guard).is_some()
     }
 
+    /// Subscribes to changes to `key`.
+    ///
+    /// The returned [`Receiver`] is notified whenever the key is inserted, updated or removed
+    /// after this call, once the bin lock of the change has been released. Notifications carry no
+    /// data, so read the map to see what changed. Writers only pay for notifications while the
+    /// map has subscribers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let mut changes = map.subscribe(&1);
+    /// assert!(!changes.try_recv());
+    ///
+    /// map.pin().insert(1, "a");
+    /// assert!(changes.try_recv());
+    /// assert!(!changes.try_recv());
+    ///
+    /// map.pin().insert(2, "b");
+    /// assert!(!changes.try_recv());
+    /// ```
+    pub fn subscribe<Q>(&self, key: &Q) -> Receiver<'_>
+    where
+        Q: ?Sized + Hash,
+    {
+        Receiver::new(&self.watchers, self.hash(key))
+    }
+
+    /// Blocks the current thread until `key` is present in the map, or until `timeout` has
+    /// passed.
+    ///
+    /// Returns `true` if the key is present. No guard is held while waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let map = Arc::new(HashMap::new());
+    /// let writer = {
+    ///     let map = Arc::clone(&map);
+    ///     std::thread::spawn(move || {
+    ///         map.pin().insert("ready", true);
+    ///     })
+    /// };
+    /// assert!(map.wait_until_present("ready", Duration::from_secs(60)));
+    /// assert!(!map.wait_until_present("never", Duration::from_millis(10)));
+    /// writer.join().unwrap();
+    /// ```
+    pub fn wait_until_present<Q>(&self, key: &Q, timeout: Duration) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut changes = self.subscribe(key);
+        loop {
+            // we subscribed before looking, so we cannot miss an insert between the two
+            if self.contains_key(key, &self.guard()) {
+                return true;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if !changes.recv_deadline(deadline) {
+                        return self.contains_key(key, &self.guard());
+                    }
+                }
+                None => changes.recv(),
+            }
+        }
+    }
+
+    /// Waits asynchronously until `key` is present in the map.
+    ///
+    /// No guard is held while waiting. To give up after a while, use the timeout facility of
+    /// your async runtime.
+    pub async fn wait_until_present_async<Q>(&self, key: &Q)
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        let mut changes = self.subscribe(key);
+        // we subscribed before looking, so we cannot miss an insert between the two
+        while !self.contains_key(key, &self.guard()) {
+            changes.changed().await;
+        }
+    }
+
     /// Returns a reference to the value corresponding to the key.
     ///
     /// The key may be any borrowed form of the map's key type, but
@@ -1364,8 +2634,7 @@ where
     #[inline]
     pub fn get<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         let node = self.get_node(key, guard)?;
@@ -1391,8 +2660,7 @@ where
     #[inline]
     pub fn get_key_value<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         let node = self.get_node(key, guard)?;
@@ -1405,34 +2673,13 @@ where
         unsafe { v.as_ref() }.map(|v| (&node.key, &**v))
     }
 
-    pub(crate) fn guarded_eq(
-        &self,
-        other: &Self,
-        our_guard: &Guard<'_>,
-        their_guard: &Guard<'_>,
-    ) -> bool
-    where
-        V: PartialEq,
-    {
-        if self.len() != other.len() {
-            return false;
-        }
-
-        self.iter(our_guard)
-            .all(|(key, value)| other.get(key, their_guard).map_or(false, |v| *value == *v))
-    }
-}
-
-// ===
-// the following methods only ever _remove_ items, but never introduce them, so they do not need
-// the thread-safety bounds.
-// ===
-
-impl<K, V, S> HashMap<K, V, S>
-where
-    K: Clone + Ord,
-{
-    /// Clears the map, removing all key-value pairs.
+    /// Returns the hash this map uses for `key`.
+    ///
+    /// The hash can be passed to the `_with_hash` methods (such as [`HashMap::get_with_hash`])
+    /// and to [`HashMap::find_by_hash`] to avoid hashing the same key again.
+    ///
+    /// `key` does not have to be of a type that `K` can be borrowed as, but it must hash the
+    /// same way as the equal key of type `K` for the hash to be useful.
     ///
     /// # Examples
     ///
@@ -1440,12 +2687,341 @@ where
     /// use flurry::HashMap;
     ///
     /// let map = HashMap::new();
-    ///
-    /// map.pin().insert(1, "a");
-    /// map.pin().clear();
-    /// assert!(map.pin().is_empty());
+    /// let guard = map.guard();
+    /// let hash = map.hash_key("a");
+    /// map.insert_with_hash(hash, String::from("a"), 1, &guard);
+    /// assert_eq!(map.get_with_hash(hash, "a", &guard), Some(&1));
+    /// assert_eq!(map.get("a", &guard), Some(&1));
     /// ```
-    pub fn clear(&self, guard: &Guard<'_>) {
+    #[inline]
+    pub fn hash_key<Q>(&self, key: &Q) -> u64
+    where
+        Q: ?Sized + Hash,
+    {
+        self.hash(key)
+    }
+
+    /// Returns a reference to the value corresponding to the key, given the key's `hash`.
+    ///
+    /// `hash` must be the hash returned by [`HashMap::hash_key`] for `key`. If it is not, the
+    /// lookup is performed in the wrong place, and is likely to return `None`.
+    ///
+    /// See also [`HashMap::get`].
+    #[inline]
+    pub fn get_with_hash<'g, Q>(&'g self, hash: u64, key: &Q, guard: &'g Guard<'_>) -> Option<&'g V>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.check_guard(guard);
+        let node = self.get_node_with_hash(hash, key, guard)?;
+
+        let v = node.value.load(Ordering::SeqCst, guard);
+        assert!(!v.is_null());
+        // safety: the lifetime of the reference is bound to the guard
+        // supplied which means that the memory will not be modified
+        // until at least after the guard goes out of scope
+        unsafe { v.as_ref().map(|linked| &**linked) }
+    }
+
+    /// Returns the first key-value pair with the given `hash` whose key satisfies `is_match`.
+    ///
+    /// This allows looking up a key without constructing a value that the key type can be
+    /// borrowed as. `hash` should be the hash returned by [`HashMap::hash_key`] for the key that
+    /// is sought, and `is_match` should only return `true` for keys that are equal to it. Since
+    /// the predicate cannot be used to navigate the red-black trees that large bins are organized
+    /// into, such bins are searched linearly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert((String::from("a"), 1), "x", &guard);
+    ///
+    /// // look up ("a", 1) without allocating a String
+    /// let hash = map.hash_key(&("a", 1));
+    /// let found = map.find_by_hash(hash, |(s, i)| s == "a" && *i == 1, &guard);
+    /// assert_eq!(found, Some((&(String::from("a"), 1), &"x")));
+    /// ```
+    pub fn find_by_hash<'g, F>(
+        &'g self,
+        hash: u64,
+        is_match: F,
+        guard: &'g Guard<'_>,
+    ) -> Option<(&'g K, &'g V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.check_guard(guard);
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if table.is_null() {
+            return None;
+        }
+
+        // safety: we loaded the table while holding a guard.
+        // table won't be deallocated until we drop our guard
+        // at the earliest.
+        let table = unsafe { table.deref() };
+        if table.is_empty() {
+            return None;
+        }
+
+        let bin = table.bin(table.bini(hash), guard);
+        if bin.is_null() {
+            return None;
+        }
+        // safety: see `find_node`
+        let node = table.find_by(unsafe { bin.deref() }, hash, is_match, guard)?;
+
+        let v = node.value.load(Ordering::SeqCst, guard);
+        assert!(!v.is_null());
+        // safety: the lifetime of the reference is bound to the guard
+        // supplied which means that the memory will not be modified
+        // until at least after the guard goes out of scope
+        unsafe { v.as_ref() }.map(|v| (&node.key, &**v))
+    }
+
+    /// Returns a clone of the value corresponding to the key.
+    ///
+    /// Unlike [`HashMap::get`], this method does not take a `Guard`. Instead, the current thread
+    /// is pinned only for the duration of the call, which is why the value has to be cloned
+    /// before it is returned. Pinning is cheap: [`seize`] tracks the current thread's reservation
+    /// in a thread-local, and guards taken while another guard for the same collector is active
+    /// on the current thread simply re-use it. Since the guard is created by this map, the
+    /// collector check performed by guard-taking methods is also skipped.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.insert_owned(1, String::from("a"));
+    /// assert_eq!(map.get_cloned(&1), Some(String::from("a")));
+    /// assert_eq!(map.get_cloned(&2), None);
+    /// ```
+    #[inline]
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        V: Clone,
+    {
+        self.with_value(key, V::clone)
+    }
+
+    /// Calls `f` with a reference to the value corresponding to the key, and returns its result.
+    ///
+    /// Returns `None` without calling `f` if the map contains no mapping for `key`.
+    ///
+    /// Like [`HashMap::get_cloned`], this method pins the current thread only for the duration of
+    /// the call, so the reference passed to `f` cannot escape it. This is useful to extract parts
+    /// of a value without cloning all of it.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.insert_owned(1, vec![1, 2, 3]);
+    /// assert_eq!(map.with_value(&1, |v| v.len()), Some(3));
+    /// assert_eq!(map.with_value(&2, |v| v.len()), None);
+    /// ```
+    pub fn with_value<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        F: FnOnce(&V) -> R,
+    {
+        let guard = self.guard();
+        let node = self.get_node(key, &guard)?;
+
+        let v = node.value.load(Ordering::SeqCst, &guard);
+        assert!(!v.is_null());
+        // safety: the value is not dropped until after `guard` is dropped, and `f` cannot hold on
+        // to the reference past its own return.
+        Some(f(unsafe { &**v.deref() }))
+    }
+
+    /// Returns references to the values corresponding to each of the given keys.
+    ///
+    /// This is equivalent to calling [`HashMap::get`] for each key, but is faster for more than a
+    /// handful of keys: all keys are hashed first, and the memory they will need is prefetched
+    /// before any of them is resolved, so that the cache misses of the individual lookups overlap
+    /// rather than happen one after the other.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// map.insert(2, "b", &guard);
+    /// assert_eq!(map.get_many(&[&1, &3, &2], &guard), vec![Some(&"a"), None, Some(&"b")]);
+    /// ```
+    pub fn get_many<'g, Q>(&'g self, keys: &[&Q], guard: &'g Guard<'_>) -> Vec<Option<&'g V>>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.check_guard(guard);
+        let mut values = vec![None; keys.len()];
+        self.get_many_into(keys, &mut values, guard);
+        values
+    }
+
+    /// Returns references to the values corresponding to each of the given keys.
+    ///
+    /// This is the same as [`HashMap::get_many`], except that it takes a fixed number of keys,
+    /// and does not allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// map.insert(2, "b", &guard);
+    /// assert_eq!(map.get_many_array([&1, &3, &2], &guard), [Some(&"a"), None, Some(&"b")]);
+    /// ```
+    pub fn get_many_array<'g, Q, const N: usize>(
+        &'g self,
+        keys: [&Q; N],
+        guard: &'g Guard<'_>,
+    ) -> [Option<&'g V>; N]
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.check_guard(guard);
+        let mut values = [None; N];
+        self.get_many_into(&keys, &mut values, guard);
+        values
+    }
+
+    /// Resolves each of `keys` into the corresponding slot of `values`.
+    fn get_many_into<'g, Q>(
+        &'g self,
+        keys: &[&Q],
+        values: &mut [Option<&'g V>],
+        guard: &'g Guard<'_>,
+    ) where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        debug_assert_eq!(keys.len(), values.len());
+        let table = self.table.load(Ordering::SeqCst, guard);
+        if table.is_null() {
+            return;
+        }
+
+        // safety: we loaded the table while holding a guard.
+        // table won't be deallocated until we drop our guard
+        // at the earliest.
+        let table = unsafe { table.deref() };
+        if table.is_empty() {
+            return;
+        }
+
+        // keys are handled in fixed-size chunks so that the scratch space can live on the stack.
+        // a chunk is also about as many outstanding prefetches as is useful.
+        let mut hashes = [0; GET_MANY_CHUNK];
+        let mut bins = [Shared::null(); GET_MANY_CHUNK];
+        for (keys, values) in keys
+            .chunks(GET_MANY_CHUNK)
+            .zip(values.chunks_mut(GET_MANY_CHUNK))
+        {
+            // first, hash every key and prefetch the bin slot it maps to
+            for (h, key) in hashes.iter_mut().zip(keys) {
+                *h = self.hash(*key);
+                table.prefetch_bin(table.bini(*h));
+            }
+
+            // then, read the bin heads, and prefetch the heads themselves
+            for (bin, &h) in bins.iter_mut().zip(&hashes[..keys.len()]) {
+                *bin = table.bin(table.bini(h), guard);
+                // safety: the pointer is only used as a hint, and is never dereferenced
+                prefetch(unsafe { bin.as_ptr() });
+            }
+
+            // finally, resolve each key in its bin
+            for (((value, key), &h), &bin) in values.iter_mut().zip(keys).zip(&hashes).zip(&bins) {
+                *value = Self::find_node(table, bin, h, *key, guard).map(|node| {
+                    let v = node.value.load(Ordering::SeqCst, guard);
+                    assert!(!v.is_null());
+                    // safety: the lifetime of the reference is bound to the guard
+                    // supplied which means that the memory will not be modified
+                    // until at least after the guard goes out of scope
+                    &**unsafe { v.deref() }
+                });
+            }
+        }
+    }
+
+    pub(crate) fn guarded_eq(
+        &self,
+        other: &Self,
+        our_guard: &Guard<'_>,
+        their_guard: &Guard<'_>,
+    ) -> bool
+    where
+        V: PartialEq,
+    {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.iter(our_guard)
+            .all(|(key, value)| other.get(key, their_guard).is_some_and(|v| *value == *v))
+    }
+}
+
+// ===
+// the following methods only ever _remove_ items, but never introduce them, so they do not need
+// the thread-safety bounds.
+// ===
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Clone + Ord,
+{
+    /// Clears the map, removing all key-value pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    ///
+    /// map.pin().insert(1, "a");
+    /// map.pin().clear();
+    /// assert!(map.pin().is_empty());
+    /// ```
+    pub fn clear(&self, guard: &Guard<'_>) {
         // Negative number of deletions
         let mut delta = 0;
         let mut idx = 0usize;
@@ -1467,6 +3043,12 @@ where
                     // start from the first bin again in the new table
                     idx = 0;
                 }
+                BinEntry::Reservation(ref lock) => {
+                    // the bin is reserved, and the reservation is replaced before its lock is released.
+                    // so wait for the lock, and then try again from the start.
+                    drop(lock.lock());
+                    continue;
+                }
                 BinEntry::Node(ref node) => {
                     let head_lock = node.lock.lock();
                     // need to check that this is _still_ the head
@@ -1482,8 +3064,12 @@ where
                     // threads and threads waiting on the lock will read the new bin, so we can
                     // drop the lock early and do the counting and garbage collection outside the
                     // critical section.
+                    let cleared = self.sequence_clear(raw_node, guard);
                     tab.store_bin(idx, Shared::null());
                     drop(head_lock);
+                    for change in cleared {
+                        self.record(change);
+                    }
                     // next, walk the nodes of the bin and free the nodes and their values as we go
                     // note that we do not free the head node yet, since we're holding the lock it contains
                     let mut p = node.next.load(Ordering::SeqCst, guard);
@@ -1534,8 +3120,13 @@ where
                     // threads and threads waiting on the lock will read the new bin, so we can
                     // drop the lock early and do the counting and garbage collection outside the
                     // critical section.
+                    let cleared =
+                        self.sequence_clear(tree_bin.first.load(Ordering::SeqCst, guard), guard);
                     tab.store_bin(idx, Shared::null());
                     drop(bin_lock);
+                    for change in cleared {
+                        self.record(change);
+                    }
                     // next, walk the nodes of the bin and count how many values we remove
                     let mut p = tree_bin.first.load(Ordering::SeqCst, guard);
                     while !p.is_null() {
@@ -1567,30 +3158,33 @@ where
         if delta != 0 {
             self.add_count(delta, None, guard);
         }
+        self.watchers.notify_all();
+        self.debug_invariants(None, guard);
     }
 }
 
-// ===
-// the following methods _do_ introduce items into the map, and so must require that the keys and
-// values are thread safe, and can be garbage collected at a later time.
-// ===
-
 impl<K, V, S> HashMap<K, V, S>
 where
-    K: Sync + Send + Clone + Hash + Ord,
-    V: Sync + Send,
-    S: BuildHasher,
+    K: Ord,
 {
-    /// Inserts a key-value pair into the map.
+    /// Checks the internal invariants of the map, and returns the first broken one it finds.
     ///
-    /// If the map did not have this key present, [`None`] is returned.
+    /// This checks that every entry is in the bin its hash maps to, that no bin holds two entries
+    /// with equal keys, that every tree bin is a valid red-black tree whose nodes are also
+    /// consistently linked through their `prev` and `next` pointers, and that the state that
+    /// controls the growth of the table fits the table. If the map is quiescent, it also checks
+    /// that the number of entries in the bins agrees with [`HashMap::len`].
     ///
-    /// If the map did have this key present, the value is updated, and the old
-    /// value is returned. The key is left unchanged. See the [std-collections
-    /// documentation] for more.
+    /// The map does not have to be quiescent, since the bins are checked one at a time while
+    /// holding their locks. Updates to a bin wait while it is checked, and lookups proceed as
+    /// usual. The count is only compared when no entry was counted and the table did not change
+    /// while the bins were walked. A change that has been made to a bin but not yet counted cannot
+    /// be told apart from a wrong count though, so this method may report a
+    /// [`InvariantViolation::CountMismatch`] if other threads modify the map while it runs.
     ///
-    /// [`None`]: std::option::Option::None
-    /// [std-collections documentation]: https://doc.rust-lang.org/std/collections/index.html#insert-and-complex-keys
+    /// This walks every bin of the map, and is meant for tests and debugging. With the
+    /// `debug-invariants` feature, the map checks the bins it changes after every mutation, and
+    /// panics if their invariants are broken.
     ///
     /// # Examples
     ///
@@ -1598,25 +3192,286 @@ where
     /// use flurry::HashMap;
     ///
     /// let map = HashMap::new();
-    /// assert_eq!(map.pin().insert(37, "a"), None);
-    /// assert_eq!(map.pin().is_empty(), false);
-    ///
-    /// // you can also re-use a map pin like so:
-    /// let mref = map.pin();
-    ///
-    /// mref.insert(37, "b");
-    /// assert_eq!(mref.insert(37, "c"), Some(&"b"));
-    /// assert_eq!(mref.get(&37), Some(&"c"));
+    /// let guard = map.guard();
+    /// for i in 0..100 {
+    ///     map.insert(i, i, &guard);
+    /// }
+    /// assert_eq!(map.validate(&guard), Ok(()));
     /// ```
-    pub fn insert<'g>(&'g self, key: K, value: V, guard: &'g Guard<'_>) -> Option<&'g V> {
+    pub fn validate(&self, guard: &Guard<'_>) -> Result<(), InvariantViolation> {
         self.check_guard(guard);
-        self.put(key, value, false, guard).before()
+
+        // the bins and the count are not updated together, so we only compare them if nothing
+        // seems to have changed while we walked the bins, and give the walk a few chances to
+        // find the map quiescent before we report a mismatch.
+        let mut mismatch = None;
+        for _ in 0..3 {
+            let count = self.count.load(Ordering::SeqCst);
+            let size_ctl = self.size_ctl.load(Ordering::SeqCst);
+            let table = self.table.load(Ordering::SeqCst, guard);
+            let entries = self.validate_bins(None, guard)?;
+            self.validate_size_ctl(guard)?;
+            if size_ctl < 0
+                || self.count.load(Ordering::SeqCst) != count
+                || self.size_ctl.load(Ordering::SeqCst) != size_ctl
+                || self.table.load(Ordering::SeqCst, guard) != table
+            {
+                // the map was initialized, resized or updated while we walked it
+                continue;
+            }
+            if entries as isize == count {
+                return Ok(());
+            }
+            mismatch = Some(InvariantViolation::CountMismatch { count, entries });
+        }
+        mismatch.map_or(Ok(()), Err)
     }
 
-    /// Inserts a key-value pair into the map unless the key already exists.
-    ///
-    /// If the map does not contain the key, the key-value pair is inserted
-    /// and this method returns `Ok`.
+    /// Checks the invariants of the bins after a mutation, and panics if one is broken.
+    ///
+    /// Only the bins `hash` maps to in the current and the next table are checked, or every bin
+    /// if `hash` is `None`, along with `size_ctl`. The count is not checked, since other threads
+    /// may be mutating the map concurrently.
+    #[cfg(feature = "debug-invariants")]
+    fn debug_invariants(&self, hash: Option<u64>, guard: &Guard<'_>) {
+        let checked = self
+            .validate_bins(hash, guard)
+            .and_then(|_| self.validate_size_ctl(guard));
+        if let Err(violation) = checked {
+            panic!("the map's invariants are broken: {}", violation);
+        }
+    }
+
+    #[cfg(not(feature = "debug-invariants"))]
+    #[inline(always)]
+    fn debug_invariants(&self, _hash: Option<u64>, _guard: &Guard<'_>) {}
+
+    /// Checks the bins `hash` maps to, or every bin if `hash` is `None`, in both the current
+    /// table and the table it is being resized into, if any. Returns the number of entries found.
+    fn validate_bins(
+        &self,
+        hash: Option<u64>,
+        guard: &Guard<'_>,
+    ) -> Result<usize, InvariantViolation> {
+        let table = self.table.load(Ordering::SeqCst, guard);
+        let next_table = self.next_table.load(Ordering::SeqCst, guard);
+        let mut entries = 0;
+        for table in [table, next_table] {
+            if table.is_null() {
+                continue;
+            }
+            // safety: we loaded the table under our guard, so it is not dropped until after we
+            // drop our guard, even if a resize finishes and retires it meanwhile.
+            let t = unsafe { table.deref() };
+            match hash {
+                Some(hash) => entries += Self::validate_bin(t, t.bini(hash), guard)?,
+                None => {
+                    for i in 0..t.len() {
+                        entries += Self::validate_bin(t, i, guard)?;
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Checks bin `i` of `t` while holding its lock, and returns the number of entries in it.
+    ///
+    /// Moved bins are skipped, since their entries are checked in the next table, and so are
+    /// reserved bins, which do not hold any entries yet.
+    fn validate_bin(
+        t: &Table<K, V>,
+        i: usize,
+        guard: &Guard<'_>,
+    ) -> Result<usize, InvariantViolation> {
+        loop {
+            let bin = t.bin(i, guard);
+            if bin.is_null() {
+                return Ok(0);
+            }
+
+            // safety: we read the bin under our guard, so it is not dropped until after we drop
+            // our guard, even if it is replaced meanwhile. once we hold its lock and have checked
+            // that it is still in the table, neither it nor the nodes it links can be removed.
+            match **unsafe { bin.deref() } {
+                BinEntry::Moved | BinEntry::Reservation(_) => return Ok(0),
+                BinEntry::Node(ref head) => {
+                    let _head_lock = head.lock.lock();
+                    if t.bin(i, guard) != bin {
+                        continue;
+                    }
+
+                    let mut entries = 0;
+                    let mut p = bin;
+                    while !p.is_null() {
+                        // safety: see above
+                        let node = unsafe { p.deref() }
+                            .as_node()
+                            .expect("linear bins only link Nodes");
+                        if t.bini(node.hash) != i {
+                            return Err(InvariantViolation::MisplacedEntry {
+                                bin: i,
+                                hash: node.hash,
+                            });
+                        }
+                        // bins are short, so we simply compare against every earlier entry
+                        let mut q = bin;
+                        while q != p {
+                            // safety: see above
+                            let earlier = unsafe { q.deref() }.as_node().unwrap();
+                            if earlier.hash == node.hash && earlier.key == node.key {
+                                return Err(InvariantViolation::DuplicateKey { bin: i });
+                            }
+                            q = earlier.next.load(Ordering::SeqCst, guard);
+                        }
+                        entries += 1;
+                        p = node.next.load(Ordering::SeqCst, guard);
+                    }
+                    return Ok(entries);
+                }
+                BinEntry::Tree(ref tree_bin) => {
+                    let _bin_lock = tree_bin.lock.lock();
+                    if t.bin(i, guard) != bin {
+                        continue;
+                    }
+
+                    let entries = tree_bin.validate(i, guard)?;
+                    let mut p = tree_bin.first.load(Ordering::SeqCst, guard);
+                    while !p.is_null() {
+                        // safety: see above. `validate` checked that the list only links
+                        // TreeNodes.
+                        let node = &unsafe { TreeNode::get_tree_node(p) }.node;
+                        if t.bini(node.hash) != i {
+                            return Err(InvariantViolation::MisplacedEntry {
+                                bin: i,
+                                hash: node.hash,
+                            });
+                        }
+                        p = node.next.load(Ordering::SeqCst, guard);
+                    }
+                    return Ok(entries);
+                }
+                BinEntry::TreeNode(_) => {
+                    return Err(InvariantViolation::MalformedTree {
+                        bin: i,
+                        reason: "the head of the bin is a tree node outside of a tree bin",
+                    })
+                }
+            }
+        }
+    }
+
+    /// Checks that `size_ctl` fits the table.
+    fn validate_size_ctl(&self, guard: &Guard<'_>) -> Result<(), InvariantViolation> {
+        // take a consistent snapshot of the fields that a resize updates
+        let (size_ctl, table, next_table) = loop {
+            let size_ctl = self.size_ctl.load(Ordering::SeqCst);
+            let table = self.table.load(Ordering::SeqCst, guard);
+            let next_table = self.next_table.load(Ordering::SeqCst, guard);
+            if self.size_ctl.load(Ordering::SeqCst) == size_ctl
+                && self.table.load(Ordering::SeqCst, guard) == table
+            {
+                break (size_ctl, table, next_table);
+            }
+            sync::yield_now();
+        };
+
+        let bins = if table.is_null() {
+            0
+        } else {
+            // safety: we loaded the table under our guard
+            unsafe { table.deref() }.len()
+        };
+        let fits = if bins == 0 {
+            // the initial capacity, 0 for the default, or -1 while the table is initialized
+            size_ctl >= -1
+        } else if size_ctl == -1 {
+            // the table may be left in place by a thread that lost the race to initialize it
+            true
+        } else if size_ctl >= 0 {
+            size_ctl == load_factor!(bins as isize) && next_table.is_null()
+        } else {
+            // a resize is ongoing, and size_ctl holds the stamp of the table plus one more than
+            // the number of threads transferring bins. once the new table is in place, but before
+            // size_ctl is updated, the stamp is still that of the old table, which was half as
+            // large.
+            let resizers = size_ctl.wrapping_sub(Self::resize_stamp(bins) << RESIZE_STAMP_SHIFT);
+            (1..=MAX_RESIZERS).contains(&resizers)
+                || (next_table.is_null()
+                    && size_ctl == (Self::resize_stamp(bins >> 1) << RESIZE_STAMP_SHIFT) + 1)
+        };
+        if fits {
+            Ok(())
+        } else {
+            Err(InvariantViolation::SizeCtl { size_ctl, bins })
+        }
+    }
+}
+
+// ===
+// the following methods _do_ introduce items into the map, and so must require that the keys and
+// values are thread safe, and can be garbage collected at a later time.
+// ===
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned. The key is left unchanged. See the [std-collections
+    /// documentation] for more.
+    ///
+    /// [`None`]: std::option::Option::None
+    /// [std-collections documentation]: https://doc.rust-lang.org/std/collections/index.html#insert-and-complex-keys
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// assert_eq!(map.pin().insert(37, "a"), None);
+    /// assert_eq!(map.pin().is_empty(), false);
+    ///
+    /// // you can also re-use a map pin like so:
+    /// let mref = map.pin();
+    ///
+    /// mref.insert(37, "b");
+    /// assert_eq!(mref.insert(37, "c"), Some(&"b"));
+    /// assert_eq!(mref.get(&37), Some(&"c"));
+    /// ```
+    pub fn insert<'g>(&'g self, key: K, value: V, guard: &'g Guard<'_>) -> Option<&'g V> {
+        self.check_guard(guard);
+        self.put(key, value, false, guard).before()
+    }
+
+    /// Inserts a key-value pair into the map, given the key's `hash`.
+    ///
+    /// `hash` must be the hash returned by [`HashMap::hash_key`] for `key`. If it is not, the
+    /// entry is stored in the wrong place, and lookups that hash the key will not find it.
+    ///
+    /// See also [`HashMap::insert`].
+    pub fn insert_with_hash<'g>(
+        &'g self,
+        hash: u64,
+        key: K,
+        value: V,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g V> {
+        self.check_guard(guard);
+        self.put_with_hash(hash, key, value, false, guard).before()
+    }
+
+    /// Inserts a key-value pair into the map unless the key already exists.
+    ///
+    /// If the map does not contain the key, the key-value pair is inserted
+    /// and this method returns `Ok`.
     ///
     /// If the map does contain the key, the map is left unchanged and this
     /// method returns `Err`.
@@ -1646,6 +3501,18 @@ where
         key: K,
         value: V,
         guard: &'g Guard<'_>,
+    ) -> Result<&'g V, TryInsertError<'g, V>> {
+        self.check_guard(guard);
+        self.try_insert_unchecked(key, value, guard)
+    }
+
+    /// Like [`HashMap::try_insert`], but without checking that `guard` belongs to this map's
+    /// collector. Callers must guarantee that it does.
+    pub(crate) fn try_insert_unchecked<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        guard: &'g Guard<'_>,
     ) -> Result<&'g V, TryInsertError<'g, V>> {
         match self.put(key, value, true, guard) {
             PutResult::Exists {
@@ -1662,14 +3529,59 @@ where
         }
     }
 
-    fn put<'g>(
+    pub(crate) fn put<'g>(
+        &'g self,
+        key: K,
+        value: V,
+        no_replacement: bool,
+        guard: &'g Guard<'_>,
+    ) -> PutResult<'g, V> {
+        self.put_with_hash(self.hash(&key), key, value, no_replacement, guard)
+    }
+
+    fn put_with_hash<'g>(
+        &'g self,
+        hash: u64,
+        key: K,
+        value: V,
+        no_replacement: bool,
+        guard: &'g Guard<'_>,
+    ) -> PutResult<'g, V> {
+        let mut sequenced = None;
+        let result = self.put_unnotified(hash, key, value, no_replacement, &mut sequenced, guard);
+        if let Some((seq, key)) = sequenced {
+            let (kind, old, new) = match result {
+                PutResult::Inserted { new } => (ChangeKind::Insert, None, new),
+                PutResult::Replaced { old, new } => (ChangeKind::Replace, Some(old), new),
+                PutResult::Exists { .. } => unreachable!("nothing changed"),
+            };
+            self.record(SequencedChange {
+                seq,
+                kind,
+                key,
+                old,
+                new: Some(new),
+            });
+        }
+        if !matches!(result, PutResult::Exists { .. }) {
+            self.watchers.notify(hash);
+            self.debug_invariants(Some(hash), guard);
+        }
+        result
+    }
+
+    /// Like `put_with_hash`, but does not notify subscribers to the key, nor record the change in
+    /// the change feed. Instead, if the change was sequenced, its sequence number and the key in
+    /// the map are stored in `sequenced`.
+    fn put_unnotified<'g>(
         &'g self,
+        hash: u64,
         mut key: K,
         value: V,
         no_replacement: bool,
+        sequenced: &mut Option<(u64, &'g K)>,
         guard: &'g Guard<'_>,
     ) -> PutResult<'g, V> {
-        let hash = self.hash(&key);
         let mut table = self.table.load(Ordering::SeqCst, guard);
         let mut bin_count;
         let value = Shared::boxed(value, &self.collector);
@@ -1705,8 +3617,13 @@ where
                 // fast path -- bin is empty so stick us at the front
                 let node =
                     Shared::boxed(BinEntry::Node(Node::new(hash, key, value)), &self.collector);
+                let feed_lock = self.lock_for_feed(node);
                 match t.cas_bin(bini, bin, node, guard) {
                     Ok(_old_null_ptr) => {
+                        // safety: the node is in the bin, and was put there under our guard
+                        let node_key = &unsafe { node.deref() }.as_node().unwrap().key;
+                        *sequenced = self.sequence().map(|seq| (seq, node_key));
+                        drop(feed_lock);
                         self.add_count(1, Some(0), guard);
                         // safety: we have not moved the node's value since we placed it into
                         // its `Atomic` in the very beginning of the method, so the ref is still
@@ -1718,6 +3635,7 @@ where
                         };
                     }
                     Err(changed) => {
+                        drop(feed_lock);
                         assert!(!changed.current.is_null());
                         bin = changed.current;
                         let BinEntry::Node(node) = unsafe { changed.new.into_box() }.value else {
@@ -1751,6 +3669,12 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                BinEntry::Reservation(ref lock) => {
+                    // the bin is reserved, and the reservation is replaced before its lock is released.
+                    // so wait for the lock, and then try again from the start.
+                    drop(lock.lock());
+                    continue;
+                }
                 BinEntry::Node(ref head)
                     if no_replacement && head.hash == hash && head.key == key =>
                 {
@@ -1809,6 +3733,7 @@ where
                                 };
                             } else {
                                 // update the value in the existing node
+                                *sequenced = self.sequence().map(|seq| (seq, &n.key));
                                 let now_garbage = n.value.swap(value, Ordering::SeqCst, guard);
                                 // NOTE: now_garbage == current_value
 
@@ -1836,135 +3761,1184 @@ where
                             break Some(current_value);
                         }
 
-                        // TODO: This Ordering can probably be relaxed due to the Mutex
-                        let next = n.next.load(Ordering::SeqCst, guard);
-                        if next.is_null() {
-                            // we're at the end of the bin -- stick the node here!
-                            let node = Shared::boxed(
-                                BinEntry::Node(Node::new(hash, key, value)),
-                                &self.collector,
-                            );
-                            n.next.store(node, Ordering::SeqCst);
-                            break None;
-                        }
-                        p = next;
+                        // TODO: This Ordering can probably be relaxed due to the Mutex
+                        let next = n.next.load(Ordering::SeqCst, guard);
+                        if next.is_null() {
+                            // we're at the end of the bin -- stick the node here!
+                            let node = Shared::boxed(
+                                BinEntry::Node(Node::new(hash, key, value)),
+                                &self.collector,
+                            );
+                            // safety: we just created the node, and it will not be dropped until
+                            // after we drop our guard once it is shared.
+                            let node_key = &unsafe { node.deref() }.as_node().unwrap().key;
+                            *sequenced = self.sequence().map(|seq| (seq, node_key));
+                            n.next.store(node, Ordering::SeqCst);
+                            break None;
+                        }
+                        p = next;
+
+                        bin_count += 1;
+                    };
+                    drop(head_lock);
+                }
+                // NOTE: BinEntry::Tree(ref tree_bin) if no_replacement && head.hash == h && &head.key == key
+                // cannot occur as in the Java code, TreeBins have a special, indicator hash value
+                BinEntry::Tree(ref tree_bin) => {
+                    // bin is non-empty, need to link into it, so we must take the lock
+                    let head_lock = tree_bin.lock.lock();
+
+                    // need to check that this is _still_ the correct bin
+                    let current_head = t.bin(bini, guard);
+                    if current_head != bin {
+                        // nope -- try again from the start
+                        continue;
+                    }
+
+                    // yes, it is still the head, so we can now "own" the bin
+                    // note that there can still be readers in the bin!
+
+                    // we don't actually count bins, just set this low enough
+                    // that we don't try to treeify the bin later
+                    bin_count = 2;
+                    let p = tree_bin.find_or_put_tree_val(hash, key, value, guard, &self.collector);
+                    if p.is_null() {
+                        // no TreeNode was returned, so the key did not previously exist in the
+                        // TreeBin. This means it was successfully put there by the call above
+                        // and we are done.
+                        *sequenced = self.sequence().map(|seq| {
+                            let first = tree_bin.first.load(Ordering::SeqCst, guard);
+                            (seq, Self::key_with_value(first, value, guard))
+                        });
+                        break;
+                    }
+                    // safety: the TreeBin was read under our guard, at which point the tree
+                    // structure was valid. Since our guard marks the current thread as active,
+                    // the TreeNodes remain valid for at least as long as we hold onto the
+                    // guard.
+                    // Structurally, TreeNodes always point to TreeNodes, so this is sound.
+                    let tree_node = unsafe { TreeNode::get_tree_node(p) };
+                    old_val = {
+                        let current_value = tree_node.node.value.load(Ordering::SeqCst, guard);
+                        // safety: since the value is present now, and we've held a guard from
+                        // the beginning of the search, the value cannot be dropped until after
+                        // we drop our guard.
+                        let current_value = unsafe { current_value.deref() };
+                        if no_replacement {
+                            // the key is not absent, so don't update because of
+                            // `no_replacement`, we don't use the new value, so we need to clean
+                            // it up and return it back to the caller
+                            // safety: we own value and did not share it
+                            return PutResult::Exists {
+                                current: current_value,
+                                not_inserted: unsafe { value.into_box() },
+                            };
+                        } else {
+                            *sequenced = self.sequence().map(|seq| (seq, &tree_node.node.key));
+                            let now_garbage =
+                                tree_node.node.value.swap(value, Ordering::SeqCst, guard);
+                            // NOTE: now_garbage == current_value
+
+                            // safety: need to guarantee that now_garbage is no longer
+                            // reachable. more specifically, no thread that executes _after_
+                            // this line can ever get a reference to now_garbage.
+                            //
+                            // here are the possible cases:
+                            //
+                            //  - another thread already has a reference to now_garbage.
+                            //    they must have read it before the call to swap while
+                            //    marked as active (holding a guard), and are included in
+                            //    the reference count. therefore t won't be freed until _after_
+                            //    it decrements the reference count, which can only happen
+                            //    when that thread drops its guard, and with it, any reference
+                            //    to the value.
+                            //  - another thread is about to get a reference to this value.
+                            //    they execute _after_ the swap, and therefore do _not_ get a
+                            //    reference to now_garbage (they get `value` instead). there are
+                            //    no other ways to get to a value except through its Node's
+                            //    `value` field (which is what we swapped), so freeing
+                            //    now_garbage is fine.
+                            unsafe { guard.retire_shared(now_garbage) };
+                        }
+                        Some(current_value)
+                    };
+                    drop(head_lock);
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            }
+            // NOTE: the Java code checks `bin_count` here because they also
+            // reach this point if the bin changed while obtaining the lock.
+            // However, our code doesn't (it uses continue) and `bin_count`
+            // _cannot_ be 0 at this point.
+            debug_assert_ne!(bin_count, 0);
+            if bin_count >= TREEIFY_THRESHOLD {
+                self.treeify_bin(t, bini, guard);
+            }
+            if let Some(old_val) = old_val {
+                return PutResult::Replaced {
+                    old: old_val,
+                    // safety: we have not moved the node's value since we placed it into
+                    // its `Atomic` in the very beginning of the method, so the ref is still
+                    // valid. since the value is not currently marked as garbage, and since
+                    // `value` was loaded under a guard, the returned reference will remain valid
+                    // for the guard's lifetime.
+                    new: unsafe { value.deref() },
+                };
+            }
+            break;
+        }
+        // increment count, since we only get here if we did not return an old (updated) value
+        debug_assert!(old_val.is_none());
+        self.add_count(1, Some(bin_count), guard);
+        PutResult::Inserted {
+            // safety: we have not moved the node's value since we placed it into
+            // its `Atomic` in the very beginning of the method, so the ref is still
+            // valid. since the value is not currently marked as garbage, and since
+            // `value` was loaded under a guard, the returned reference will remain valid
+            // for the guard's lifetime.
+            new: unsafe { value.deref() },
+        }
+    }
+
+    /// Inserts all key-value pairs from `items` into the map.
+    ///
+    /// Returns, for each item in order, the value previously associated with its key (if any),
+    /// just like [`HashMap::insert`] would. If the same key appears more than once in `items`,
+    /// the items are inserted in order, so the last one wins.
+    ///
+    /// This is faster than calling [`HashMap::insert`] for each item: the table is grown to fit
+    /// all of the new items up front, and items are grouped by the bin they belong to so that each
+    /// bin is locked only once for the whole batch.
+    ///
+    /// Note that, as with individual inserts, other threads may observe some of the items before
+    /// all of them have been inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert(1, "a", &guard);
+    /// assert_eq!(
+    ///     map.insert_many(vec![(1, "b"), (2, "c"), (2, "d")], &guard),
+    ///     vec![Some(&"a"), None, Some(&"c")]
+    /// );
+    /// assert_eq!(map.get(&1, &guard), Some(&"b"));
+    /// assert_eq!(map.get(&2, &guard), Some(&"d"));
+    /// ```
+    pub fn insert_many<'g, I>(&'g self, items: I, guard: &'g Guard<'_>) -> Vec<Option<&'g V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.check_guard(guard);
+        let mut pending: Vec<_> = items
+            .into_iter()
+            .enumerate()
+            .map(|(index, (key, value))| BatchEntry {
+                index,
+                hash: self.hash(&key),
+                key,
+                value: Shared::boxed(value, &self.collector),
+            })
+            .collect();
+        let mut replaced = vec![None; pending.len()];
+        if pending.is_empty() {
+            return replaced;
+        }
+        let hashes: Vec<u64> = pending.iter().map(|e| e.hash).collect();
+        let mut changes = Vec::new();
+
+        self.try_presize(self.len() + pending.len(), guard);
+
+        let mut table = self.table.load(Ordering::SeqCst, guard);
+        while !pending.is_empty() {
+            // safety: see argument in `put`
+            if table.is_null() || unsafe { table.deref() }.is_empty() {
+                table = self.init_table(guard);
+                continue;
+            }
+
+            // safety: see argument in `put`
+            let t = unsafe { table.deref() };
+
+            // group the entries by bin, and order the entries of each bin such that popping from
+            // the back yields them in the order they were given to us.
+            pending.sort_unstable_by_key(|e| (t.bini(e.hash), std::cmp::Reverse(e.index)));
+
+            let mut moved = Vec::new();
+            let mut added = 0;
+            let mut max_bin_count = 0;
+            while let Some(last) = pending.last() {
+                let bini = t.bini(last.hash);
+                let start = pending
+                    .iter()
+                    .rposition(|e| t.bini(e.hash) != bini)
+                    .map_or(0, |i| i + 1);
+                let mut batch = pending.split_off(start);
+
+                let (inserted, bin_count) =
+                    self.put_batch(t, bini, &mut batch, &mut replaced, &mut changes, guard);
+                for change in changes.drain(..) {
+                    self.record(change);
+                }
+                added += inserted;
+                match bin_count {
+                    Some(bin_count) => {
+                        max_bin_count = max_bin_count.max(bin_count);
+                        if bin_count >= TREEIFY_THRESHOLD {
+                            self.treeify_bin(t, bini, guard);
+                        }
+                    }
+                    None => moved.append(&mut batch),
+                }
+            }
+
+            if added != 0 {
+                self.add_count(added, Some(max_bin_count), guard);
+            }
+            if !moved.is_empty() {
+                table = self.help_transfer(table, guard);
+            }
+            pending = moved;
+        }
+
+        for hash in hashes {
+            self.watchers.notify(hash);
+            self.debug_invariants(Some(hash), guard);
+        }
+        replaced
+    }
+
+    /// Inserts the entries in `batch`, which must all belong in bin `bini` of `t`, while taking
+    /// the bin's lock only once.
+    ///
+    /// Entries are popped off the back of `batch` as they are inserted, and the previous value of
+    /// each is stored in `replaced`. If the map has a change feed, the changes are sequenced and
+    /// added to `changes`, to be recorded once the bin has been unlocked. Returns the number of
+    /// new entries, and the number of nodes seen in the bin. The latter is `None` if the bin has
+    /// been moved to a new table before all entries were inserted, in which case the remaining
+    /// entries are left in `batch`.
+    fn put_batch<'g>(
+        &'g self,
+        t: &'g Table<K, V>,
+        bini: usize,
+        batch: &mut Vec<BatchEntry<'g, K, V>>,
+        replaced: &mut [Option<&'g V>],
+        changes: &mut Vec<SequencedChange<'g, K, V>>,
+        guard: &'g Guard<'_>,
+    ) -> (isize, Option<usize>) {
+        let mut inserted = 0;
+        loop {
+            let bin = t.bin(bini, guard);
+            if bin.is_null() {
+                // fast path -- bin is empty so stick the first entry at the front
+                let e = batch.pop().expect("batch is non-empty");
+                let node = Shared::boxed(
+                    BinEntry::Node(Node::new(e.hash, e.key, e.value)),
+                    &self.collector,
+                );
+                let feed_lock = self.lock_for_feed(node);
+                match t.cas_bin(bini, bin, node, guard) {
+                    Ok(_) => {
+                        if let Some(seq) = self.sequence() {
+                            // safety: the node is in the bin, and was put there under our guard
+                            let node = unsafe { node.deref() }.as_node().unwrap();
+                            changes.push(SequencedChange {
+                                seq,
+                                kind: ChangeKind::Insert,
+                                key: &node.key,
+                                old: None,
+                                // safety: as for the node
+                                new: Some(unsafe { e.value.deref() }),
+                            });
+                        }
+                        drop(feed_lock);
+                        inserted += 1;
+                        if batch.is_empty() {
+                            return (inserted, Some(1));
+                        }
+                    }
+                    Err(changed) => {
+                        drop(feed_lock);
+                        // safety: we never shared the node
+                        let BinEntry::Node(node) = unsafe { changed.new.into_box() }.value else {
+                            unreachable!("we declared node and it is a BinEntry::Node");
+                        };
+                        batch.push(BatchEntry { key: node.key, ..e });
+                    }
+                }
+                continue;
+            }
+
+            // safety: bin is a valid pointer, see argument in `put`
+            match **unsafe { bin.deref() } {
+                BinEntry::Moved => return (inserted, None),
+                BinEntry::Reservation(ref lock) => {
+                    // the bin is reserved, and the reservation is replaced before its lock is released.
+                    // so wait for the lock, and then try again from the start.
+                    drop(lock.lock());
+                    continue;
+                }
+                BinEntry::Node(ref head) => {
+                    let head_lock = head.lock.lock();
+                    if t.bin(bini, guard) != bin {
+                        // the head changed -- try again from the start
+                        continue;
+                    }
+
+                    let mut bin_count = 1;
+                    while let Some(e) = batch.pop() {
+                        let mut p = bin;
+                        bin_count = 1;
+                        loop {
+                            // safety: we loaded the bin while holding a guard, so any retirements
+                            // must have seen us as active. the bin and its nodes cannot be dropped
+                            // until at least after we drop our guard.
+                            let n = unsafe { p.deref() }.as_node().unwrap();
+                            if n.hash == e.hash && n.key == e.key {
+                                let now_garbage = n.value.swap(e.value, Ordering::SeqCst, guard);
+                                // safety: the old value was present while we held our guard, and
+                                // is no longer reachable. see the argument in `put`.
+                                replaced[e.index] = Some(unsafe { now_garbage.deref() });
+                                if let Some(seq) = self.sequence() {
+                                    changes.push(SequencedChange {
+                                        seq,
+                                        kind: ChangeKind::Replace,
+                                        key: &n.key,
+                                        old: replaced[e.index],
+                                        // safety: the new value is in the bin, and was put there
+                                        // under our guard
+                                        new: Some(unsafe { e.value.deref() }),
+                                    });
+                                }
+                                unsafe { guard.retire_shared(now_garbage) };
+                                break;
+                            }
+
+                            let next = n.next.load(Ordering::SeqCst, guard);
+                            if next.is_null() {
+                                let node = Shared::boxed(
+                                    BinEntry::Node(Node::new(e.hash, e.key, e.value)),
+                                    &self.collector,
+                                );
+                                if let Some(seq) = self.sequence() {
+                                    // safety: we just created the node and its value, and they
+                                    // will not be dropped until after we drop our guard once they
+                                    // are shared.
+                                    changes.push(SequencedChange {
+                                        seq,
+                                        kind: ChangeKind::Insert,
+                                        key: &unsafe { node.deref() }.as_node().unwrap().key,
+                                        old: None,
+                                        new: Some(unsafe { e.value.deref() }),
+                                    });
+                                }
+                                n.next.store(node, Ordering::SeqCst);
+                                inserted += 1;
+                                break;
+                            }
+                            p = next;
+                            bin_count += 1;
+                        }
+                    }
+                    drop(head_lock);
+                    return (inserted, Some(bin_count));
+                }
+                BinEntry::Tree(ref tree_bin) => {
+                    let head_lock = tree_bin.lock.lock();
+                    if t.bin(bini, guard) != bin {
+                        // the bin changed -- try again from the start
+                        continue;
+                    }
+
+                    while let Some(e) = batch.pop() {
+                        let p = tree_bin.find_or_put_tree_val(
+                            e.hash,
+                            e.key,
+                            e.value,
+                            guard,
+                            &self.collector,
+                        );
+                        if p.is_null() {
+                            if let Some(seq) = self.sequence() {
+                                let first = tree_bin.first.load(Ordering::SeqCst, guard);
+                                changes.push(SequencedChange {
+                                    seq,
+                                    kind: ChangeKind::Insert,
+                                    key: Self::key_with_value(first, e.value, guard),
+                                    old: None,
+                                    // safety: the value is in the bin, and was put there under our
+                                    // guard
+                                    new: Some(unsafe { e.value.deref() }),
+                                });
+                            }
+                            inserted += 1;
+                            continue;
+                        }
+                        // safety: see argument in `put`
+                        let tree_node = unsafe { TreeNode::get_tree_node(p) };
+                        let now_garbage =
+                            tree_node.node.value.swap(e.value, Ordering::SeqCst, guard);
+                        // safety: the old value was present while we held our guard, and is no
+                        // longer reachable. see the argument in `put`.
+                        replaced[e.index] = Some(unsafe { now_garbage.deref() });
+                        if let Some(seq) = self.sequence() {
+                            changes.push(SequencedChange {
+                                seq,
+                                kind: ChangeKind::Replace,
+                                key: &tree_node.node.key,
+                                old: replaced[e.index],
+                                // safety: the new value is in the bin, and was put there under our
+                                // guard
+                                new: Some(unsafe { e.value.deref() }),
+                            });
+                        }
+                        unsafe { guard.retire_shared(now_garbage) };
+                    }
+                    drop(head_lock);
+                    // as in `put`, tree bins are never treeified again
+                    return (inserted, Some(2));
+                }
+                BinEntry::TreeNode(_) => unreachable!(
+                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                ),
+            }
+        }
+    }
+
+    /// Atomically reads and updates the entries for all of the given `keys`.
+    ///
+    /// The bins that hold the keys are locked in ascending order, so that concurrent
+    /// transactions cannot deadlock, and empty bins are reserved so that no entries can be
+    /// inserted into them. `f` is then called with one [`TransactionEntry`] per key, in the
+    /// order of `keys`, and may change or remove each entry. If `f` returns `Ok`, all of the
+    /// changes are committed before any of the bins are unlocked. If it returns `Err` (or
+    /// panics), none of them are.
+    ///
+    /// Other updates to the involved bins wait until the transaction has finished. Lookups do not
+    /// take locks, however, so a concurrent lookup may observe some of the changes before the
+    /// others.
+    ///
+    /// Since the bins are locked while `f` runs, `f` should be short, and must not modify the
+    /// map itself. Doing so may deadlock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` contains the same key more than once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    /// map.insert("alice", 100, &guard);
+    /// map.insert("bob", 20, &guard);
+    ///
+    /// // move 50 from alice to bob, but only if alice can afford it
+    /// let transfer = |amount| {
+    ///     map.transaction(
+    ///         &["alice", "bob"],
+    ///         |entries| {
+    ///             let from = *entries[0].get().ok_or("no such account")?;
+    ///             let to = *entries[1].get().ok_or("no such account")?;
+    ///             if from < amount {
+    ///                 return Err("insufficient funds");
+    ///             }
+    ///             entries[0].insert(from - amount);
+    ///             entries[1].insert(to + amount);
+    ///             Ok(())
+    ///         },
+    ///         &guard,
+    ///     )
+    /// };
+    ///
+    /// assert_eq!(transfer(50), Ok(()));
+    /// assert_eq!(transfer(80), Err("insufficient funds"));
+    /// assert_eq!(map.get("alice", &guard), Some(&50));
+    /// assert_eq!(map.get("bob", &guard), Some(&70));
+    /// ```
+    pub fn transaction<F, R, E>(&self, keys: &[K], f: F, guard: &Guard<'_>) -> Result<R, E>
+    where
+        F: FnOnce(&mut [TransactionEntry<'_, K, V>]) -> Result<R, E>,
+    {
+        self.check_guard(guard);
+        if keys.is_empty() {
+            return f(&mut []);
+        }
+
+        let hashes: Vec<u64> = keys.iter().map(|key| self.hash(key)).collect();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by(|&a, &b| (hashes[a], &keys[a]).cmp(&(hashes[b], &keys[b])));
+        assert!(
+            order.windows(2).all(|w| keys[w[0]] != keys[w[1]]),
+            "transaction keys must be distinct"
+        );
+
+        let mut table = self.table.load(Ordering::SeqCst, guard);
+        let (t, mut locks) = loop {
+            // safety: see argument in `put`
+            if table.is_null() || unsafe { table.deref() }.is_empty() {
+                table = self.init_table(guard);
+                continue;
+            }
+
+            // safety: see argument in `put`
+            let t = unsafe { table.deref() };
+            let mut bins: Vec<usize> = hashes.iter().map(|&hash| t.bini(hash)).collect();
+            bins.sort_unstable();
+            bins.dedup();
+            if let Some(locks) = self.lock_bins(t, &bins, guard) {
+                break (t, locks);
+            }
+
+            // one of the bins has been moved. we cannot follow it into the next table, since
+            // the other bins we need may not have been moved yet, so we help with the resize
+            // and then start over on whatever table is current.
+            self.help_transfer(table, guard);
+            table = self.table.load(Ordering::SeqCst, guard);
+        };
+
+        let mut entries: Vec<_> = keys
+            .iter()
+            .zip(hashes)
+            .map(|(key, hash)| {
+                let bini = t.bini(hash);
+                let node = Self::find_node(t, t.bin(bini, guard), hash, key, guard);
+                let current = node.map(|node| {
+                    let value = node.value.load(Ordering::SeqCst, guard);
+                    // safety: the value is present while we hold the bin lock, and cannot be
+                    // dropped until after we drop our guard.
+                    &**unsafe { value.deref() }
+                });
+                TransactionEntry {
+                    key: node.map_or(key, |node| &node.key),
+                    hash,
+                    bini,
+                    node,
+                    current,
+                    pending: None,
+                }
+            })
+            .collect();
+
+        // if `f` fails or panics, dropping `locks` releases the bins without any changes
+        let result = f(&mut entries)?;
+
+        let changed: Vec<u64> = entries
+            .iter()
+            .filter(|entry| entry.pending.is_some())
+            .map(|entry| entry.hash)
+            .collect();
+        entries.sort_unstable_by_key(|entry| entry.bini);
+        let mut entries = entries.into_iter().peekable();
+        // everything that may panic happens before the first change is made, so that the
+        // transaction commits all of its changes or none of them
+        let prepared: Vec<_> = locks
+            .bins
+            .iter()
+            .map(|locked| {
+                let bini = locked.index;
+                let bin_entries =
+                    std::iter::from_fn(|| entries.next_if(|entry| entry.bini == bini));
+                self.prepare_bin(locked, bin_entries, guard)
+            })
+            .collect();
+        let mut delta = 0;
+        let mut max_bin_count = 0;
+        let mut to_treeify = Vec::with_capacity(prepared.len());
+        let mut changes = Vec::with_capacity(changed.len());
+        for (locked, prepared) in locks.bins.iter_mut().zip(prepared) {
+            let bini = locked.index;
+            let (added, bin_count, is_tree) =
+                self.commit_bin(t, locked, prepared, &mut changes, guard);
+            delta += added;
+            max_bin_count = max_bin_count.max(bin_count);
+            if !is_tree && bin_count >= TREEIFY_THRESHOLD {
+                to_treeify.push(bini);
+            }
+        }
+        drop(locks);
+        for change in changes {
+            self.record(change);
+        }
+
+        if delta != 0 {
+            // removals cannot make the table too small, so only check for a resize on growth
+            let resize_hint = if delta > 0 { Some(max_bin_count) } else { None };
+            self.add_count(delta, resize_hint, guard);
+        }
+        for bini in to_treeify {
+            self.treeify_bin(t, bini, guard);
+        }
+        for hash in changed {
+            self.watchers.notify(hash);
+            self.debug_invariants(Some(hash), guard);
+        }
+        Ok(result)
+    }
+
+    /// Locks `key`, so that other updates to it wait until the returned [`KeyLock`] is dropped.
+    ///
+    /// This is useful for critical sections that involve more than the map, such as loading a
+    /// missing value from elsewhere without another thread doing the same. The lock covers the
+    /// bin that holds the key, or a reservation of that bin if it is empty, so updates to other
+    /// keys in the same bin also wait. Lookups do not take locks, and are not affected. A resize
+    /// does not wait for the lock either: it moves the lock into the new table along with the
+    /// key.
+    ///
+    /// The key can be read and updated through the lock. Other updates to the map from the
+    /// thread that holds the lock deadlock if they need the same bin, so they should be avoided.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let guard = map.guard();
+    ///
+    /// let mut lock = map.lock_key(&"config", &guard);
+    /// if lock.get().is_none() {
+    ///     // no other thread can insert the key while we compute its value
+    ///     lock.insert(String::from("loaded"));
+    /// }
+    /// drop(lock);
+    ///
+    /// assert_eq!(map.get("config", &guard).map(String::as_str), Some("loaded"));
+    /// ```
+    pub fn lock_key<'g>(&'g self, key: &K, guard: &'g Guard<'_>) -> KeyLock<'g, K, V, S> {
+        self.check_guard(guard);
+        let hash = self.hash(key);
+
+        let mut table = self.table.load(Ordering::SeqCst, guard);
+        loop {
+            // safety: see argument in `put`
+            if table.is_null() || unsafe { table.deref() }.is_empty() {
+                table = self.init_table(guard);
+                continue;
+            }
+
+            // safety: see argument in `put`
+            let t = unsafe { table.deref() };
+            if let Some(mut locks) = self.lock_bins(t, &[t.bini(hash)], guard) {
+                let mut locked = locks.bins.pop().expect("we locked one bin");
+                let mut key_locks = self.key_locks.lock();
+                // safety: the bin is locked, so it is not retired before we drop our guard
+                if !unsafe { locked.bin.deref() }.bin_lock().mark_key_locked() {
+                    // a transfer is already waiting for the bin, so let it have it first
+                    drop(key_locks);
+                    locks.bins.push(locked);
+                    drop(locks);
+                    sync::yield_now();
+                    continue;
+                }
+                // the bin stays locked until the `KeyLock` is dropped, see `KeyLocks`
+                std::mem::forget(locked.lock.take());
+                let id = key_locks.register(hash, table, locked.index, locked.bin);
+                drop(key_locks);
+                return KeyLock {
+                    map: self,
+                    key: key.clone(),
+                    hash,
+                    id,
+                    guard,
+                    changed: false,
+                    #[cfg(feature = "debug-invariants")]
+                    invariants: Self::debug_invariants,
+                };
+            }
+
+            // the bin has been moved, so the key is in the next table
+            table = self.help_transfer(table, guard);
+        }
+    }
+
+    /// Applies a change recorded by the change feed of another map to this map.
+    ///
+    /// The key is set to the event's new value, or removed if there is none. Applying every
+    /// event of a feed in order of [`seq`](ChangeEvent::seq) to an initially empty map leaves it
+    /// equal to the map the feed was recorded from. Events applied out of order may leave stale
+    /// values behind.
+    ///
+    /// See [`HashMap::with_change_sink`] for an example.
+    pub fn apply_event(&self, event: ChangeEvent<K, V>, guard: &Guard<'_>) {
+        match event.new {
+            Some(value) => {
+                self.insert(event.key, value, guard);
+            }
+            None => {
+                self.remove(&event.key, guard);
+            }
+        }
+    }
+
+    /// Locks the given bins of `t` for a transaction, in order, reserving the empty ones.
+    ///
+    /// `bins` must be sorted. Returns `None` if one of the bins has been moved.
+    fn lock_bins<'g, 'c>(
+        &'g self,
+        t: &'g Table<K, V>,
+        bins: &[usize],
+        guard: &'g Guard<'c>,
+    ) -> Option<TransactionLocks<'g, 'c, K, V>> {
+        let mut locks = TransactionLocks {
+            table: t,
+            bins: Vec::with_capacity(bins.len()),
+            guard,
+        };
+        for &bini in bins {
+            loop {
+                let bin = t.bin(bini, guard);
+                if bin.is_null() {
+                    let (reservation, lock) = self.new_reservation();
+                    if t.cas_bin(bini, bin, reservation, guard).is_ok() {
+                        locks.bins.push(LockedBin {
+                            index: bini,
+                            bin: reservation,
+                            lock: Some(lock),
+                        });
+                        break;
+                    }
+                    drop(lock);
+                    // safety: we never shared the reservation
+                    drop(unsafe { reservation.into_box() });
+                    continue;
+                }
+
+                // safety: bin is a valid pointer, see argument in `put`
+                let lock = match **unsafe { bin.deref() } {
+                    // dropping `locks` releases the bins we have locked so far
+                    BinEntry::Moved => return None,
+                    BinEntry::Node(ref head) => head.lock.lock(),
+                    BinEntry::Tree(ref tree_bin) => tree_bin.lock.lock(),
+                    BinEntry::Reservation(ref lock) => {
+                        // the reservation is replaced before its lock is released
+                        drop(lock.lock());
+                        continue;
+                    }
+                    BinEntry::TreeNode(_) => unreachable!(
+                        "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
+                    ),
+                };
+                if t.bin(bini, guard) != bin {
+                    // the head changed -- try again
+                    continue;
+                }
+                locks.bins.push(LockedBin {
+                    index: bini,
+                    bin,
+                    lock: Some(lock),
+                });
+                break;
+            }
+        }
+        Some(locks)
+    }
+
+    /// Prepares the changes in `entries` to the locked bin, so that `commit_bin` can apply them
+    /// without running any code that may panic.
+    ///
+    /// New keys are cloned and new values are boxed. Since removing a node from a tree bin may
+    /// require untreeifying it, and inserting one requires comparing keys, a tree bin that gains
+    /// or loses nodes is rebuilt here instead, as a tree bin or, if it becomes small enough, as
+    /// a linked bin.
+    fn prepare_bin<'g, 't, I>(
+        &'g self,
+        locked: &LockedBin<'g, K, V>,
+        entries: I,
+        guard: &'g Guard<'_>,
+    ) -> PreparedBin<'g, K, V>
+    where
+        I: Iterator<Item = TransactionEntry<'t, K, V>>,
+        K: 't,
+        V: 't,
+    {
+        let mut prepared = PreparedBin {
+            changes: Vec::new(),
+            replacement: None,
+            rebuilt: false,
+        };
+        let mut removes = false;
+        for entry in entries {
+            let Some(pending) = entry.pending else {
+                continue;
+            };
+            let change = match (entry.node, pending) {
+                (Some(node), Some(value)) => {
+                    let (_, node) = Self::locked_node(locked, node, guard);
+                    PreparedChange::Replace(node, Shared::boxed(value, &self.collector))
+                }
+                (Some(node), None) => {
+                    removes = true;
+                    let (p, node) = Self::locked_node(locked, node, guard);
+                    PreparedChange::Remove(p, node)
+                }
+                (None, Some(value)) => {
+                    let value = Shared::boxed(value, &self.collector);
+                    let node = Node::new(entry.hash, entry.key.clone(), value);
+                    PreparedChange::Insert(
+                        Shared::boxed(BinEntry::Node(node), &self.collector),
+                        value,
+                    )
+                }
+                (None, None) => continue,
+            };
+            prepared.changes.push(change);
+        }
+
+        // safety: we hold the lock on the bin, and read it under our guard
+        let tree_bin = match **unsafe { locked.bin.deref() } {
+            BinEntry::Tree(ref tree_bin) => tree_bin,
+            _ => {
+                if removes {
+                    prepared.replacement = Some(self.new_reservation());
+                }
+                return prepared;
+            }
+        };
+        if !prepared
+            .changes
+            .iter()
+            .any(|change| !matches!(change, PreparedChange::Replace(..)))
+        {
+            return prepared;
+        }
 
-                        bin_count += 1;
-                    };
-                    drop(head_lock);
+        let mut nodes = Vec::new();
+        let mut p = tree_bin.first.load(Ordering::SeqCst, guard);
+        while !p.is_null() {
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            let node = &unsafe { TreeNode::get_tree_node(p) }.node;
+            let change = prepared.changes.iter().find_map(|change| match *change {
+                PreparedChange::Replace(n, value) if std::ptr::eq(n, node) => {
+                    Some(Some(Atomic::from(value)))
                 }
-                // NOTE: BinEntry::Tree(ref tree_bin) if no_replacement && head.hash == h && &head.key == key
-                // cannot occur as in the Java code, TreeBins have a special, indicator hash value
-                BinEntry::Tree(ref tree_bin) => {
-                    // bin is non-empty, need to link into it, so we must take the lock
-                    let head_lock = tree_bin.lock.lock();
+                PreparedChange::Remove(_, n) if std::ptr::eq(n, node) => Some(None),
+                _ => None,
+            });
+            if let Some(value) = change.unwrap_or_else(|| Some(node.value.clone())) {
+                nodes.push((node.hash, node.key.clone(), value));
+            }
+            p = node.next.load(Ordering::SeqCst, guard);
+        }
+        for change in &mut prepared.changes {
+            if let PreparedChange::Insert(ref mut node, value) = *change {
+                // safety: we created the node above, and never shared it
+                let BinEntry::Node(node) =
+                    unsafe { std::mem::replace(node, Shared::null()).into_box() }.value
+                else {
+                    unreachable!("we created a Node");
+                };
+                nodes.push((node.hash, node.key, Atomic::from(value)));
+            }
+        }
 
-                    // need to check that this is _still_ the correct bin
-                    let current_head = t.bin(bini, guard);
-                    if current_head != bin {
-                        // nope -- try again from the start
-                        continue;
-                    }
+        prepared.rebuilt = true;
+        prepared.replacement = Some(if nodes.is_empty() {
+            self.new_reservation()
+        } else {
+            // a tree bin is only ever in a table that is large enough for tree bins
+            let bin = if nodes.len() > UNTREEIFY_THRESHOLD {
+                Self::build_tree_bin(nodes, &self.collector, guard)
+            } else {
+                Self::build_linked_bin(nodes, &self.collector)
+            };
+            // safety: we just created the bin, and it will not be dropped until after we drop
+            // our guard once it is shared.
+            (bin, unsafe { bin.deref() }.bin_lock().lock())
+        });
+        prepared
+    }
 
-                    // yes, it is still the head, so we can now "own" the bin
-                    // note that there can still be readers in the bin!
+    /// Applies the changes prepared by `prepare_bin` to the locked bin.
+    ///
+    /// Values are replaced in their nodes, removed nodes are unlinked, and new nodes are added to
+    /// the end of the bin, so lookups that run concurrently may see some of the changes before
+    /// others. A rebuilt bin replaces the locked bin as a whole. If the head of the bin changes,
+    /// the new head is locked before it is stored, and `locked` is updated to hold it, so the bin
+    /// stays locked throughout; if the bin becomes empty, it is replaced by a reservation. If the
+    /// map has a change feed, the changes are sequenced and added to `changes`, which must have
+    /// room for them. Returns the change in the number of entries, the number of entries in the
+    /// bin, and whether it is a tree bin.
+    fn commit_bin<'g>(
+        &'g self,
+        t: &'g Table<K, V>,
+        locked: &mut LockedBin<'g, K, V>,
+        mut prepared: PreparedBin<'g, K, V>,
+        changes: &mut Vec<SequencedChange<'g, K, V>>,
+        guard: &'g Guard<'_>,
+    ) -> (isize, usize, bool) {
+        let rebuilt = prepared.rebuilt;
+        if rebuilt {
+            let old = locked.bin;
+            let bin = prepared
+                .replacement
+                .take()
+                .expect("a rebuilt bin has a replacement");
+            self.replace_locked(t, locked, bin);
+            // the old bin is now garbage, but its values are not, since the ones that are not
+            // changed are re-used in the new bin, and the others are retired below.
+            // safety: same as in `replace_node`
+            unsafe { TreeBin::defer_drop_without_values(old, guard) };
+        }
 
-                    // we don't actually count bins, just set this low enough
-                    // that we don't try to treeify the bin later
-                    bin_count = 2;
-                    let p = tree_bin.find_or_put_tree_val(hash, key, value, guard, &self.collector);
-                    if p.is_null() {
-                        // no TreeNode was returned, so the key did not previously exist in the
-                        // TreeBin. This means it was successfully put there by the call above
-                        // and we are done.
-                        break;
+        let mut delta = 0;
+        for change in prepared.changes.drain(..) {
+            match change {
+                PreparedChange::Replace(node, value) => {
+                    let now_garbage = if rebuilt {
+                        node.value.load(Ordering::SeqCst, guard)
+                    } else {
+                        node.value.swap(value, Ordering::SeqCst, guard)
+                    };
+                    if let Some(seq) = self.sequence() {
+                        changes.push(SequencedChange {
+                            seq,
+                            kind: ChangeKind::Replace,
+                            key: &node.key,
+                            // safety: the old value was present while we held the bin lock, and
+                            // it will not be dropped until after we drop our guard.
+                            old: Some(unsafe { now_garbage.deref() }),
+                            // safety: we just stored the value, and it will not be dropped
+                            // until after we drop our guard.
+                            new: Some(unsafe { value.deref() }),
+                        });
                     }
-                    // safety: the TreeBin was read under our guard, at which point the tree
-                    // structure was valid. Since our guard marks the current thread as active,
-                    // the TreeNodes remain valid for at least as long as we hold onto the
-                    // guard.
-                    // Structurally, TreeNodes always point to TreeNodes, so this is sound.
-                    let tree_node = unsafe { TreeNode::get_tree_node(p) };
-                    old_val = {
-                        let current_value = tree_node.node.value.load(Ordering::SeqCst, guard);
-                        // safety: since the value is present now, and we've held a guard from
-                        // the beginning of the search, the value cannot be dropped until after
-                        // we drop our guard.
-                        let current_value = unsafe { current_value.deref() };
-                        if no_replacement {
-                            // the key is not absent, so don't update because of
-                            // `no_replacement`, we don't use the new value, so we need to clean
-                            // it up and return it back to the caller
-                            // safety: we own value and did not share it
-                            return PutResult::Exists {
-                                current: current_value,
-                                not_inserted: unsafe { value.into_box() },
-                            };
-                        } else {
-                            let now_garbage =
-                                tree_node.node.value.swap(value, Ordering::SeqCst, guard);
-                            // NOTE: now_garbage == current_value
-
-                            // safety: need to guarantee that now_garbage is no longer
-                            // reachable. more specifically, no thread that executes _after_
-                            // this line can ever get a reference to now_garbage.
-                            //
-                            // here are the possible cases:
-                            //
-                            //  - another thread already has a reference to now_garbage.
-                            //    they must have read it before the call to swap while
-                            //    marked as active (holding a guard), and are included in
-                            //    the reference count. therefore t won't be freed until _after_
-                            //    it decrements the reference count, which can only happen
-                            //    when that thread drops its guard, and with it, any reference
-                            //    to the value.
-                            //  - another thread is about to get a reference to this value.
-                            //    they execute _after_ the swap, and therefore do _not_ get a
-                            //    reference to now_garbage (they get `value` instead). there are
-                            //    no other ways to get to a value except through its Node's
-                            //    `value` field (which is what we swapped), so freeing
-                            //    now_garbage is fine.
-                            unsafe { guard.retire_shared(now_garbage) };
-                        }
-                        Some(current_value)
+                    // safety: the old value is no longer reachable, see the argument in `put`
+                    unsafe { guard.retire_shared(now_garbage) };
+                }
+                PreparedChange::Remove(p, node) => {
+                    let value = node.value.load(Ordering::SeqCst, guard);
+                    if let Some(seq) = self.sequence() {
+                        changes.push(SequencedChange {
+                            seq,
+                            kind: ChangeKind::Remove,
+                            key: &node.key,
+                            // safety: the value was present while we held the bin lock, and it
+                            // will not be dropped until after we drop our guard.
+                            old: Some(unsafe { value.deref() }),
+                            new: None,
+                        });
+                    }
+                    if !rebuilt {
+                        self.unlink_locked(t, locked, p, &mut prepared.replacement, guard);
+                    }
+                    // safety: the node has been unlinked, so the value is no longer reachable.
+                    // threads that read it before did so while holding a guard, so it will not
+                    // be dropped until they release it.
+                    unsafe { guard.retire_shared(value) };
+                    delta -= 1;
+                }
+                PreparedChange::Insert(node, value) => {
+                    let key = if rebuilt {
+                        Self::key_with_value(Self::first_locked(locked, guard), value, guard)
+                    } else {
+                        self.link_locked(t, locked, node, guard)
                     };
-                    drop(head_lock);
+                    if let Some(seq) = self.sequence() {
+                        changes.push(SequencedChange {
+                            seq,
+                            kind: ChangeKind::Insert,
+                            key,
+                            old: None,
+                            // safety: the value is in the bin now, and will not be dropped until
+                            // after we drop our guard.
+                            new: Some(unsafe { value.deref() }),
+                        });
+                    }
+                    delta += 1;
                 }
-                BinEntry::TreeNode(_) => unreachable!(
-                    "The head of a bin cannot be a TreeNode directly without BinEntry::Tree"
-                ),
             }
-            // NOTE: the Java code checks `bin_count` here because they also
-            // reach this point if the bin changed while obtaining the lock.
-            // However, our code doesn't (it uses continue) and `bin_count`
-            // _cannot_ be 0 at this point.
-            debug_assert_ne!(bin_count, 0);
-            if bin_count >= TREEIFY_THRESHOLD {
-                self.treeify_bin(t, bini, guard);
+        }
+
+        let mut p = Self::first_locked(locked, guard);
+        let mut bin_count = 0;
+        while !p.is_null() {
+            bin_count += 1;
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            p = match **unsafe { p.deref() } {
+                BinEntry::Node(ref node) => node.next.load(Ordering::SeqCst, guard),
+                BinEntry::TreeNode(ref tree_node) => {
+                    tree_node.node.next.load(Ordering::SeqCst, guard)
+                }
+                _ => unreachable!("bins only ever link Nodes or TreeNodes"),
+            };
+        }
+        // safety: as above
+        let is_tree = matches!(**unsafe { locked.bin.deref() }, BinEntry::Tree(_));
+        (delta, bin_count, is_tree)
+    }
+
+    /// Returns the first node of the locked bin, or null if it is a reservation.
+    fn first_locked<'g>(
+        locked: &LockedBin<'g, K, V>,
+        guard: &'g Guard<'_>,
+    ) -> Shared<'g, BinEntry<K, V>> {
+        // safety: we hold the lock on the bin, and read it under our guard
+        match **unsafe { locked.bin.deref() } {
+            BinEntry::Node(_) => locked.bin,
+            BinEntry::Tree(ref tree_bin) => tree_bin.first.load(Ordering::SeqCst, guard),
+            BinEntry::Reservation(_) => Shared::null(),
+            BinEntry::Moved | BinEntry::TreeNode(_) => {
+                unreachable!("a locked bin is never moved, and always starts with its head")
             }
-            if let Some(old_val) = old_val {
-                return PutResult::Replaced {
-                    old: old_val,
-                    // safety: we have not moved the node's value since we placed it into
-                    // its `Atomic` in the very beginning of the method, so the ref is still
-                    // valid. since the value is not currently marked as garbage, and since
-                    // `value` was loaded under a guard, the returned reference will remain valid
-                    // for the guard's lifetime.
-                    new: unsafe { value.deref() },
-                };
+        }
+    }
+
+    /// Returns the pointer to `node`, which is in the locked bin, along with the node itself.
+    fn locked_node<'g>(
+        locked: &LockedBin<'g, K, V>,
+        node: &Node<K, V>,
+        guard: &'g Guard<'_>,
+    ) -> (Shared<'g, BinEntry<K, V>>, &'g Node<K, V>) {
+        let mut p = Self::first_locked(locked, guard);
+        loop {
+            assert!(!p.is_null(), "the entry's node is in its locked bin");
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            let n = match **unsafe { p.deref() } {
+                BinEntry::Node(ref n) => n,
+                BinEntry::TreeNode(ref tree_node) => &tree_node.node,
+                _ => unreachable!("bins only ever link Nodes or TreeNodes"),
+            };
+            if std::ptr::eq(n, node) {
+                return (p, n);
             }
-            break;
+            p = n.next.load(Ordering::SeqCst, guard);
         }
-        // increment count, since we only get here if we did not return an old (updated) value
-        debug_assert!(old_val.is_none());
-        self.add_count(1, Some(bin_count), guard);
-        PutResult::Inserted {
-            // safety: we have not moved the node's value since we placed it into
-            // its `Atomic` in the very beginning of the method, so the ref is still
-            // valid. since the value is not currently marked as garbage, and since
-            // `value` was loaded under a guard, the returned reference will remain valid
-            // for the guard's lifetime.
-            new: unsafe { value.deref() },
+    }
+
+    /// Unlinks the node `p` from the locked linked bin, and retires it.
+    ///
+    /// If `p` is the head of the bin, the bin is replaced and `locked` is updated to hold the new
+    /// bin, which is the `spare` reservation if the bin becomes empty. The value of `p` is not
+    /// retired.
+    fn unlink_locked<'g>(
+        &'g self,
+        t: &'g Table<K, V>,
+        locked: &mut LockedBin<'g, K, V>,
+        p: Shared<'g, BinEntry<K, V>>,
+        spare: &mut Option<NewBin<'g, K, V>>,
+        guard: &'g Guard<'_>,
+    ) {
+        let bin = locked.bin;
+        let mut pred = Shared::null();
+        let mut e = bin;
+        while e != p {
+            pred = e;
+            // safety: the nodes of a locked bin cannot be removed, and were read under our guard
+            e = unsafe { e.deref() }
+                .as_node()
+                .unwrap()
+                .next
+                .load(Ordering::SeqCst, guard);
+        }
+        // safety: as above
+        let next = unsafe { p.deref() }
+            .as_node()
+            .unwrap()
+            .next
+            .load(Ordering::SeqCst, guard);
+        if !pred.is_null() {
+            // safety: as above
+            unsafe { pred.deref() }
+                .as_node()
+                .unwrap()
+                .next
+                .store(next, Ordering::SeqCst);
+        } else if next.is_null() {
+            let reservation = spare
+                .take()
+                .expect("a bin with removals has a spare reservation");
+            self.replace_locked(t, locked, reservation);
+        } else {
+            // nobody else locks a node that is not the head of its bin for longer than it takes
+            // them to notice, so this does not wait for long
+            // safety: as above
+            let lock = unsafe { next.deref() }.as_node().unwrap().lock.lock();
+            self.replace_locked(t, locked, (next, lock));
+        }
+        // safety: the node is no longer reachable. threads that read it before did so while
+        // holding a guard, so it will not be dropped until they release it.
+        unsafe { guard.retire_shared(p) };
+    }
+
+    /// Adds `node`, a `BinEntry::Node` whose key is not in the locked linked bin yet, to the end
+    /// of the bin, and returns a reference to its key.
+    ///
+    /// If the bin is a reservation, it is replaced by the new node, and `locked` is updated to
+    /// hold it.
+    fn link_locked<'g>(
+        &'g self,
+        t: &'g Table<K, V>,
+        locked: &mut LockedBin<'g, K, V>,
+        node: Shared<'g, BinEntry<K, V>>,
+        guard: &'g Guard<'_>,
+    ) -> &'g K {
+        let bin = locked.bin;
+        // safety: we created the node in `prepare_bin`, and it will not be dropped until after we
+        // drop our guard once it is shared.
+        let new_node = unsafe { node.deref() }.as_node().unwrap();
+        // safety: we hold the lock on the bin, and read it under our guard
+        if let BinEntry::Reservation(_) = **unsafe { bin.deref() } {
+            self.replace_locked(t, locked, (node, new_node.lock.lock()));
+            // safety: the reservation has been replaced, so no thread that executes after this
+            // can get a reference to it. threads that read it before did so while holding a
+            // guard, so it will not be dropped until they release it.
+            unsafe { guard.retire_shared(bin) };
+        } else {
+            let mut tail = bin;
+            loop {
+                // safety: the nodes of a locked bin cannot be removed, and were read under our
+                // guard
+                let n = unsafe { tail.deref() }.as_node().unwrap();
+                let next = n.next.load(Ordering::SeqCst, guard);
+                if next.is_null() {
+                    n.next.store(node, Ordering::SeqCst);
+                    break;
+                }
+                tail = next;
+            }
+        }
+        &new_node.key
+    }
+
+    /// Stores `new` in place of the locked bin, which `new` holds the lock of, and updates
+    /// `locked` to hold it instead.
+    ///
+    /// Writers that were waiting for the old bin will see that it has been replaced, and then
+    /// wait for the new one.
+    fn replace_locked<'g>(
+        &'g self,
+        t: &'g Table<K, V>,
+        locked: &mut LockedBin<'g, K, V>,
+        (bin, lock): NewBin<'g, K, V>,
+    ) {
+        if locked.lock.is_none() {
+            // the new bin is marked before anyone can see it, so a transfer that finds it will
+            // look for it among the `KeyLocks` rather than wait for its lock.
+            // safety: the new bin is not shared yet
+            let marked = unsafe { bin.deref() }.bin_lock().mark_key_locked();
+            debug_assert!(marked, "nobody can wait for a bin they cannot see");
+        }
+        t.store_bin(locked.index, bin);
+        let old = std::mem::replace(&mut locked.bin, bin);
+        match locked.lock {
+            Some(ref mut held) => *held = lock,
+            None => {
+                std::mem::forget(lock);
+                // safety: the old bin has been replaced, so the `KeyLock` that holds it no
+                // longer needs its lock, and it will not be dropped until after we drop our guard
+                unsafe {
+                    old.deref().bin_lock().unmark_key_locked();
+                    unlock_leaked(old);
+                }
+            }
         }
     }
 
@@ -2002,16 +4976,31 @@ where
         guard: &'g Guard<'_>,
     ) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
         F: FnOnce(&K, &V) -> Option<V>,
     {
         self.check_guard(guard);
+        self.compute_if_present_unchecked(key, remapping_function, guard)
+    }
+
+    /// Like [`HashMap::compute_if_present`], but without checking that `guard` belongs to this
+    /// map's collector. Callers must guarantee that it does.
+    pub(crate) fn compute_if_present_unchecked<'g, Q, F>(
+        &'g self,
+        key: &Q,
+        remapping_function: F,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        F: FnOnce(&K, &V) -> Option<V>,
+    {
         let hash = self.hash(&key);
 
         let mut table = self.table.load(Ordering::SeqCst, guard);
         let mut new_val = None;
         let mut removed_node = false;
+        let mut change: Option<(u64, &'g K, &'g V)> = None;
         let mut bin_count;
         loop {
             // safety: see argument below for !is_null case
@@ -2068,6 +5057,12 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                BinEntry::Reservation(ref lock) => {
+                    // the bin is reserved, and the reservation is replaced before its lock is released.
+                    // so wait for the lock, and then try again from the start.
+                    drop(lock.lock());
+                    continue;
+                }
                 BinEntry::Node(ref head) => {
                     // bin is non-empty, need to link into it, so we must take the lock
                     let head_lock = head.lock.lock();
@@ -2094,7 +5089,7 @@ where
                         let n = unsafe { p.deref() }.as_node().unwrap();
                         // TODO: This Ordering can probably be relaxed due to the Mutex
                         let next = n.next.load(Ordering::SeqCst, guard);
-                        if n.hash == hash && n.key.borrow() == key {
+                        if n.hash == hash && key.equivalent(&n.key) {
                             // the key already exists in the map!
                             let current_value = n.value.load(Ordering::SeqCst, guard);
 
@@ -2103,6 +5098,9 @@ where
                             // we drop our guard.
                             let new_value =
                                 remapping_function(&n.key, unsafe { current_value.deref() });
+                            change = self
+                                .sequence()
+                                .map(|seq| (seq, &n.key, &**unsafe { current_value.deref() }));
 
                             if let Some(value) = new_value {
                                 let value = Shared::boxed(value, &self.collector);
@@ -2230,6 +5228,9 @@ where
                             // we drop our guard.
                             let new_value =
                                 remapping_function(&n.key, unsafe { current_value.deref() });
+                            change = self
+                                .sequence()
+                                .map(|seq| (seq, &n.key, &**unsafe { current_value.deref() }));
 
                             if let Some(value) = new_value {
                                 let value = Shared::boxed(value, &self.collector);
@@ -2317,6 +5318,23 @@ where
             // decrement count
             self.add_count(-1, Some(bin_count), guard);
         }
+        if let Some((seq, key, old)) = change {
+            self.record(SequencedChange {
+                seq,
+                kind: if removed_node {
+                    ChangeKind::Remove
+                } else {
+                    ChangeKind::Replace
+                },
+                key,
+                old: Some(old),
+                new: new_val.map(|linked| &**linked),
+            });
+        }
+        if removed_node || new_val.is_some() {
+            self.watchers.notify(hash);
+            self.debug_invariants(Some(hash), guard);
+        }
         new_val.map(|linked| &**linked)
     }
 
@@ -2341,8 +5359,7 @@ where
     /// ```
     pub fn remove<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         // NOTE: _technically_, this method shouldn't require the thread-safety bounds, but a) that
         // would require special-casing replace_node for when new_value.is_none(), and b) it's sort
@@ -2374,13 +5391,88 @@ where
     /// ```
     pub fn remove_entry<'g, Q>(&'g self, key: &Q, guard: &'g Guard<'_>) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.check_guard(guard);
         self.replace_node(key, None, None, guard)
     }
 
+    /// Removes a key-value pair from the map given the key's `hash`, and returns the removed
+    /// value (if any).
+    ///
+    /// `hash` must be the hash returned by [`HashMap::hash_key`] for `key`. If it is not, the
+    /// removal is performed in the wrong place, and is likely to return `None`.
+    ///
+    /// See also [`HashMap::remove`].
+    pub fn remove_with_hash<'g, Q>(
+        &'g self,
+        hash: u64,
+        key: &Q,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g V>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.check_guard(guard);
+        self.replace_node_with_hash(hash, key, None, None, guard)
+            .map(|(_, v)| v)
+    }
+
+    /// Inserts a key-value pair into the map, and returns a clone of the previous value (if any).
+    ///
+    /// Unlike [`HashMap::insert`], this method does not take a `Guard`. See
+    /// [`HashMap::get_cloned`] for how the current thread is pinned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// assert_eq!(map.insert_owned(37, String::from("a")), None);
+    /// assert_eq!(map.insert_owned(37, String::from("b")), Some(String::from("a")));
+    /// assert_eq!(map.get_cloned(&37), Some(String::from("b")));
+    /// ```
+    pub fn insert_owned(&self, key: K, value: V) -> Option<V>
+    where
+        V: Clone,
+    {
+        let guard = self.guard();
+        self.put(key, value, false, &guard).before().cloned()
+    }
+
+    /// Removes a key from the map, and returns a clone of the removed value (if any).
+    ///
+    /// Unlike [`HashMap::remove`], this method does not take a `Guard`. See
+    /// [`HashMap::get_cloned`] for how the current thread is pinned instead.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the key type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.insert_owned(1, String::from("a"));
+    /// assert_eq!(map.remove_owned(&1), Some(String::from("a")));
+    /// assert_eq!(map.remove_owned(&1), None);
+    /// ```
+    pub fn remove_owned<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        V: Clone,
+    {
+        let guard = self.guard();
+        self.replace_node(key, None, None, &guard)
+            .map(|(_, v)| v.clone())
+    }
+
     /// Replaces node value with `new_value`.
     ///
     /// If an `observed_value` is provided, the replacement only happens if `observed_value` equals
@@ -2398,21 +5490,34 @@ where
     ///
     /// [`Ord`]: std::cmp::Ord
     /// [`Hash`]: std::hash::Hash
-    fn replace_node<'g, Q>(
+    pub(crate) fn replace_node<'g, Q>(
+        &'g self,
+        key: &Q,
+        new_value: Option<V>,
+        observed_value: Option<Shared<'g, V>>,
+        guard: &'g Guard<'_>,
+    ) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.replace_node_with_hash(self.hash(key), key, new_value, observed_value, guard)
+    }
+
+    fn replace_node_with_hash<'g, Q>(
         &'g self,
+        hash: u64,
         key: &Q,
         new_value: Option<V>,
         observed_value: Option<Shared<'g, V>>,
         guard: &'g Guard<'_>,
     ) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Comparable<K>,
     {
-        let hash = self.hash(key);
-
         let is_remove = new_value.is_none();
         let mut old_val = None;
+        let mut seq = None;
+        let mut replacement = None;
         let mut table = self.table.load(Ordering::SeqCst, guard);
         loop {
             if table.is_null() {
@@ -2463,6 +5568,12 @@ where
                     table = self.help_transfer(table, guard);
                     continue;
                 }
+                BinEntry::Reservation(ref lock) => {
+                    // the bin is reserved, and the reservation is replaced before its lock is released.
+                    // so wait for the lock, and then try again from the start.
+                    drop(lock.lock());
+                    continue;
+                }
                 BinEntry::Node(ref head) => {
                     let head_lock = head.lock.lock();
 
@@ -2484,20 +5595,22 @@ where
                         // if it was obtained from a next pointer.
                         let n = unsafe { e.deref() }.as_node().unwrap();
                         let next = n.next.load(Ordering::SeqCst, guard);
-                        if n.hash == hash && n.key.borrow() == key {
+                        if n.hash == hash && key.equivalent(&n.key) {
                             let ev = n.value.load(Ordering::SeqCst, guard);
 
                             // only replace the node if the value is the one we expected at method call
                             if observed_value.map(|ov| ov == ev).unwrap_or(true) {
                                 // we remember the old value so that we can return it and mark it for deletion below
                                 old_val = Some((&n.key, ev));
+                                seq = self.sequence();
 
                                 // found the node but we have a new value to replace the old one
                                 if let Some(nv) = new_value {
-                                    n.value.store(
-                                        Shared::boxed(nv, &self.collector),
-                                        Ordering::SeqCst,
-                                    );
+                                    let nv = Shared::boxed(nv, &self.collector);
+                                    // safety: we just created the value, and it will not be
+                                    // dropped until after we drop our guard once it is shared.
+                                    replacement = Some(&**unsafe { nv.deref() });
+                                    n.value.store(nv, Ordering::SeqCst);
                                     // we are just replacing entry value and we do not want to remove the node
                                     // so we stop iterating here
                                     break;
@@ -2570,11 +5683,15 @@ where
                     if observed_value.map(|ov| ov == pv).unwrap_or(true) {
                         // we remember the old value so that we can return it and mark it for deletion below
                         old_val = Some((&n.key, pv));
+                        seq = self.sequence();
 
                         if let Some(nv) = new_value {
                             // found the node but we have a new value to replace the old one
-                            n.value
-                                .store(Shared::boxed(nv, &self.collector), Ordering::SeqCst);
+                            let nv = Shared::boxed(nv, &self.collector);
+                            // safety: we just created the value, and it will not be dropped
+                            // until after we drop our guard once it is shared.
+                            replacement = Some(&**unsafe { nv.deref() });
+                            n.value.store(nv, Ordering::SeqCst);
                         } else {
                             // drop `p` without its value, since the old value is dropped
                             // in the check on `old_val` below
@@ -2632,6 +5749,22 @@ where
                 //    `value` field (which is what we swapped), so freeing
                 //    now_garbage is fine.
                 unsafe { guard.retire_shared(val) };
+                if let Some(seq) = seq {
+                    self.record(SequencedChange {
+                        seq,
+                        kind: if is_remove {
+                            ChangeKind::Remove
+                        } else {
+                            ChangeKind::Replace
+                        },
+                        key,
+                        // safety: the old value was present while we held our guard
+                        old: Some(unsafe { val.deref() }),
+                        new: replacement,
+                    });
+                }
+                self.watchers.notify(hash);
+                self.debug_invariants(Some(hash), guard);
 
                 // safety: the lifetime of the reference is bound to the guard
                 // supplied which means that the memory will not be freed
@@ -2810,6 +5943,10 @@ where
                         }
                     }
                 }
+                BinEntry::Reservation(_) => {
+                    // The bin we wanted to treeify was emptied and has since been reserved, so
+                    // there is nothing left to treeify.
+                }
                 BinEntry::Moved | BinEntry::Tree(_) => {
                     // The bin we wanted to treeify has changed under us. This is possible because
                     // the call to `treeify_bin` does not happen inside the critical section of its
@@ -2930,7 +6067,7 @@ where
     V: Debug,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let guard = self.collector.enter();
+        let guard = reclaim::enter(&self.collector);
         f.debug_map().entries(self.iter(&guard)).finish()
     }
 }
@@ -2978,7 +6115,7 @@ where
             (iter.size_hint().0 + 1) / 2
         };
 
-        let guard = self.collector.enter();
+        let guard = reclaim::enter(&self.collector);
         self.reserve(reserve, &guard);
         (*self).put_all(iter, &guard);
     }
@@ -3049,21 +6186,183 @@ where
     V: Sync + Send + Clone,
     S: BuildHasher + Clone,
 {
+    /// Clones the map bin by bin.
+    ///
+    /// Since the hasher is cloned as well, every entry lands in the same bin of the new map, so
+    /// the stored hashes are reused and the new table is built directly rather than through
+    /// [`HashMap::insert`].
+    ///
+    /// The clone of a map that was created by a [`MapGroup`](crate::MapGroup) is in the same
+    /// group, and accepts the group's guards. Any other map is cloned with a new collector that
+    /// is configured like the map's own.
     fn clone(&self) -> HashMap<K, V, S> {
-        let cloned_map = Self::with_capacity_and_hasher(self.len(), self.build_hasher.clone())
-            .with_collector(self.collector.clone());
+        let mut cloned_map = if self.grouped {
+            Self::with_capacity_and_shared_collector(
+                0,
+                self.build_hasher.clone(),
+                Arc::clone(&self.collector),
+            )
+        } else {
+            Self::with_hasher(self.build_hasher.clone())
+                .with_collector(Collector::clone(&self.collector))
+        };
 
-        {
-            let guard = self.collector.enter();
-            let cloned_guard = cloned_map.collector.enter();
-            for (k, v) in self.iter(&guard) {
-                cloned_map.insert(k.clone(), v.clone(), &cloned_guard);
+        let bins = {
+            let guard = reclaim::enter(&self.collector);
+            let table = self.table.load(Ordering::SeqCst, &guard);
+            if table.is_null() {
+                return cloned_map;
             }
-        }
+            // safety: we loaded the table while holding a guard, so it will not be dropped
+            // until after we drop the guard.
+            let n = unsafe { table.deref() }.len();
+
+            // bins that are being moved are followed into the next table, where their nodes
+            // have been split across two bins that both map back to bin `i` of this table.
+            (0..n)
+                .map(|i| {
+                    NodeIter::for_bins(table, i..i + 1, &guard)
+                        .map(|node| {
+                            let value = node.value.load(Ordering::SeqCst, &guard);
+                            // safety: flurry does not drop or move until after guard drop
+                            let value = unsafe { value.deref() };
+                            (node.hash, node.key.clone(), V::clone(value))
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        cloned_map.install_bins(bins);
         cloned_map
     }
 }
 
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Sync + Send + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher + Clone,
+{
+    /// Builds a map from a [`std::collections::HashMap`], using a clone of its hasher.
+    ///
+    /// Since the new map is not shared with other threads until this returns, its table is
+    /// built directly, without any of the locking or atomic operations [`HashMap::insert`]
+    /// has to perform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let std_map: std::collections::HashMap<_, _> = (0..100).map(|i| (i, i * 2)).collect();
+    /// let map = HashMap::from_std(std_map);
+    /// assert_eq!(map.pin().get(&21), Some(&42));
+    /// ```
+    pub fn from_std(map: std::collections::HashMap<K, V, S>) -> Self {
+        let mut flurry_map = Self::with_hasher(map.hasher().clone());
+        if map.is_empty() {
+            return flurry_map;
+        }
+
+        let n = table_size_for(map.len());
+        let mut bins: Vec<Vec<_>> = std::iter::repeat_with(Vec::new).take(n).collect();
+        for (key, value) in map {
+            let hash = flurry_map.hash(&key);
+            // the table has `n` bins, and `n` is a power of two
+            bins[(hash & (n as u64 - 1)) as usize].push((hash, key, value));
+        }
+        flurry_map.install_bins(bins);
+        flurry_map
+    }
+
+    /// Converts this map into a [`std::collections::HashMap`] that uses a clone of its hasher.
+    ///
+    /// Since this consumes the map, its entries are moved out directly, without any of the
+    /// locking or atomic operations [`HashMap::remove`] has to perform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// map.pin().insert(1, "a");
+    /// let std_map = map.into_std();
+    /// assert_eq!(std_map[&1], "a");
+    /// ```
+    pub fn into_std(mut self) -> std::collections::HashMap<K, V, S> {
+        let mut std_map = std::collections::HashMap::with_capacity_and_hasher(
+            self.len(),
+            self.build_hasher.clone(),
+        );
+        self.drain_owned(|key, value| {
+            std_map.insert(key, value);
+        });
+        std_map
+    }
+
+    /// Builds a map that uses `hash_builder` from the `len` entries yielded by `entries`.
+    ///
+    /// Every entry carries the hash `hash_builder` gives its key, so unlike
+    /// [`HashMap::from_std`], no key is hashed here. If two entries have the same key, the error
+    /// returned by `duplicate_key` is returned instead.
+    #[cfg(feature = "rkyv")]
+    pub(crate) fn try_from_hashed<I, E>(
+        hash_builder: S,
+        len: usize,
+        entries: I,
+        duplicate_key: impl FnOnce() -> E,
+    ) -> Result<Self, E>
+    where
+        I: IntoIterator<Item = Result<(u64, K, V), E>>,
+    {
+        let mut flurry_map = Self::with_hasher(hash_builder);
+        if len == 0 {
+            return Ok(flurry_map);
+        }
+
+        let n = table_size_for(len);
+        let mut bins: Vec<Vec<_>> = std::iter::repeat_with(Vec::new).take(n).collect();
+        for entry in entries {
+            let (hash, key, value) = entry?;
+            bins[(hash & (n as u64 - 1)) as usize].push((hash, key, value));
+        }
+        // sorting puts equal keys next to each other, even in a bin that is full of collisions
+        for bin in bins.iter_mut().filter(|bin| bin.len() > 1) {
+            bin.sort_unstable_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+            if bin.windows(2).any(|w| w[0].0 == w[1].0 && w[0].1 == w[1].1) {
+                return Err(duplicate_key());
+            }
+        }
+        flurry_map.install_bins(bins);
+        Ok(flurry_map)
+    }
+}
+
+impl<K, V, S> From<std::collections::HashMap<K, V, S>> for HashMap<K, V, S>
+where
+    K: Sync + Send + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher + Clone,
+{
+    fn from(map: std::collections::HashMap<K, V, S>) -> Self {
+        Self::from_std(map)
+    }
+}
+
+/// Returns the number of bins in a table that should accommodate `size` elements.
+fn table_size_for(size: usize) -> usize {
+    if size >= MAXIMUM_CAPACITY / 2 {
+        MAXIMUM_CAPACITY
+    } else {
+        // round the requested_capacity to the next power of to from 1.5 * size + 1
+        // TODO: find out if this is neccessary
+        let size = size + (size >> 1) + 1;
+
+        std::cmp::min(MAXIMUM_CAPACITY, size.next_power_of_two())
+    }
+}
+
 #[cfg(not(miri))]
 #[inline]
 /// Returns the number of physical CPUs in the machine (_O(1)_).
@@ -3135,6 +6434,178 @@ mod tests {
         let resize_stamp = HashMap::<usize, usize>::resize_stamp(MAXIMUM_CAPACITY);
         assert!(resize_stamp << RESIZE_STAMP_SHIFT < 0);
     }
+
+    #[test]
+    fn validate() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        assert_eq!(map.validate(&guard), Ok(()));
+
+        for i in 0..1000 {
+            map.insert(i, i, &guard);
+        }
+        for i in (0..1000).step_by(3) {
+            map.remove(&i, &guard);
+        }
+        assert_eq!(map.validate(&guard), Ok(()));
+
+        map.clear(&guard);
+        assert_eq!(map.validate(&guard), Ok(()));
+
+        let map = HashMap::<usize, usize>::with_capacity(100);
+        assert_eq!(map.validate(&map.guard()), Ok(()));
+    }
+
+    /// Returns the table of `map` and the index of the bin of `key`.
+    fn bin_of<'g>(
+        map: &'g HashMap<usize, usize>,
+        key: usize,
+        guard: &'g Guard<'_>,
+    ) -> (&'g Table<usize, usize>, usize) {
+        let t = map.table.load(Ordering::SeqCst, guard);
+        // safety: we loaded the table under our guard
+        let t = unsafe { t.deref() };
+        (t, t.bini(map.hash(&key)))
+    }
+
+    #[test]
+    fn validate_misplaced_entry() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        map.insert(42, 0, &guard);
+
+        // move the only entry into the next bin
+        let (t, i) = bin_of(&map, 42, &guard);
+        let j = (i + 1) % t.len();
+        let bin = t.bin(i, &guard);
+        t.store_bin(j, bin);
+        t.store_bin(i, Shared::null());
+        assert_eq!(
+            map.validate(&guard),
+            Err(InvariantViolation::MisplacedEntry {
+                bin: j,
+                hash: map.hash(&42)
+            })
+        );
+
+        t.store_bin(i, bin);
+        t.store_bin(j, Shared::null());
+        assert_eq!(map.validate(&guard), Ok(()));
+    }
+
+    #[test]
+    fn validate_duplicate_key() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        map.insert(42, 0, &guard);
+
+        // link a second node for the same key in front of the first one
+        let (t, i) = bin_of(&map, 42, &guard);
+        let head = t.bin(i, &guard);
+        let value = Shared::boxed(1, &map.collector);
+        let duplicate = Shared::boxed(
+            BinEntry::Node(Node::with_next(
+                map.hash(&42),
+                42,
+                value,
+                Atomic::from(head),
+            )),
+            &map.collector,
+        );
+        t.store_bin(i, duplicate);
+        assert_eq!(
+            map.validate(&guard),
+            Err(InvariantViolation::DuplicateKey { bin: i })
+        );
+    }
+
+    #[test]
+    fn validate_count_mismatch() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        for i in 0..10 {
+            map.insert(i, i, &guard);
+        }
+
+        map.count.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(
+            map.validate(&guard),
+            Err(InvariantViolation::CountMismatch {
+                count: 11,
+                entries: 10
+            })
+        );
+
+        map.count.fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(map.validate(&guard), Ok(()));
+    }
+
+    #[test]
+    fn validate_size_ctl() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        map.insert(42, 0, &guard);
+
+        let size_ctl = map.size_ctl.load(Ordering::SeqCst);
+        map.size_ctl.store(size_ctl + 1, Ordering::SeqCst);
+        assert_eq!(
+            map.validate(&guard),
+            Err(InvariantViolation::SizeCtl {
+                size_ctl: size_ctl + 1,
+                bins: DEFAULT_CAPACITY
+            })
+        );
+
+        map.size_ctl.store(size_ctl, Ordering::SeqCst);
+        assert_eq!(map.validate(&guard), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "debug-invariants")]
+    #[should_panic(expected = "size_ctl")]
+    fn debug_invariants() {
+        let map = HashMap::<usize, usize>::new();
+        let guard = map.guard();
+        map.insert(42, 0, &guard);
+
+        let size_ctl = map.size_ctl.load(Ordering::SeqCst);
+        map.size_ctl.store(size_ctl + 1, Ordering::SeqCst);
+        map.insert(43, 0, &guard);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn validate_concurrently() {
+        let map = Arc::new(HashMap::<usize, usize>::new());
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                std::thread::spawn(move || {
+                    let guard = map.guard();
+                    for i in 0..2000 {
+                        map.insert(t * 2000 + i, i, &guard);
+                        if i % 2 == 0 {
+                            map.remove(&(t * 2000 + i / 2), &guard);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // updates that have not been counted yet may be reported as a mismatch, but the bins
+        // themselves are always valid
+        let guard = map.guard();
+        while !writers.iter().all(|writer| writer.is_finished()) {
+            match map.validate(&guard) {
+                Ok(()) | Err(InvariantViolation::CountMismatch { .. }) => {}
+                Err(violation) => panic!("{}", violation),
+            }
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(map.validate(&guard), Ok(()));
+    }
 }
 
 /// It's kind of stupid, but apparently there is no way to write a regular `#[test]` that is _not_
@@ -3353,6 +6824,7 @@ fn replace_twice() {
 #[cfg(test)]
 mod tree_bins {
     use super::*;
+    use crate::Equivalent;
     use std::hash::Hasher;
 
     // Tests for the tree bin optimization.
@@ -3362,6 +6834,7 @@ mod tree_bins {
     #[derive(Default)]
     struct ZeroHasher;
 
+    #[derive(Clone)]
     struct ZeroHashBuilder;
 
     impl Hasher for ZeroHasher {
@@ -3378,6 +6851,364 @@ mod tree_bins {
         }
     }
 
+    #[test]
+    fn insert_many_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        // a single batch with enough colliding keys causes the bin to be treeified
+        let replaced = map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert!(replaced.iter().all(Option::is_none));
+        let t = map.table.load(Ordering::Relaxed, guard);
+        let t = unsafe { t.deref() };
+        let bin = t.bin(t.bini(0), guard);
+        assert!(matches!(unsafe { &**bin.deref() }, BinEntry::Tree(_)));
+
+        // and a subsequent batch goes through the tree bin
+        let replaced = map.insert_many((10..30).map(|i| (i, i + 1)), guard);
+        assert!(replaced[..10]
+            .iter()
+            .zip(10..20)
+            .all(|(old, i)| *old == Some(&i)));
+        assert!(replaced[10..].iter().all(Option::is_none));
+        assert_eq!(map.len(), 30);
+        for i in 0..30 {
+            let expected = if i < 10 { i } else { i + 1 };
+            assert_eq!(map.get(&i, guard), Some(&expected));
+        }
+    }
+
+    fn assert_tree_bin<K, V, S>(map: &HashMap<K, V, S>, guard: &Guard<'_>) {
+        let t = map.table.load(Ordering::Relaxed, guard);
+        let t = unsafe { t.deref() };
+        let bin = t.bin(t.bini(0), guard);
+        assert!(matches!(unsafe { &**bin.deref() }, BinEntry::Tree(_)));
+    }
+
+    #[test]
+    fn validate_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert_tree_bin(&map, guard);
+        assert_eq!(map.validate(guard), Ok(()));
+
+        let t = map.table.load(Ordering::Relaxed, guard);
+        let t = unsafe { t.deref() };
+        let bini = t.bini(0);
+        let bin = t.bin(bini, guard);
+        let BinEntry::Tree(ref tree_bin) = **(unsafe { bin.deref() }) else {
+            unreachable!("we just checked that the bin is a tree bin");
+        };
+
+        // a red root
+        let root = unsafe { TreeNode::get_tree_node(tree_bin.root.load(Ordering::SeqCst, guard)) };
+        root.red.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            map.validate(guard),
+            Err(InvariantViolation::MalformedTree { bin, .. }) if bin == bini
+        ));
+        root.red.store(false, Ordering::SeqCst);
+
+        // a broken `prev` link
+        let first = tree_bin.first.load(Ordering::SeqCst, guard);
+        let second = unsafe { TreeNode::get_tree_node(first) }
+            .node
+            .next
+            .load(Ordering::SeqCst, guard);
+        let second = unsafe { TreeNode::get_tree_node(second) };
+        second.prev.store(Shared::null(), Ordering::SeqCst);
+        assert!(matches!(
+            map.validate(guard),
+            Err(InvariantViolation::MalformedTreeList { bin, .. }) if bin == bini
+        ));
+        second.prev.store(first, Ordering::SeqCst);
+
+        // an unbalanced tree
+        let left = root.left.load(Ordering::SeqCst, guard);
+        let left = unsafe { TreeNode::get_tree_node(left) };
+        let red = left.red.load(Ordering::SeqCst);
+        left.red.store(!red, Ordering::SeqCst);
+        assert!(matches!(
+            map.validate(guard),
+            Err(InvariantViolation::MalformedTree { bin, .. }) if bin == bini
+        ));
+        left.red.store(red, Ordering::SeqCst);
+
+        assert_eq!(map.validate(guard), Ok(()));
+    }
+
+    #[test]
+    fn clone_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        {
+            let guard = &map.guard();
+            map.insert_many((0..20).map(|i| (i, i)), guard);
+            assert_tree_bin(&map, guard);
+        }
+        let cloned_map = map.clone();
+        let guard = &cloned_map.guard();
+        assert_tree_bin(&cloned_map, guard);
+        assert_eq!(cloned_map.len(), 20);
+        for i in 0..20 {
+            assert_eq!(cloned_map.get(&i, guard), Some(&i));
+        }
+        // the cloned tree bin is fully functional
+        assert_eq!(cloned_map.remove(&5, guard), Some(&5));
+        cloned_map.insert(20, 20, guard);
+        assert_eq!(cloned_map.len(), 20);
+        assert_eq!(map.pin().get(&5), Some(&5));
+    }
+
+    #[test]
+    fn std_round_trip_tree_bin() {
+        let mut std_map = std::collections::HashMap::with_hasher(ZeroHashBuilder);
+        std_map.extend((0..100).map(|i| (i, i * 2)));
+        let map = HashMap::from_std(std_map);
+        {
+            let guard = &map.guard();
+            assert_tree_bin(&map, guard);
+            assert_eq!(map.len(), 100);
+            for i in 0..100 {
+                assert_eq!(map.get(&i, guard), Some(&(i * 2)));
+            }
+        }
+        let std_map = map.into_std();
+        assert_eq!(std_map.len(), 100);
+        assert!((0..100).all(|i| std_map[&i] == i * 2));
+    }
+
+    #[test]
+    fn with_hash_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert_tree_bin(&map, guard);
+
+        let hash = map.hash_key(&0);
+        for i in 0..20 {
+            assert_eq!(map.get_with_hash(hash, &i, guard), Some(&i));
+            assert_eq!(map.find_by_hash(hash, |&k| k == i, guard), Some((&i, &i)));
+        }
+        assert_eq!(map.find_by_hash(hash, |&k| k == 20, guard), None);
+        assert_eq!(map.insert_with_hash(hash, 20, 20, guard), None);
+        assert_eq!(map.remove_with_hash(hash, &3, guard), Some(&3));
+        assert_eq!(map.find_by_hash(hash, |&k| k == 3, guard), None);
+        assert_eq!(map.len(), 20);
+    }
+
+    #[test]
+    fn comparable_tree_bin() {
+        struct Pair<'a>(&'a str, usize);
+
+        impl Equivalent<(String, usize)> for Pair<'_> {
+            fn equivalent(&self, key: &(String, usize)) -> bool {
+                self.0 == key.0 && self.1 == key.1
+            }
+        }
+
+        impl Comparable<(String, usize)> for Pair<'_> {
+            fn compare(&self, key: &(String, usize)) -> std::cmp::Ordering {
+                (self.0, self.1).cmp(&(key.0.as_str(), key.1))
+            }
+        }
+
+        impl Hash for Pair<'_> {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (self.0, self.1).hash(state);
+            }
+        }
+
+        let map = HashMap::<(String, usize), usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| ((i.to_string(), i), i)), guard);
+        assert_tree_bin(&map, guard);
+
+        for i in 0..20 {
+            let s = i.to_string();
+            assert_eq!(map.get(&Pair(&s, i), guard), Some(&i));
+            assert_eq!(map.get(&Pair(&s, i + 1), guard), None);
+        }
+        assert_eq!(map.remove(&Pair("3", 3), guard), Some(&3));
+        assert_eq!(map.get(&Pair("3", 3), guard), None);
+        assert_eq!(map.len(), 19);
+    }
+
+    #[test]
+    fn transaction_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert_tree_bin(&map, guard);
+
+        // updating the bin keeps it a tree
+        let keys: Vec<_> = (15..25).collect();
+        map.transaction(
+            &keys,
+            |entries| {
+                for entry in entries.iter_mut() {
+                    entry.insert(entry.key() + 1);
+                }
+                Ok::<_, ()>(())
+            },
+            guard,
+        )
+        .unwrap();
+        assert_tree_bin(&map, guard);
+        assert_eq!(map.len(), 25);
+        for i in 0..25 {
+            let expected = if i < 15 { i } else { i + 1 };
+            assert_eq!(map.get(&i, guard), Some(&expected));
+        }
+
+        // removing most entries untreeifies it
+        let keys: Vec<_> = (3..25).collect();
+        map.transaction(
+            &keys,
+            |entries| {
+                entries.iter_mut().for_each(TransactionEntry::remove);
+                Ok::<_, ()>(())
+            },
+            guard,
+        )
+        .unwrap();
+        assert_eq!(map.len(), 3);
+        let t = map.table.load(Ordering::Relaxed, guard);
+        let t = unsafe { t.deref() };
+        let bin = t.bin(t.bini(0), guard);
+        assert!(matches!(unsafe { &**bin.deref() }, BinEntry::Node(_)));
+        for i in 0..3 {
+            assert_eq!(map.get(&i, guard), Some(&i));
+        }
+    }
+
+    #[test]
+    fn transaction_linked_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..4).map(|i| (i, i)), guard);
+
+        // removing the head hands the lock over to the next node
+        map.transaction(
+            &[0, 2, 1, 10],
+            |entries| {
+                entries[0].remove();
+                entries[1].remove();
+                entries[2].insert(11);
+                entries[3].insert(10);
+                Ok::<_, ()>(())
+            },
+            guard,
+        )
+        .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&0, guard), None);
+        assert_eq!(map.get(&1, guard), Some(&11));
+        assert_eq!(map.get(&2, guard), None);
+        assert_eq!(map.get(&3, guard), Some(&3));
+        assert_eq!(map.get(&10, guard), Some(&10));
+
+        // emptying the bin and filling it again within one transaction
+        map.transaction(
+            &[1, 3, 10, 20],
+            |entries| {
+                entries[..3].iter_mut().for_each(TransactionEntry::remove);
+                entries[3].insert(20);
+                Ok::<_, ()>(())
+            },
+            guard,
+        )
+        .unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&20, guard), Some(&20));
+
+        // the bin is unlocked again
+        map.insert(21, 21, guard);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn lock_key_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert_tree_bin(&map, guard);
+
+        let mut lock = map.lock_key(&5, guard);
+        assert_eq!(lock.insert(50), Some(&5));
+        assert_eq!(lock.remove(), Some(&50));
+        assert_eq!(lock.get(), None);
+        drop(lock);
+        let mut lock = map.lock_key(&100, guard);
+        assert_eq!(lock.insert(100), None);
+        drop(lock);
+
+        assert_tree_bin(&map, guard);
+        assert_eq!(map.len(), 20);
+        assert_eq!(map.get(&5, guard), None);
+        assert_eq!(map.get(&100, guard), Some(&100));
+    }
+
+    #[test]
+    fn change_feed_tree_bin() {
+        use crate::feed::ChangeEvent;
+        use std::sync::Mutex;
+
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder).with_change_sink({
+            let events = std::sync::Arc::clone(&events);
+            move |event: ChangeEvent<&usize, &usize>| events.lock().unwrap().push(event.cloned())
+        });
+        let guard = &map.guard();
+        map.insert_many((0..20).map(|i| (i, i)), guard);
+        assert_tree_bin(&map, guard);
+        map.insert(3, 30, guard);
+        map.remove(&4, guard);
+        map.compute_if_present(&5, |_, v| Some(v * 10), guard);
+        map.lock_key(&6, guard).insert(60);
+        map.transaction(
+            &[7, 100],
+            |entries| {
+                entries[0].remove();
+                entries[1].insert(100);
+                Ok::<_, ()>(())
+            },
+            guard,
+        )
+        .unwrap();
+        assert_tree_bin(&map, guard);
+
+        let follower = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let follower_guard = &follower.guard();
+        let mut events = std::mem::take(&mut *events.lock().unwrap());
+        events.sort_by_key(|event| event.seq);
+        for (i, event) in events.into_iter().enumerate() {
+            assert_eq!(event.seq, i as u64);
+            follower.apply_event(event, follower_guard);
+        }
+        assert_eq!(map, follower);
+
+        map.clear(guard);
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn get_many_tree_bin() {
+        let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+        let guard = &map.guard();
+        for i in 0..10 {
+            map.insert(i, i, guard);
+        }
+        let t = map.table.load(Ordering::Relaxed, guard);
+        let t = unsafe { t.deref() };
+        let bin = t.bin(t.bini(0), guard);
+        assert!(matches!(unsafe { &**bin.deref() }, BinEntry::Tree(_)));
+
+        assert_eq!(
+            map.get_many(&[&3, &10, &9], guard),
+            vec![Some(&3), None, Some(&9)]
+        );
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn concurrent_tree_bin() {
@@ -3399,6 +7230,9 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly treeified -- is Moved"),
                 BinEntry::Node(_) => panic!("bin was not correctly treeified -- is Node"),
                 BinEntry::TreeNode(_) => panic!("bin was not correctly treeified -- is TreeNode"),
+                BinEntry::Reservation(_) => {
+                    panic!("bin was not correctly treeified -- is Reservation")
+                }
             }
 
             let _ = guard;
@@ -3496,6 +7330,9 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly treeified -- is Moved"),
                 BinEntry::Node(_) => panic!("bin was not correctly treeified -- is Node"),
                 BinEntry::TreeNode(_) => panic!("bin was not correctly treeified -- is TreeNode"),
+                BinEntry::Reservation(_) => {
+                    panic!("bin was not correctly treeified -- is Reservation")
+                }
             }
 
             // Delete keys to force untreeifying the bin
@@ -3518,6 +7355,9 @@ mod tree_bins {
                 BinEntry::Moved => panic!("bin was not correctly untreeified -- is Moved"),
                 BinEntry::Node(_) => {} // pass
                 BinEntry::TreeNode(_) => panic!("bin was not correctly untreeified -- is TreeNode"),
+                BinEntry::Reservation(_) => {
+                    panic!("bin was not correctly untreeified -- is Reservation")
+                }
             }
         }
 
@@ -3549,4 +7389,18 @@ mod tree_bins {
         // but we can still access it!
         assert_eq!(oops.unwrap(), "hello");
     }
+    #[test]
+    #[should_panic]
+    fn disallow_evil_try_insert() {
+        let map: HashMap<_, _> = HashMap::default();
+        map.insert(42, String::from("hello"), &map.guard());
+
+        let evil = seize::Collector::new();
+        let guard = evil.enter();
+        // the existing value is returned in the error, tied to the foreign guard
+        let oops = map.try_insert(42, String::from("world"), &guard);
+
+        map.remove(&42, &map.guard());
+        assert_eq!(oops.unwrap_err().current, "hello");
+    }
 }
@@ -1,7 +1,8 @@
 use crate::iter::*;
 use crate::reclaim::{Guard, GuardRef};
-use crate::{HashMap, TryInsertError};
-use std::borrow::Borrow;
+use crate::watch::Receiver;
+use crate::Comparable;
+use crate::{HashMap, KeyLock, TransactionEntry, TryInsertError};
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
 use std::ops::Index;
@@ -77,6 +78,14 @@ impl<K, V, S> HashMapRef<'_, K, V, S> {
     pub fn values(&self) -> Values<'_, K, V> {
         self.map.values(&self.guard)
     }
+
+    /// Incrementally iterates over the map's entries, starting at the bin that `cursor` points
+    /// to.
+    ///
+    /// See also [`HashMap::scan`].
+    pub fn scan(&self, cursor: usize, count: usize) -> (usize, Vec<(&'_ K, &'_ V)>) {
+        self.map.scan(cursor, count, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
@@ -104,8 +113,7 @@ where
     /// See also [`HashMap::contains_key`].
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.contains_key(key, &self.guard)
     }
@@ -116,8 +124,7 @@ where
     #[inline]
     pub fn get<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.get(key, &self.guard)
     }
@@ -128,11 +135,62 @@ where
     #[inline]
     pub fn get_key_value<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.get_key_value(key, &self.guard)
     }
+
+    /// Returns the hash the map uses for `key`.
+    ///
+    /// See also [`HashMap::hash_key`].
+    #[inline]
+    pub fn hash_key<Q>(&self, key: &Q) -> u64
+    where
+        Q: ?Sized + Hash,
+    {
+        self.map.hash_key(key)
+    }
+
+    /// Returns a reference to the value corresponding to the key, given the key's `hash`.
+    ///
+    /// See also [`HashMap::get_with_hash`].
+    #[inline]
+    pub fn get_with_hash<'g, Q>(&'g self, hash: u64, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.map.get_with_hash(hash, key, &self.guard)
+    }
+
+    /// Returns the first key-value pair with the given `hash` whose key satisfies `is_match`.
+    ///
+    /// See also [`HashMap::find_by_hash`].
+    pub fn find_by_hash<F>(&self, hash: u64, is_match: F) -> Option<(&'_ K, &'_ V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.map.find_by_hash(hash, is_match, &self.guard)
+    }
+
+    /// Returns references to the values corresponding to each of the given keys.
+    ///
+    /// See also [`HashMap::get_many`].
+    pub fn get_many<'g, Q>(&'g self, keys: &[&Q]) -> Vec<Option<&'g V>>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get_many(keys, &self.guard)
+    }
+
+    /// Returns references to the values corresponding to each of the given keys.
+    ///
+    /// See also [`HashMap::get_many_array`].
+    pub fn get_many_array<'g, Q, const N: usize>(&'g self, keys: [&Q; N]) -> [Option<&'g V>; N]
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get_many_array(keys, &self.guard)
+    }
 }
 
 impl<K, V, S> HashMapRef<'_, K, V, S>
@@ -160,6 +218,13 @@ where
         self.map.insert(key, value, &self.guard)
     }
 
+    /// Inserts a key-value pair into the map, given the key's `hash`.
+    ///
+    /// See also [`HashMap::insert_with_hash`].
+    pub fn insert_with_hash(&self, hash: u64, key: K, value: V) -> Option<&'_ V> {
+        self.map.insert_with_hash(hash, key, value, &self.guard)
+    }
+
     /// Inserts a key-value pair into the map unless the key already exists.
     ///
     /// See also [`HashMap::try_insert`].
@@ -168,14 +233,50 @@ where
         self.map.try_insert(key, value, &self.guard)
     }
 
+    /// Inserts all key-value pairs from `items` into the map.
+    ///
+    /// See also [`HashMap::insert_many`].
+    pub fn insert_many<I>(&self, items: I) -> Vec<Option<&'_ V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.map.insert_many(items, &self.guard)
+    }
+
+    /// Atomically reads and updates the entries for all of the given `keys`.
+    ///
+    /// See also [`HashMap::transaction`].
+    pub fn transaction<F, R, E>(&self, keys: &[K], f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut [TransactionEntry<'_, K, V>]) -> Result<R, E>,
+    {
+        self.map.transaction(keys, f, &self.guard)
+    }
+
+    /// Subscribes to changes to `key`.
+    ///
+    /// See also [`HashMap::subscribe`].
+    pub fn subscribe<Q>(&self, key: &Q) -> Receiver<'_>
+    where
+        Q: ?Sized + Hash,
+    {
+        self.map.subscribe(key)
+    }
+
+    /// Locks `key`, so that other updates to it wait until the returned [`KeyLock`] is dropped.
+    ///
+    /// See also [`HashMap::lock_key`].
+    pub fn lock_key(&self, key: &K) -> KeyLock<'_, K, V, S> {
+        self.map.lock_key(key, &self.guard)
+    }
+
     /// If the value for the specified `key` is present, attempts to
     /// compute a new mapping given the key and its current mapped value.
     ///
     /// See also [`HashMap::compute_if_present`].
     pub fn compute_if_present<'g, Q, F>(&'g self, key: &Q, remapping_function: F) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
         F: FnOnce(&K, &V) -> Option<V>,
     {
         self.map
@@ -187,20 +288,29 @@ where
     /// See also [`HashMap::remove`].
     pub fn remove<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.remove(key, &self.guard)
     }
 
+    /// Removes a key-value pair from the map given the key's `hash`, and returns the removed
+    /// value (if any).
+    ///
+    /// See also [`HashMap::remove_with_hash`].
+    pub fn remove_with_hash<'g, Q>(&'g self, hash: u64, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Comparable<K>,
+    {
+        self.map.remove_with_hash(hash, key, &self.guard)
+    }
+
     /// Removes a key from the map, returning the stored key and value if the
     /// key was previously in the map.
     ///
     /// See also [`HashMap::remove_entry`].
     pub fn remove_entry<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<K>,
     {
         self.map.remove_entry(key, &self.guard)
     }
@@ -294,8 +404,8 @@ where
 
 impl<K, Q, V, S> Index<&'_ Q> for HashMapRef<'_, K, V, S>
 where
-    K: Hash + Ord + Borrow<Q>,
-    Q: ?Sized + Hash + Ord,
+    K: Hash + Ord,
+    Q: ?Sized + Hash + Comparable<K>,
     S: BuildHasher,
 {
     type Output = V;
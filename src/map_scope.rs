@@ -0,0 +1,230 @@
+use crate::iter::*;
+use crate::reclaim::{self, Guard};
+use crate::Comparable;
+use crate::{HashMap, TryInsertError};
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::Ordering;
+
+/// A scoped handle to a [`HashMap`], constructed with [`HashMap::scope`].
+///
+/// A `HashMapScope` owns a `Guard` that was created from the map's own collector, and none of
+/// its methods accept a `Guard` from the outside. Using a guard from a foreign
+/// [`seize::Collector`] with the scope is therefore a type error rather than a runtime panic, and
+/// operations on the scope skip the collector comparison that the guard-taking methods on
+/// [`HashMap`] have to perform on every call.
+///
+/// The scope only lives for the duration of the closure passed to [`HashMap::scope`], so no
+/// reference obtained through it can outlive its guard:
+///
+/// ```compile_fail
+/// use flurry::HashMap;
+///
+/// let map = HashMap::new();
+/// map.pin().insert(1, String::from("a"));
+/// let escaped = map.scope(|s| s.get(&1));
+/// ```
+///
+/// The current thread is pinned for the duration of the scope. Keep in mind that this prevents
+/// the collection of garbage generated by the map; use [`HashMapScope::refresh`] in long-running
+/// scopes.
+pub struct HashMapScope<'map, K, V, S = crate::DefaultHashBuilder> {
+    map: &'map HashMap<K, V, S>,
+    guard: Guard<'map>,
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Runs `f` with a [`HashMapScope`] for this map, and returns its result.
+    ///
+    /// Within the scope, operations do not take a `Guard` argument, and so cannot be handed a
+    /// guard from the wrong collector. See [`HashMapScope`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let map = HashMap::new();
+    /// let sum = map.scope(|s| {
+    ///     for i in 0..10 {
+    ///         s.insert(i, i * 2);
+    ///     }
+    ///     (0..10).filter_map(|i| s.get(&i)).sum::<usize>()
+    /// });
+    /// assert_eq!(sum, 90);
+    /// ```
+    pub fn scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut HashMapScope<'_, K, V, S>) -> R,
+    {
+        let mut scope = HashMapScope {
+            guard: self.guard(),
+            map: self,
+        };
+        f(&mut scope)
+    }
+}
+
+impl<K, V, S> HashMapScope<'_, K, V, S> {
+    /// Returns the guard that this scope holds.
+    ///
+    /// The guard is guaranteed to belong to the map's collector, and may be passed to the
+    /// guard-taking methods of the map this scope was created for.
+    pub fn guard(&self) -> &Guard<'_> {
+        &self.guard
+    }
+
+    /// Refreshes the guard held by this scope.
+    ///
+    /// This allows garbage generated by the map since the scope was entered to be collected.
+    /// Since it takes `&mut self`, the borrow checker ensures that no references obtained
+    /// through the scope are still held.
+    pub fn refresh(&mut self) {
+        reclaim::refresh(&mut self.guard);
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// See also [`HashMap::len`].
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map is empty. Otherwise returns `false`.
+    ///
+    /// See also [`HashMap::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// See also [`HashMap::iter`].
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter_unchecked(&self.guard)
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    ///
+    /// See also [`HashMap::keys`].
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        self.map.keys_unchecked(&self.guard)
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    ///
+    /// See also [`HashMap::values`].
+    pub fn values(&self) -> Values<'_, K, V> {
+        self.map.values_unchecked(&self.guard)
+    }
+}
+
+impl<K, V, S> HashMapScope<'_, K, V, S>
+where
+    K: Hash + Ord,
+    S: BuildHasher,
+{
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// See also [`HashMap::contains_key`].
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.get_node(key, &self.guard).is_some()
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// See also [`HashMap::get`].
+    #[inline]
+    pub fn get<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to `key`.
+    ///
+    /// See also [`HashMap::get_key_value`].
+    #[inline]
+    pub fn get_key_value<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        let node = self.map.get_node(key, &self.guard)?;
+
+        let v = node.value.load(Ordering::SeqCst, &self.guard);
+        assert!(!v.is_null());
+        // safety: the lifetime of the reference is bound to the scope's guard, which is from the
+        // map's collector, so the memory will not be freed until after the guard is dropped
+        unsafe { v.as_ref() }.map(|v| (&node.key, &**v))
+    }
+}
+
+impl<K, V, S> HashMapScope<'_, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// See also [`HashMap::insert`].
+    pub fn insert(&self, key: K, value: V) -> Option<&'_ V> {
+        self.map.put(key, value, false, &self.guard).before()
+    }
+
+    /// Inserts a key-value pair into the map unless the key already exists.
+    ///
+    /// See also [`HashMap::try_insert`].
+    #[inline]
+    pub fn try_insert(&self, key: K, value: V) -> Result<&'_ V, TryInsertError<'_, V>> {
+        self.map.try_insert_unchecked(key, value, &self.guard)
+    }
+
+    /// If the value for the specified `key` is present, attempts to
+    /// compute a new mapping given the key and its current mapped value.
+    ///
+    /// See also [`HashMap::compute_if_present`].
+    pub fn compute_if_present<'g, Q, F>(&'g self, key: &Q, remapping_function: F) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+        F: FnOnce(&K, &V) -> Option<V>,
+    {
+        self.map
+            .compute_if_present_unchecked(key, remapping_function, &self.guard)
+    }
+
+    /// Removes a key-value pair from the map, and returns the removed value (if any).
+    ///
+    /// See also [`HashMap::remove`].
+    pub fn remove<'g, Q>(&'g self, key: &Q) -> Option<&'g V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// See also [`HashMap::remove_entry`].
+    pub fn remove_entry<'g, Q>(&'g self, key: &Q) -> Option<(&'g K, &'g V)>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        self.map.replace_node(key, None, None, &self.guard)
+    }
+}
+
+impl<K, V, S> Debug for HashMapScope<'_, K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -1,10 +1,11 @@
+use crate::map::InvariantViolation;
 use crate::raw::Table;
 use crate::reclaim::{Atomic, Collector, Guard, RetireShared, Shared};
-use core::sync::atomic::{AtomicBool, AtomicI64, Ordering};
-use parking_lot::Mutex;
+use crate::sync::{self, current, park, AtomicBool, AtomicI64, AtomicU8, Mutex, Thread};
+use crate::Comparable;
+use core::sync::atomic::Ordering;
 use seize::{Link, Linked};
-use std::borrow::Borrow;
-use std::thread::{current, park, Thread};
+use std::ops::Deref;
 
 /// Entry in a bin.
 ///
@@ -15,6 +16,12 @@ pub(crate) enum BinEntry<K, V> {
     Tree(TreeBin<K, V>),
     TreeNode(TreeNode<K, V>),
     Moved,
+    /// A placeholder that locks an empty bin, like `ReservationNode` in the Java code.
+    ///
+    /// The lock is held for as long as the reservation is in the table, and the reservation is
+    /// always replaced before the lock is released. Threads that need to modify the bin can thus
+    /// wait for the lock and then start over.
+    Reservation(BinLock),
 }
 
 unsafe impl<K, V> Send for BinEntry<K, V>
@@ -36,6 +43,18 @@ where
 }
 
 impl<K, V> BinEntry<K, V> {
+    /// Returns the lock of the bin that this entry is the head of.
+    pub(crate) fn bin_lock(&self) -> &BinLock {
+        match *self {
+            BinEntry::Node(ref node) => &node.lock,
+            BinEntry::Tree(ref tree_bin) => &tree_bin.lock,
+            BinEntry::Reservation(ref lock) => lock,
+            BinEntry::TreeNode(_) | BinEntry::Moved => {
+                unreachable!("only the head of a bin has a lock")
+            }
+        }
+    }
+
     pub(crate) fn as_node(&self) -> Option<&Node<K, V>> {
         if let BinEntry::Node(ref n) = *self {
             Some(n)
@@ -67,7 +86,7 @@ pub(crate) struct Node<K, V> {
     pub(crate) key: K,
     pub(crate) value: Atomic<V>,
     pub(crate) next: Atomic<BinEntry<K, V>>,
-    pub(crate) lock: Mutex<()>,
+    pub(crate) lock: BinLock,
 }
 
 impl<K, V> Node<K, V> {
@@ -86,8 +105,70 @@ impl<K, V> Node<K, V> {
             key,
             value: value.into(),
             next,
-            lock: Mutex::new(()),
+            lock: BinLock::new(),
+        }
+    }
+}
+
+/// The lock of a bin, held by whoever modifies the bin.
+///
+/// A [`KeyLock`](crate::KeyLock) holds the lock of its bin for as long as it exists, and marks it
+/// as key-locked meanwhile, so that `transfer` can move the bin along with the `KeyLock` instead
+/// of waiting for the lock. In turn, `transfer` marks the lock while it waits for it. Both marks
+/// are set by read-modify-write operations on the same atomic, so of a `KeyLock` and a transfer
+/// that race for the bin, at least one sees the mark of the other: either the transfer moves the
+/// `KeyLock`, or the `KeyLock` backs off until the transfer is done with the bin.
+#[derive(Debug)]
+pub(crate) struct BinLock {
+    mutex: Mutex<()>,
+    state: AtomicU8,
+}
+
+impl BinLock {
+    const KEY_LOCKED: u8 = 1;
+    const TRANSFERRING: u8 = 2;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            mutex: Mutex::new(()),
+            state: AtomicU8::new(0),
+        }
+    }
+
+    /// Marks the lock as held by a `KeyLock`, unless a transfer is waiting for it. Returns
+    /// whether the lock was marked.
+    ///
+    /// The caller must hold the lock.
+    pub(crate) fn mark_key_locked(&self) -> bool {
+        let state = self.state.fetch_or(Self::KEY_LOCKED, Ordering::SeqCst);
+        if state & Self::TRANSFERRING != 0 {
+            self.unmark_key_locked();
+            return false;
         }
+        true
+    }
+
+    /// Marks the lock as no longer held by a `KeyLock`.
+    pub(crate) fn unmark_key_locked(&self) {
+        self.state.fetch_and(!Self::KEY_LOCKED, Ordering::SeqCst);
+    }
+
+    /// Marks a transfer as waiting for the lock, and returns whether it is held by a `KeyLock`.
+    pub(crate) fn start_transfer(&self) -> bool {
+        self.state.fetch_or(Self::TRANSFERRING, Ordering::SeqCst) & Self::KEY_LOCKED != 0
+    }
+
+    /// Marks the transfer as no longer waiting for the lock.
+    pub(crate) fn end_transfer(&self) {
+        self.state.fetch_and(!Self::TRANSFERRING, Ordering::SeqCst);
+    }
+}
+
+impl Deref for BinLock {
+    type Target = Mutex<()>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mutex
     }
 }
 
@@ -138,8 +219,7 @@ impl<K, V> TreeNode<K, V> {
         guard: &'g Guard<'_>,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         // NOTE: in the Java code, this method is implemented on the `TreeNode`
         // instance directly, as they don't need to worry about shared pointers.
@@ -175,7 +255,7 @@ impl<K, V> TreeNode<K, V> {
             // if the hash matches, check if the given key also matches. If so,
             // we have found the target node.
             let p_key = &p_deref.node.key;
-            if p_key.borrow() == key {
+            if key.equivalent(p_key) {
                 return p;
             }
 
@@ -192,9 +272,9 @@ impl<K, V> TreeNode<K, V> {
             }
 
             // Otherwise, we compare keys to find the next child to look at.
-            p = match p_key.borrow().cmp(key) {
-                std::cmp::Ordering::Greater => p_left,
-                std::cmp::Ordering::Less => p_right,
+            p = match key.compare(p_key) {
+                std::cmp::Ordering::Less => p_left,
+                std::cmp::Ordering::Greater => p_right,
                 std::cmp::Ordering::Equal => {
                     unreachable!("Ord and Eq have to match and Eq is checked above")
                 }
@@ -230,7 +310,7 @@ pub(crate) struct TreeBin<K, V> {
     pub(crate) root: Atomic<BinEntry<K, V>>,
     pub(crate) first: Atomic<BinEntry<K, V>>,
     pub(crate) waiter: Atomic<Thread>,
-    pub(crate) lock: parking_lot::Mutex<()>,
+    pub(crate) lock: BinLock,
     pub(crate) lock_state: AtomicI64,
 }
 
@@ -324,7 +404,7 @@ where
             root: Atomic::from(root),
             first: Atomic::from(bin),
             waiter: Atomic::null(),
-            lock: parking_lot::Mutex::new(()),
+            lock: BinLock::new(),
             lock_state: AtomicI64::new(0),
         }
     }
@@ -403,7 +483,7 @@ impl<K, V> TreeBin<K, V> {
             } else if waiting {
                 park();
             }
-            std::hint::spin_loop();
+            sync::spin_loop();
         }
     }
 
@@ -417,8 +497,7 @@ impl<K, V> TreeBin<K, V> {
         guard: &'g Guard<'_>,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         // safety: bin is a valid pointer.
         //
@@ -453,7 +532,7 @@ impl<K, V> TreeBin<K, V> {
                 // Structurally, TreeNodes always point to TreeNodes, so this is sound.
                 let element_deref = unsafe { TreeNode::get_tree_node(element) };
                 let element_key = &element_deref.node.key;
-                if element_deref.node.hash == hash && element_key.borrow() == key {
+                if element_deref.node.hash == hash && key.equivalent(element_key) {
                     return element;
                 }
                 element = element_deref.node.next.load(Ordering::SeqCst, guard);
@@ -774,6 +853,75 @@ impl<K, V> TreeBin<K, V> {
     }
 }
 
+impl<K, V> TreeBin<K, V>
+where
+    K: Ord,
+{
+    /// Checks the red-black properties of the tree, and that the `prev` and `next` links of its
+    /// nodes are consistent and link the same nodes as the tree. Returns the number of nodes.
+    ///
+    /// `bin` is the index of this bin, which is used in the returned violation. The bin lock must
+    /// be held, so that no writer modifies the tree or the list while they are checked.
+    pub(crate) fn validate(
+        &self,
+        bin: usize,
+        guard: &Guard<'_>,
+    ) -> Result<usize, InvariantViolation> {
+        if self.lock_state.load(Ordering::SeqCst) & (WRITER | WAITER) != 0 {
+            return Err(InvariantViolation::MalformedTree {
+                bin,
+                reason: "a writer holds the root lock without holding the bin lock",
+            });
+        }
+
+        let root = self.root.load(Ordering::SeqCst, guard);
+        // safety: we read the root under our guard and while holding the bin lock, so it (and
+        // the rest of the tree) is not dropped until after we drop our guard.
+        if !root.is_null()
+            && unsafe { root.deref() }
+                .as_tree_node()
+                .is_some_and(|root| root.red.load(Ordering::Relaxed))
+        {
+            return Err(InvariantViolation::MalformedTree {
+                bin,
+                reason: "the root is red",
+            });
+        }
+        let mut nodes = 0;
+        TreeNode::validate_subtree(bin, root, Shared::null(), &mut None, &mut nodes, guard)?;
+
+        let malformed = |reason| InvariantViolation::MalformedTreeList { bin, reason };
+        let mut prev = Shared::null();
+        let mut p = self.first.load(Ordering::SeqCst, guard);
+        let mut listed = 0;
+        while !p.is_null() {
+            // safety: same as for the root above
+            let Some(p_deref) = unsafe { p.deref() }.as_tree_node() else {
+                return Err(malformed("a node in the list is not a tree node"));
+            };
+            if p_deref.prev.load(Ordering::SeqCst, guard) != prev {
+                return Err(malformed(
+                    "a node's `prev` does not link the node before it",
+                ));
+            }
+            listed += 1;
+            if listed > nodes {
+                // this also stops us from going around a cycle forever
+                return Err(malformed("the list links more nodes than the tree"));
+            }
+            if TreeNode::find_tree_node(root, p_deref.node.hash, &p_deref.node.key, guard) != p {
+                return Err(malformed("a node in the list is not in the tree"));
+            }
+            prev = p;
+            p = p_deref.node.next.load(Ordering::SeqCst, guard);
+        }
+        if listed != nodes {
+            return Err(malformed("the list links fewer nodes than the tree"));
+        }
+        Ok(nodes)
+    }
+}
+
 impl<K, V> TreeBin<K, V>
 where
     K: Ord + Send + Sync,
@@ -1458,6 +1606,73 @@ impl<K, V> TreeNode<K, V> {
     }
 }
 
+impl<K, V> TreeNode<K, V>
+where
+    K: Ord,
+{
+    /// Checks the subtree rooted at `t`, whose parent is `parent`, and returns its black height.
+    ///
+    /// `last` is the node before the subtree in tree order, if any, and is updated to the last
+    /// node of the subtree. `nodes` is incremented by the number of nodes in the subtree.
+    fn validate_subtree<'g>(
+        bin: usize,
+        t: Shared<'g, BinEntry<K, V>>,
+        parent: Shared<'g, BinEntry<K, V>>,
+        last: &mut Option<&'g Node<K, V>>,
+        nodes: &mut usize,
+        guard: &'g Guard<'_>,
+    ) -> Result<usize, InvariantViolation> {
+        if t.is_null() {
+            // the leaves are black
+            return Ok(1);
+        }
+
+        let malformed = |reason| InvariantViolation::MalformedTree { bin, reason };
+        // safety: the caller holds the bin lock, so the tree is not modified, and we read `t`
+        // under our guard, so it is not dropped until after we drop our guard.
+        let Some(t_deref) = unsafe { t.deref() }.as_tree_node() else {
+            return Err(malformed("a node in the tree is not a tree node"));
+        };
+        if t_deref.parent.load(Ordering::Relaxed, guard) != parent {
+            return Err(malformed(
+                "a node's `parent` is not the node it is a child of",
+            ));
+        }
+        let red = t_deref.red.load(Ordering::Relaxed);
+        // safety: same as for `t`. the parent is a tree node, since we just checked it.
+        if red && !parent.is_null() && treenode!(parent).red.load(Ordering::Relaxed) {
+            return Err(malformed("a red node has a red child"));
+        }
+
+        let left = t_deref.left.load(Ordering::Relaxed, guard);
+        let left_height = Self::validate_subtree(bin, left, t, last, nodes, guard)?;
+        if let Some(last) = last {
+            match last
+                .hash
+                .cmp(&t_deref.node.hash)
+                .then_with(|| last.key.cmp(&t_deref.node.key))
+            {
+                std::cmp::Ordering::Less => {}
+                std::cmp::Ordering::Equal => return Err(InvariantViolation::DuplicateKey { bin }),
+                std::cmp::Ordering::Greater => {
+                    return Err(malformed("the nodes are not ordered by hash and key"))
+                }
+            }
+        }
+        *last = Some(&t_deref.node);
+        *nodes += 1;
+        let right = t_deref.right.load(Ordering::Relaxed, guard);
+        let right_height = Self::validate_subtree(bin, right, t, last, nodes, guard)?;
+
+        if left_height != right_height {
+            return Err(malformed(
+                "the paths from a node to its leaves hold different numbers of black nodes",
+            ));
+        }
+        Ok(left_height + usize::from(!red))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1469,7 +1684,7 @@ mod tests {
             key,
             value: Atomic::from(Shared::boxed(value, collector)),
             next: Atomic::null(),
-            lock: Mutex::new(()),
+            lock: BinLock::new(),
         }
     }
 
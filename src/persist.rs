@@ -0,0 +1,640 @@
+//! A map that is persisted to local files.
+//!
+//! See `DurableHashMap` for details.
+
+use crate::feed::{ChangeEvent, ChangeSink};
+use crate::HashMap;
+use parking_lot::{Condvar, Mutex};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The first bytes of every log segment.
+const LOG_MAGIC: &[u8; 8] = b"flurrywl";
+/// The first bytes of every checkpoint.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"flurrycp";
+
+/// Frames in a checkpoint start with one of these tags.
+const ENTRY_TAG: u8 = 0;
+const END_TAG: u8 = 1;
+
+/// The number of entries [`DurableHashMap::checkpoint`] reads from the map under each guard.
+const CHECKPOINT_CHUNK: usize = 1024;
+
+/// The default for [`DurableHashMap::with_checkpoint_threshold`].
+const DEFAULT_CHECKPOINT_THRESHOLD: u64 = 64 << 20;
+
+/// A [`HashMap`] that persists its contents to a directory, so that they survive restarts.
+///
+/// Every change to the map is appended to a write-ahead log in the directory. When the log has
+/// grown large enough, [`sync`](DurableHashMap::sync) also writes a checkpoint of the whole map
+/// and starts a new log, so that the old one can be deleted. [`DurableHashMap::open`] recovers
+/// the map by loading the latest checkpoint and replaying the log written since.
+///
+/// The map is used through `Deref`, and every way of changing it is logged. Changes are
+/// buffered, and are only durable once `sync` has returned. If the process crashes before that,
+/// the recovered map reflects a prefix of the changes that were made: a change is never
+/// recovered without the changes that were made before it.
+///
+/// Keys and values are written with [`bincode`](https://docs.rs/bincode), and so must implement
+/// `Serialize` and `DeserializeOwned`. Only one `DurableHashMap` may use a directory at a time.
+///
+/// # Examples
+///
+/// ```no_run
+/// use flurry::DurableHashMap;
+///
+/// let map = DurableHashMap::<u64, String>::open("/var/lib/app/users")?;
+/// map.pin().insert(1, String::from("alice"));
+/// map.sync()?;
+/// drop(map);
+///
+/// let map = DurableHashMap::<u64, String>::open("/var/lib/app/users")?;
+/// assert_eq!(map.pin().get(&1).map(String::as_str), Some("alice"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct DurableHashMap<K, V, S = crate::DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+    dir: PathBuf,
+    log: Arc<Log>,
+    // held while writing a checkpoint, so that only one is written at a time
+    checkpointing: Mutex<()>,
+    checkpoint_threshold: Option<u64>,
+}
+
+impl<K, V> DurableHashMap<K, V, crate::DefaultHashBuilder>
+where
+    K: Sync + Send + Clone + Hash + Ord + Serialize + DeserializeOwned,
+    V: Sync + Send + Serialize + DeserializeOwned,
+{
+    /// Opens the map stored in `dir`, creating the directory if it does not exist.
+    ///
+    /// This loads the latest checkpoint in the directory and replays the log written since. A
+    /// record at the end of the log that was cut short by a crash is discarded.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        Self::open_with_hasher(dir, crate::DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> DurableHashMap<K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord + Serialize + DeserializeOwned,
+    V: Sync + Send + Serialize + DeserializeOwned,
+    S: BuildHasher,
+{
+    /// Opens the map stored in `dir`, which will use `hash_builder` to hash keys.
+    ///
+    /// See [`DurableHashMap::open`].
+    pub fn open_with_hasher<P: AsRef<Path>>(dir: P, hash_builder: S) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut checkpoints = Vec::new();
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            match parse_file_name(&path) {
+                Some((generation, FileKind::Checkpoint)) => checkpoints.push(generation),
+                Some((generation, FileKind::Log)) => segments.push(generation),
+                // left behind by a crash while a file was being created
+                Some((_, FileKind::Temporary)) => fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+        checkpoints.sort_unstable();
+        segments.sort_unstable();
+
+        let map = HashMap::with_hasher(hash_builder);
+        let latest = checkpoints.last().copied();
+        if let Some(generation) = latest {
+            load_checkpoint(&map, &checkpoint_path(&dir, generation))?;
+        }
+        // the log segment with the same generation as a checkpoint was started when the
+        // checkpoint was, so it holds every change that the checkpoint might have missed
+        let replayed: Vec<u64> = segments
+            .iter()
+            .copied()
+            .filter(|&generation| generation >= latest.unwrap_or(0))
+            .collect();
+        let mut log_len = 0;
+        for (i, &generation) in replayed.iter().enumerate() {
+            let last = i + 1 == replayed.len();
+            log_len += replay_segment(&map, &log_path(&dir, generation), last)?;
+        }
+
+        // whatever is older than the latest checkpoint is left over from a crash in `checkpoint`
+        if let Some(latest) = latest {
+            remove_older_than(&dir, latest)?;
+        }
+
+        let generation = checkpoints
+            .iter()
+            .chain(&segments)
+            .max()
+            .map_or(0, |&generation| generation + 1);
+        let file = create_segment(&dir, generation)?;
+        let log = Arc::new(Log {
+            state: Mutex::new(LogState {
+                file,
+                generation,
+                next_seq: 0,
+                pending: BTreeMap::new(),
+                len: log_len,
+                error: None,
+            }),
+            written: Condvar::new(),
+        });
+
+        Ok(Self {
+            map: map.with_change_sink(LogSink(Arc::clone(&log))),
+            dir,
+            log,
+            checkpointing: Mutex::new(()),
+            checkpoint_threshold: Some(DEFAULT_CHECKPOINT_THRESHOLD),
+        })
+    }
+
+    /// Sets the size in bytes that the log must reach for [`DurableHashMap::sync`] to write a
+    /// checkpoint, or `None` to only write checkpoints through [`DurableHashMap::checkpoint`].
+    ///
+    /// The default is 64 MiB.
+    #[must_use]
+    pub fn with_checkpoint_threshold(mut self, threshold: Option<u64>) -> Self {
+        self.checkpoint_threshold = threshold;
+        self
+    }
+
+    /// Makes every change that has been made to the map so far durable.
+    ///
+    /// This also writes a checkpoint if the log has grown past the checkpoint threshold.
+    ///
+    /// If writing to the log has failed since the last checkpoint, or a change could not be
+    /// serialized, the log no longer covers every change, and the error is returned by every call
+    /// until a checkpoint succeeds.
+    pub fn sync(&self) -> io::Result<()> {
+        let len = self.log.sync()?;
+        match self.checkpoint_threshold {
+            Some(threshold) if len >= threshold => self.checkpoint(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes a checkpoint of the map, and deletes the log written before it.
+    ///
+    /// Changes may continue while the checkpoint is written; they are logged to a new log that is
+    /// replayed on top of the checkpoint.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        let _checkpointing = self.checkpointing.lock();
+        // changes recorded before the new log starts have been made to the map, and so are in the
+        // checkpoint. the rest are in the new log.
+        let (generation, covered) = self.log.rotate(&self.dir)?;
+
+        let path = checkpoint_path(&self.dir, generation);
+        let tmp = path.with_extension("checkpoint.tmp");
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        file.write_all(CHECKPOINT_MAGIC)?;
+        let mut frame = Vec::new();
+        let mut count = 0u64;
+        // walk the map a chunk at a time, so that garbage can be reclaimed while the checkpoint is
+        // written. an entry may be written twice if the table is resized in the meantime, but
+        // every entry that was in the map when the log was rotated is written at least once.
+        let mut cursor = 0;
+        loop {
+            let guard = self.map.guard();
+            let (next, entries) = self.map.scan(cursor, CHECKPOINT_CHUNK, &guard);
+            for (key, value) in entries {
+                frame.clear();
+                frame.push(ENTRY_TAG);
+                bincode::serialize_into(&mut frame, &(key, value)).map_err(invalid_data)?;
+                write_frame(&mut file, &frame)?;
+                count += 1;
+            }
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        frame.clear();
+        frame.push(END_TAG);
+        frame.extend_from_slice(&count.to_le_bytes());
+        write_frame(&mut file, &frame)?;
+        file.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(&tmp, &path)?;
+        sync_dir(&self.dir)?;
+
+        self.log.checkpointed(generation, covered);
+        remove_older_than(&self.dir, generation)
+    }
+}
+
+impl<K, V, S> Deref for DurableHashMap<K, V, S> {
+    type Target = HashMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<K, V, S> Debug for DurableHashMap<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DurableHashMap")
+            .field("dir", &self.dir)
+            .field("map", &self.map)
+            .finish()
+    }
+}
+
+/// The write-ahead log of a [`DurableHashMap`].
+///
+/// Changes are sequenced by the map's change feed, but may be recorded out of order. The log
+/// holds back changes until those sequenced before them have been written, so that it only
+/// ever contains a prefix of the map's history.
+struct Log {
+    state: Mutex<LogState>,
+    // notified whenever changes have been written
+    written: Condvar,
+}
+
+struct LogState {
+    file: BufWriter<File>,
+    generation: u64,
+    /// The sequence number of the next change to write.
+    next_seq: u64,
+    /// Framed changes that are waiting for the changes sequenced before them.
+    pending: BTreeMap<u64, io::Result<Vec<u8>>>,
+    /// The number of bytes logged since the last checkpoint.
+    len: u64,
+    /// The first error since the last checkpoint, and the generation of the log it happened in.
+    error: Option<(u64, io::Error)>,
+}
+
+impl Log {
+    fn append(&self, seq: u64, frame: io::Result<Vec<u8>>) {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+        if seq < state.next_seq {
+            // skipped by a checkpoint, see `checkpointed`
+            return;
+        }
+        if seq != state.next_seq {
+            state.pending.insert(seq, frame);
+            return;
+        }
+        state.write(frame);
+        while let Some(frame) = state.pending.remove(&state.next_seq) {
+            state.write(frame);
+        }
+        self.written.notify_all();
+    }
+
+    /// Writes the changes recorded so far to disk, and returns the size of the log.
+    fn sync(&self) -> io::Result<u64> {
+        let mut state = self.state.lock();
+        // wait for the changes that are holding back the ones recorded so far
+        let target = match state.pending.keys().next_back() {
+            Some(&seq) => seq + 1,
+            None => state.next_seq,
+        };
+        while state.next_seq < target {
+            self.written.wait(&mut state);
+        }
+        state.check()?;
+        if let Err(e) = state.flush() {
+            let generation = state.generation;
+            state.error = Some((generation, copy_error(&e)));
+            return Err(e);
+        }
+        Ok(state.len)
+    }
+
+    /// Finishes the current log segment, and starts a new one. Returns its generation, and the
+    /// sequence number of the first change that has not been recorded yet.
+    fn rotate(&self, dir: &Path) -> io::Result<(u64, u64)> {
+        let mut state = self.state.lock();
+        // if the old segment is incomplete, the checkpoint will stand in for it
+        if let Err(e) = state.flush() {
+            if state.error.is_none() {
+                state.error = Some((state.generation, e));
+            }
+        }
+        let generation = state.generation + 1;
+        state.file = create_segment(dir, generation)?;
+        state.generation = generation;
+        state.len = 0;
+        let covered = match state.pending.keys().next_back() {
+            Some(&seq) => seq + 1,
+            None => state.next_seq,
+        };
+        Ok((generation, covered))
+    }
+
+    /// Forgets about the segments that have been superseded by the checkpoint of `generation`:
+    /// their errors, and the changes before `covered` that are still missing from them.
+    ///
+    /// A change only goes missing if recording it panicked, and the changes sequenced after it
+    /// would otherwise be held back forever. It was made before changes that had been recorded
+    /// when the checkpoint started, so the checkpoint stands in for it.
+    fn checkpointed(&self, generation: u64, covered: u64) {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+        if matches!(state.error, Some((failed, _)) if failed < generation) {
+            state.error = None;
+        }
+        if state.next_seq < covered {
+            // the held back changes before `covered` are in the checkpoint as well
+            state.pending = state.pending.split_off(&covered);
+            state.next_seq = covered;
+            while let Some(frame) = state.pending.remove(&state.next_seq) {
+                state.write(frame);
+            }
+            self.written.notify_all();
+        }
+    }
+}
+
+impl LogState {
+    fn write(&mut self, frame: io::Result<Vec<u8>>) {
+        self.next_seq += 1;
+        if matches!(self.error, Some((failed, _)) if failed == self.generation) {
+            // the segment has a hole, so there is no point in writing more of it
+            return;
+        }
+        match frame.and_then(|frame| {
+            self.file.write_all(&frame)?;
+            Ok(frame.len() as u64)
+        }) {
+            Ok(len) => self.len += len,
+            Err(e) => self.error = Some((self.generation, e)),
+        }
+    }
+
+    fn check(&self) -> io::Result<()> {
+        match self.error {
+            Some((_, ref e)) => Err(copy_error(e)),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+}
+
+/// The change sink that appends to a [`Log`].
+struct LogSink(Arc<Log>);
+
+impl<K, V> ChangeSink<K, V> for LogSink
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn record(&self, event: ChangeEvent<&K, &V>) {
+        let mut payload = Vec::new();
+        // if serializing panics, the change is logged as an error before the panic continues, so
+        // that the changes sequenced after it are not held back waiting for it
+        let serialized = panic::catch_unwind(AssertUnwindSafe(|| {
+            bincode::serialize_into(&mut payload, &(event.key, event.new))
+        }));
+        let (frame, panicked) = match serialized {
+            Ok(serialized) => {
+                let frame = serialized.map_err(invalid_data).and_then(|()| {
+                    let mut frame = Vec::with_capacity(payload.len() + 8);
+                    write_frame(&mut frame, &payload)?;
+                    Ok(frame)
+                });
+                (frame, None)
+            }
+            Err(panicked) => (
+                Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "serializing a change panicked",
+                )),
+                Some(panicked),
+            ),
+        };
+        self.0.append(event.seq, frame);
+        if let Some(panicked) = panicked {
+            panic::resume_unwind(panicked);
+        }
+    }
+}
+
+enum FileKind {
+    Log,
+    Checkpoint,
+    Temporary,
+}
+
+fn parse_file_name(path: &Path) -> Option<(u64, FileKind)> {
+    let name = path.file_name()?.to_str()?;
+    let (generation, extension) = name.split_once('.')?;
+    let kind = match extension {
+        "log" => FileKind::Log,
+        "checkpoint" => FileKind::Checkpoint,
+        "log.tmp" | "checkpoint.tmp" => FileKind::Temporary,
+        _ => return None,
+    };
+    Some((generation.parse().ok()?, kind))
+}
+
+fn log_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("{:020}.log", generation))
+}
+
+fn checkpoint_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("{:020}.checkpoint", generation))
+}
+
+/// Removes the checkpoints and log segments that are older than `generation`.
+fn remove_older_than(dir: &Path, generation: u64) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        match parse_file_name(&path) {
+            Some((older, FileKind::Log | FileKind::Checkpoint)) if older < generation => {
+                fs::remove_file(&path)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Creates an empty log segment.
+///
+/// The segment is created under a temporary name, so that a crash cannot leave behind a segment
+/// with an incomplete header.
+fn create_segment(dir: &Path, generation: u64) -> io::Result<BufWriter<File>> {
+    let path = log_path(dir, generation);
+    let tmp = path.with_extension("log.tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(LOG_MAGIC)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)?;
+    sync_dir(dir)?;
+    Ok(BufWriter::new(file))
+}
+
+fn load_checkpoint<K, V, S>(map: &HashMap<K, V, S>, path: &Path) -> io::Result<()>
+where
+    K: Sync + Send + Clone + Hash + Ord + DeserializeOwned,
+    V: Sync + Send + DeserializeOwned,
+    S: BuildHasher,
+{
+    let mut file = BufReader::new(File::open(path)?);
+    read_magic(&mut file, CHECKPOINT_MAGIC)?;
+    let guard = map.guard();
+    let mut frame = Vec::new();
+    let mut count = 0u64;
+    loop {
+        if !matches!(read_frame(&mut file, &mut frame)?, FrameRead::Frame) {
+            // checkpoints are renamed into place once complete, so this is not a torn write
+            return Err(invalid_data("incomplete checkpoint"));
+        }
+        match frame.split_first() {
+            Some((&ENTRY_TAG, entry)) => {
+                let (key, value) = bincode::deserialize(entry).map_err(invalid_data)?;
+                map.insert(key, value, &guard);
+                count += 1;
+            }
+            Some((&END_TAG, expected)) if expected == count.to_le_bytes() => return Ok(()),
+            _ => return Err(invalid_data("corrupt checkpoint")),
+        }
+    }
+}
+
+/// Applies the changes in a log segment to `map`, and returns the size of the segment.
+///
+/// If the segment ends with a torn write and is the `last` segment, it is truncated to the
+/// changes that were written completely.
+fn replay_segment<K, V, S>(map: &HashMap<K, V, S>, path: &Path, last: bool) -> io::Result<u64>
+where
+    K: Sync + Send + Clone + Hash + Ord + DeserializeOwned,
+    V: Sync + Send + DeserializeOwned,
+    S: BuildHasher,
+{
+    let mut file = BufReader::new(File::open(path)?);
+    read_magic(&mut file, LOG_MAGIC)?;
+    let guard = map.guard();
+    let mut frame = Vec::new();
+    let mut len = LOG_MAGIC.len() as u64;
+    loop {
+        match read_frame(&mut file, &mut frame)? {
+            FrameRead::Frame => {}
+            FrameRead::End => return Ok(len),
+            FrameRead::Torn if last => {
+                drop(file);
+                let file = fs::OpenOptions::new().write(true).open(path)?;
+                file.set_len(len)?;
+                file.sync_all()?;
+                return Ok(len);
+            }
+            FrameRead::Torn => {
+                return Err(invalid_data(
+                    "corrupt write-ahead log segment before the last one",
+                ))
+            }
+        }
+        let (key, value): (K, Option<V>) = bincode::deserialize(&frame).map_err(invalid_data)?;
+        match value {
+            Some(value) => {
+                map.insert(key, value, &guard);
+            }
+            None => {
+                map.remove(&key, &guard);
+            }
+        }
+        len += frame.len() as u64 + 8;
+    }
+}
+
+fn read_magic(file: &mut impl Read, magic: &[u8; 8]) -> io::Result<()> {
+    let mut found = [0; 8];
+    file.read_exact(&mut found)?;
+    if &found != magic {
+        return Err(invalid_data("not a flurry persistence file"));
+    }
+    Ok(())
+}
+
+/// Writes a frame: the payload's length, its checksum and the payload itself.
+fn write_frame(file: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len()).map_err(|_| invalid_data("entry is too large"))?;
+    file.write_all(&len.to_le_bytes())?;
+    file.write_all(&crc32fast::hash(payload).to_le_bytes())?;
+    file.write_all(payload)
+}
+
+enum FrameRead {
+    /// A complete frame was read.
+    Frame,
+    /// The file ended between frames.
+    End,
+    /// The file ended within a frame, or the frame's checksum is wrong.
+    Torn,
+}
+
+fn read_frame(file: &mut impl Read, payload: &mut Vec<u8>) -> io::Result<FrameRead> {
+    let mut header = [0; 8];
+    match read_full(file, &mut header)? {
+        0 => return Ok(FrameRead::End),
+        8 => {}
+        _ => return Ok(FrameRead::Torn),
+    }
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    payload.clear();
+    file.by_ref().take(len as u64).read_to_end(payload)?;
+    if payload.len() != len || crc32fast::hash(payload) != checksum {
+        return Ok(FrameRead::Torn);
+    }
+    Ok(FrameRead::Frame)
+}
+
+/// Reads until `buf` is full or the file ends, and returns the number of bytes read.
+fn read_full(file: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Makes the creation and renaming of files in `dir` durable.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// `io::Error` is not `Clone`, so errors that are returned repeatedly are copied this way.
+fn copy_error(error: &io::Error) -> io::Error {
+    io::Error::new(error.kind(), error.to_string())
+}
@@ -0,0 +1,326 @@
+use crate::{HashMap, HashSet};
+use ::proptest::arbitrary::{any_with, Arbitrary};
+use ::proptest::collection::{
+    btree_map, btree_set, BTreeMapStrategy, BTreeMapValueTree, BTreeSetStrategy, BTreeSetValueTree,
+    SizeRange,
+};
+use ::proptest::strategy::{NewTree, Strategy, ValueTree};
+use ::proptest::test_runner::TestRunner;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// Creates a strategy that generates [`HashMap`]s with keys and values drawn from `key` and
+/// `value`, and a number of entries in `size`.
+///
+/// The maps use the default hasher. For another hasher `S`, use the [`Arbitrary`] impl of
+/// `HashMap<K, V, S>` through [`any_with`](::proptest::arbitrary::any_with).
+///
+/// The entries are generated and shrunk like those of
+/// [`proptest::collection::btree_map`](::proptest::collection::btree_map): shrinking first removes
+/// entries, and then shrinks the remaining keys and values. Like there, duplicate keys are
+/// rejected locally to reach the minimum size.
+///
+/// # Examples
+///
+/// ```
+/// use proptest::prelude::*;
+///
+/// proptest! {
+///     fn len_is_in_range(map in flurry::proptest::hash_map(0..100u32, any::<u8>(), 1..20)) {
+///         prop_assert!((1..20).contains(&map.len()));
+///     }
+/// }
+/// # len_is_in_range();
+/// ```
+pub fn hash_map<K, V>(key: K, value: V, size: impl Into<SizeRange>) -> HashMapStrategy<K, V>
+where
+    K: Strategy,
+    K::Value: Sync + Send + Clone + Hash + Ord,
+    V: Strategy,
+    V::Value: Sync + Send,
+{
+    HashMapStrategy {
+        entries: btree_map(key, value, size),
+        hasher: PhantomData,
+    }
+}
+
+/// Creates a strategy that generates [`HashSet`]s with elements drawn from `element`, and a
+/// number of elements in `size`.
+///
+/// The sets use the default hasher, like those of [`hash_map`].
+///
+/// The elements are generated and shrunk like those of
+/// [`proptest::collection::btree_set`](::proptest::collection::btree_set).
+pub fn hash_set<T>(element: T, size: impl Into<SizeRange>) -> HashSetStrategy<T>
+where
+    T: Strategy,
+    T::Value: Sync + Send + Clone + Hash + Ord,
+{
+    HashSetStrategy {
+        elements: btree_set(element, size),
+        hasher: PhantomData,
+    }
+}
+
+/// The strategy created by [`hash_map`].
+#[must_use = "strategies do nothing unless used"]
+pub struct HashMapStrategy<K, V, S = crate::DefaultHashBuilder>
+where
+    K: Strategy,
+    K::Value: Ord,
+    V: Strategy,
+{
+    entries: BTreeMapStrategy<K, V>,
+    hasher: PhantomData<fn() -> S>,
+}
+
+impl<K, V, S> Clone for HashMapStrategy<K, V, S>
+where
+    K: Strategy + Clone,
+    K::Value: Ord,
+    V: Strategy + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for HashMapStrategy<K, V, S>
+where
+    K: Strategy,
+    K::Value: Ord,
+    V: Strategy,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashMapStrategy")
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+impl<K, V, S> Strategy for HashMapStrategy<K, V, S>
+where
+    K: Strategy,
+    K::Value: Sync + Send + Clone + Hash + Ord,
+    V: Strategy,
+    V::Value: Sync + Send,
+    S: BuildHasher + Default,
+{
+    type Tree = HashMapValueTree<K::Tree, V::Tree, S>;
+    type Value = HashMap<K::Value, V::Value, S>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        Ok(HashMapValueTree {
+            entries: self.entries.new_tree(runner)?,
+            hasher: PhantomData,
+        })
+    }
+}
+
+/// The [`ValueTree`] of a [`HashMapStrategy`].
+pub struct HashMapValueTree<K, V, S = crate::DefaultHashBuilder>
+where
+    K: ValueTree,
+    K::Value: Ord,
+    V: ValueTree,
+{
+    entries: BTreeMapValueTree<K, V>,
+    hasher: PhantomData<fn() -> S>,
+}
+
+impl<K, V, S> Clone for HashMapValueTree<K, V, S>
+where
+    K: ValueTree + Clone,
+    K::Value: Ord,
+    V: ValueTree + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for HashMapValueTree<K, V, S>
+where
+    K: ValueTree + fmt::Debug,
+    K::Value: Ord,
+    V: ValueTree + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashMapValueTree")
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+impl<K, V, S> ValueTree for HashMapValueTree<K, V, S>
+where
+    K: ValueTree,
+    K::Value: Sync + Send + Clone + Hash + Ord,
+    V: ValueTree,
+    V::Value: Sync + Send,
+    S: BuildHasher + Default,
+{
+    type Value = HashMap<K::Value, V::Value, S>;
+
+    fn current(&self) -> Self::Value {
+        self.entries.current().into_iter().collect()
+    }
+
+    fn simplify(&mut self) -> bool {
+        self.entries.simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        self.entries.complicate()
+    }
+}
+
+/// The strategy created by [`hash_set`].
+#[must_use = "strategies do nothing unless used"]
+pub struct HashSetStrategy<T, S = crate::DefaultHashBuilder>
+where
+    T: Strategy,
+    T::Value: Ord,
+{
+    elements: BTreeSetStrategy<T>,
+    hasher: PhantomData<fn() -> S>,
+}
+
+impl<T, S> Clone for HashSetStrategy<T, S>
+where
+    T: Strategy + Clone,
+    T::Value: Ord,
+{
+    fn clone(&self) -> Self {
+        Self {
+            elements: self.elements.clone(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<T, S> fmt::Debug for HashSetStrategy<T, S>
+where
+    T: Strategy,
+    T::Value: Ord,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashSetStrategy")
+            .field("elements", &self.elements)
+            .finish()
+    }
+}
+
+impl<T, S> Strategy for HashSetStrategy<T, S>
+where
+    T: Strategy,
+    T::Value: Sync + Send + Clone + Hash + Ord,
+    S: BuildHasher + Default,
+{
+    type Tree = HashSetValueTree<T::Tree, S>;
+    type Value = HashSet<T::Value, S>;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        Ok(HashSetValueTree {
+            elements: self.elements.new_tree(runner)?,
+            hasher: PhantomData,
+        })
+    }
+}
+
+/// The [`ValueTree`] of a [`HashSetStrategy`].
+pub struct HashSetValueTree<T, S = crate::DefaultHashBuilder>
+where
+    T: ValueTree,
+    T::Value: Ord,
+{
+    elements: BTreeSetValueTree<T>,
+    hasher: PhantomData<fn() -> S>,
+}
+
+impl<T, S> Clone for HashSetValueTree<T, S>
+where
+    T: ValueTree + Clone,
+    T::Value: Ord,
+{
+    fn clone(&self) -> Self {
+        Self {
+            elements: self.elements.clone(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<T, S> fmt::Debug for HashSetValueTree<T, S>
+where
+    T: ValueTree + fmt::Debug,
+    T::Value: Ord,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashSetValueTree")
+            .field("elements", &self.elements)
+            .finish()
+    }
+}
+
+impl<T, S> ValueTree for HashSetValueTree<T, S>
+where
+    T: ValueTree,
+    T::Value: Sync + Send + Clone + Hash + Ord,
+    S: BuildHasher + Default,
+{
+    type Value = HashSet<T::Value, S>;
+
+    fn current(&self) -> Self::Value {
+        self.elements.current().into_iter().collect()
+    }
+
+    fn simplify(&mut self) -> bool {
+        self.elements.simplify()
+    }
+
+    fn complicate(&mut self) -> bool {
+        self.elements.complicate()
+    }
+}
+
+impl<K, V, S> Arbitrary for HashMap<K, V, S>
+where
+    K: Arbitrary + Sync + Send + Clone + Hash + Ord,
+    V: Arbitrary + Sync + Send,
+    S: BuildHasher + Default,
+{
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = HashMapStrategy<K::Strategy, V::Strategy, S>;
+
+    fn arbitrary_with((size, key, value): Self::Parameters) -> Self::Strategy {
+        HashMapStrategy {
+            entries: btree_map(any_with::<K>(key), any_with::<V>(value), size),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<T, S> Arbitrary for HashSet<T, S>
+where
+    T: Arbitrary + Sync + Send + Clone + Hash + Ord,
+    S: BuildHasher + Default,
+{
+    type Parameters = (SizeRange, T::Parameters);
+    type Strategy = HashSetStrategy<T::Strategy, S>;
+
+    fn arbitrary_with((size, element): Self::Parameters) -> Self::Strategy {
+        HashSetStrategy {
+            elements: btree_set(any_with::<T>(element), size),
+            hasher: PhantomData,
+        }
+    }
+}
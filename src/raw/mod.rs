@@ -2,7 +2,7 @@ use seize::Linked;
 
 use crate::node::*;
 use crate::reclaim::{self, Atomic, Collector, Guard, Shared};
-use std::borrow::Borrow;
+use crate::Comparable;
 use std::fmt::Debug;
 use std::sync::atomic::Ordering;
 
@@ -60,7 +60,7 @@ impl<K, V> Table<K, V> {
     }
 
     pub(crate) fn new(bins: usize, collector: &Collector) -> Self {
-        Self::from(vec![Atomic::null(); bins], collector)
+        Self::from((0..bins).map(|_| Atomic::null()).collect(), collector)
     }
 
     pub(crate) fn is_empty(&self) -> bool {
@@ -110,8 +110,7 @@ impl<K, V> Table<K, V> {
         guard: &'g Guard<'_>,
     ) -> Shared<'g, BinEntry<K, V>>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Ord,
+        Q: ?Sized + Comparable<K>,
     {
         match **bin {
             BinEntry::Node(_) => {
@@ -121,7 +120,7 @@ impl<K, V> Table<K, V> {
                         unreachable!("BinEntry::Node only points to BinEntry::Node");
                     };
 
-                    if n.hash == hash && n.key.borrow() == key {
+                    if n.hash == hash && key.equivalent(&n.key) {
                         // safety: this cast is fine because find
                         // is only used to return shared references
                         return Shared::from(node as *const _ as *mut _);
@@ -162,10 +161,13 @@ impl<K, V> Table<K, V> {
                             table = unsafe { table.next_table(guard).deref() };
                             continue;
                         }
+                        BinEntry::Reservation(_) => break Shared::null(),
                         BinEntry::TreeNode(_) => unreachable!("`find` was called on a Moved entry pointing to a TreeNode, which cannot be the first entry in a bin"),
                     }
                 }
             }
+            // a reserved bin is empty
+            BinEntry::Reservation(_) => Shared::null(),
             BinEntry::TreeNode(_) => {
                 unreachable!(
                     "`find` was called on a TreeNode, which cannot be the first entry in a bin"
@@ -179,6 +181,73 @@ impl<K, V> Table<K, V> {
         }
     }
 
+    /// Like [`Table::find`], but matches keys using `is_match` instead of comparing them with a
+    /// given key.
+    ///
+    /// Since `is_match` cannot be used to navigate a tree, tree bins are searched linearly by
+    /// following the `next` pointers of their nodes.
+    pub(crate) fn find_by<'g, F>(
+        &'g self,
+        bin: &'g Linked<BinEntry<K, V>>,
+        hash: u64,
+        mut is_match: F,
+        guard: &'g Guard<'_>,
+    ) -> Option<&'g Node<K, V>>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut node = match **bin {
+            BinEntry::Node(ref n) => n,
+            BinEntry::Tree(ref tree_bin) => {
+                let first = tree_bin.first.load(Ordering::SeqCst, guard);
+                if first.is_null() {
+                    return None;
+                }
+                // safety: the tree bin was read under our guard, and its nodes are only dropped
+                // after the bin is. the `next` pointers of a tree bin's nodes always form a valid
+                // list, even while another thread restructures the tree.
+                &unsafe { TreeNode::get_tree_node(first) }.node
+            }
+            BinEntry::Moved => {
+                // safety: same as for `find`.
+                let table = unsafe { self.next_table(guard).deref() };
+                if table.is_empty() {
+                    return None;
+                }
+                let bin = table.bin(table.bini(hash), guard);
+                if bin.is_null() {
+                    return None;
+                }
+                // safety: the table is protected by the guard, and so is the bin.
+                return table.find_by(unsafe { bin.deref() }, hash, is_match, guard);
+            }
+            // a reserved bin is empty
+            BinEntry::Reservation(_) => return None,
+            BinEntry::TreeNode(_) => {
+                unreachable!(
+                    "`find_by` was called on a TreeNode, which cannot be the first entry in a bin"
+                );
+            }
+        };
+
+        loop {
+            if node.hash == hash && is_match(&node.key) {
+                return Some(node);
+            }
+            let next = node.next.load(Ordering::SeqCst, guard);
+            if next.is_null() {
+                return None;
+            }
+            // safety: next will only be dropped, if bin are dropped. bin was read under
+            // a guard, and so cannot be dropped until we drop the guard at the earliest.
+            node = match **unsafe { next.deref() } {
+                BinEntry::Node(ref n) => n,
+                BinEntry::TreeNode(ref tn) => &tn.node,
+                _ => unreachable!("bins only ever link Nodes or TreeNodes"),
+            };
+        }
+    }
+
     pub(crate) fn drop_bins(&mut self) {
         // safety: we have &mut self _and_ all references we have returned are bound to the
         // lifetime of their borrow of self, so there cannot be any outstanding references to
@@ -198,6 +267,12 @@ impl<K, V> Table<K, V> {
             let bin_entry = unsafe { bin.load(Ordering::SeqCst, &guard).deref() };
             match **bin_entry {
                 BinEntry::Moved => {}
+                BinEntry::Reservation(_) => {
+                    // a reservation is only left behind by a `KeyLock` that was forgotten, and
+                    // holds no entries
+                    // safety: same as above + we own the bin
+                    drop(unsafe { bin.into_box() });
+                }
                 BinEntry::Node(_) => {
                     // safety: same as above + we own the bin - Nodes are not shared across the table
                     let mut p = unsafe { bin.into_box() };
@@ -296,6 +371,12 @@ impl<K, V> Table<K, V> {
         (hash & mask) as usize
     }
 
+    /// Hint to the CPU that the `i`th bin slot is about to be read.
+    #[inline]
+    pub(crate) fn prefetch_bin(&self, i: usize) {
+        prefetch(&self.bins[i]);
+    }
+
     #[inline]
     pub(crate) fn bin<'g>(&'g self, i: usize, guard: &'g Guard<'_>) -> Shared<'g, BinEntry<K, V>> {
         self.bins[i].load(Ordering::Acquire, guard)
@@ -323,3 +404,26 @@ impl<K, V> Table<K, V> {
         self.next_table.load(Ordering::SeqCst, guard)
     }
 }
+
+/// Hint to the CPU that the memory behind `ptr` is about to be read.
+///
+/// This is purely a performance hint, and is a no-op on architectures where we do not know how to
+/// issue prefetches. `ptr` does not need to be valid.
+#[inline(always)]
+pub(crate) fn prefetch<T>(ptr: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    // safety: prefetching never faults, even for invalid addresses
+    unsafe {
+        std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(ptr.cast())
+    };
+    #[cfg(all(target_arch = "x86", target_feature = "sse"))]
+    // safety: prefetching never faults, even for invalid addresses
+    unsafe {
+        std::arch::x86::_mm_prefetch::<{ std::arch::x86::_MM_HINT_T0 }>(ptr.cast())
+    };
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        all(target_arch = "x86", target_feature = "sse")
+    )))]
+    let _ = ptr;
+}
@@ -1,10 +1,56 @@
 pub(crate) use seize::{Collector, Guard, Linked};
 
+use crate::sync::AtomicPtr;
+use seize::Link;
 use std::marker::PhantomData;
 use std::ops::Deref;
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::atomic::Ordering;
 use std::{fmt, ptr};
 
+/// Creates the collector of a new map or group.
+#[cfg(not(loom))]
+pub(crate) fn collector() -> Collector {
+    Collector::new()
+}
+
+/// Creates the collector of a new map or group.
+///
+/// Loom runs all threads of a model on one OS thread, so they share seize's thread-local
+/// reservation, and retired values are only reclaimed once none of them holds a guard. Epoch
+/// tracking is disabled, which lets [`Atomic::load`] skip `Guard::protect` (it only accepts `std`
+/// atomics), and batches hold a single value so that reclamation happens within the few
+/// operations of a model. Maps given a collector of their own must configure it the same way.
+#[cfg(loom)]
+pub(crate) fn collector() -> Collector {
+    Collector::new().epoch_frequency(None).batch_size(1)
+}
+
+/// Marks the current thread as active in `collector`.
+pub(crate) fn enter(collector: &Collector) -> Guard<'_> {
+    let guard = collector.enter();
+    seqcst_fence();
+    guard
+}
+
+/// Lets garbage retired since `guard` was entered be reclaimed, keeping the thread active.
+pub(crate) fn refresh(guard: &mut Guard<'_>) {
+    guard.refresh();
+    seqcst_fence();
+}
+
+/// Mirrors seize's synchronization between entering a guard, retiring a value and reclaiming it
+/// to loom.
+///
+/// Seize orders these with `SeqCst` operations on its own atomics: a thread that enters a guard
+/// either is seen as active by the retiring thread, or sees every store that unlinked the retired
+/// value, and the thread that reclaims the value sees every store made before it was retired.
+/// Loom does not see those operations, and would otherwise let loads return stale values.
+#[inline(always)]
+fn seqcst_fence() {
+    #[cfg(loom)]
+    loom::sync::atomic::fence(Ordering::SeqCst);
+}
+
 pub(crate) struct Atomic<T>(AtomicPtr<Linked<T>>);
 
 impl<T> Atomic<T> {
@@ -12,10 +58,17 @@ impl<T> Atomic<T> {
         Self(AtomicPtr::default())
     }
 
+    #[cfg(not(loom))]
     pub(crate) fn load<'g>(&self, ordering: Ordering, guard: &'g Guard<'_>) -> Shared<'g, T> {
         guard.protect(&self.0, ordering).into()
     }
 
+    #[cfg(loom)]
+    pub(crate) fn load<'g>(&self, _: Ordering, _: &'g Guard<'_>) -> Shared<'g, T> {
+        // this is what `Guard::protect` does when epoch tracking is disabled, see `collector`
+        self.0.load(Ordering::SeqCst).into()
+    }
+
     pub(crate) fn store(&self, new: Shared<'_, T>, ordering: Ordering) {
         self.0.store(new.ptr, ordering);
     }
@@ -56,13 +109,13 @@ impl<T> Atomic<T> {
 
 impl<T> From<Shared<'_, T>> for Atomic<T> {
     fn from(shared: Shared<'_, T>) -> Self {
-        Atomic(shared.ptr.into())
+        Atomic(AtomicPtr::new(shared.ptr))
     }
 }
 
 impl<T> Clone for Atomic<T> {
     fn clone(&self) -> Self {
-        Atomic(self.0.load(Ordering::Relaxed).into())
+        Atomic(AtomicPtr::new(self.0.load(Ordering::Relaxed)))
     }
 }
 
@@ -149,10 +202,17 @@ pub(crate) trait RetireShared {
 
 impl RetireShared for Guard<'_> {
     unsafe fn retire_shared<T>(&self, shared: Shared<'_, T>) {
-        self.defer_retire(shared.ptr, seize::reclaim::boxed::<Linked<T>>);
+        seqcst_fence();
+        self.defer_retire(shared.ptr, reclaim_boxed::<T>);
     }
 }
 
+/// Drops a value retired with [`RetireShared::retire_shared`].
+unsafe fn reclaim_boxed<T>(link: *mut Link) {
+    seqcst_fence();
+    seize::reclaim::boxed::<Linked<T>>(link)
+}
+
 pub(crate) enum GuardRef<'g> {
     Owned(Guard<'g>),
     Ref(&'g Guard<'g>),
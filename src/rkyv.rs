@@ -0,0 +1,424 @@
+use crate::{HashMap, HashSet};
+use ::rkyv::bytecheck::CheckBytes;
+use ::rkyv::hash::{hash_value, FxHasher64};
+use ::rkyv::munge::munge;
+use ::rkyv::primitive::{ArchivedU64, ArchivedUsize, FixedUsize};
+use ::rkyv::rancor::{Fallible, Source};
+use ::rkyv::ser::{Allocator, Writer};
+use ::rkyv::vec::{ArchivedVec, VecResolver};
+use ::rkyv::{Archive, Deserialize, Place, Portable, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+/// The hash builder whose hashes are stored in an [`ArchivedHashMap`] or [`ArchivedHashSet`].
+///
+/// It is a portable fxhash, so an archive written on one platform can be queried on another.
+pub type ArchiveHashBuilder = BuildHasherDefault<FxHasher64>;
+
+/// An archived [`HashMap`], which can be queried directly from the bytes it was written to.
+///
+/// The entries are stored together with their hashes, grouped into a power-of-two number of bins
+/// by the low bits of the hash, and an index of where each bin starts. A lookup hashes the key
+/// once with [`ArchiveHashBuilder`], and then compares it with the entries of a single bin.
+///
+/// The archived map deserializes into a [`HashMap`] with any hasher that implements `Default`.
+/// Its table is built directly, like in [`HashMap::from_std`]. The stored hashes are not trusted
+/// for this: every key is hashed again, and an archive with duplicate keys fails to deserialize.
+///
+/// The layout is only checked to be memory safe when the archive is accessed with validation.
+/// **Lookups in the archive itself trust the stored hashes and bins.** An archive that was not
+/// written by flurry may have entries in the wrong bin, or duplicate keys, which a lookup then
+/// fails to find, or finds only once.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::HashMap;
+/// use rkyv::rancor::Error;
+///
+/// let map: HashMap<String, u32> = (0..100).map(|i| (i.to_string(), i)).collect();
+/// let bytes = rkyv::to_bytes::<Error>(&map)?;
+///
+/// let archived = rkyv::access::<rkyv::Archived<HashMap<String, u32>>, Error>(&bytes)?;
+/// assert_eq!(archived.get("42").map(|v| v.to_native()), Some(42));
+/// assert!(!archived.contains_key("100"));
+///
+/// let copy: HashMap<String, u32> = rkyv::deserialize::<_, Error>(archived)?;
+/// assert_eq!(copy, map);
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Portable, CheckBytes)]
+#[rkyv(crate = ::rkyv)]
+#[bytecheck(crate = ::rkyv::bytecheck)]
+#[repr(C)]
+pub struct ArchivedHashMap<K, V> {
+    /// `bins[i]..bins[i + 1]` is the range of `entries` that falls into bin `i`.
+    bins: ArchivedVec<ArchivedUsize>,
+    entries: ArchivedVec<Entry<K, V>>,
+}
+
+/// An entry of an [`ArchivedHashMap`].
+#[derive(Portable, CheckBytes)]
+#[rkyv(crate = ::rkyv)]
+#[bytecheck(crate = ::rkyv::bytecheck)]
+#[repr(C)]
+struct Entry<K, V> {
+    hash: ArchivedU64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> ArchivedHashMap<K, V> {
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries of the map, in the order of their bins.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.entries.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Returns an iterator over the keys of the map, in the order of their bins.
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.entries.iter().map(|entry| &entry.key)
+    }
+
+    /// Returns an iterator over the values of the map, in the order of their bins.
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.entries.iter().map(|entry| &entry.value)
+    }
+
+    /// Returns the key-value pair corresponding to `key`.
+    ///
+    /// The key may be any borrowed form of the archived key type, but [`Hash`] on the borrowed
+    /// form must match that of the key type it was archived from, as it does for `str` and
+    /// `String`.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let hash = hash_value::<Q, FxHasher64>(key);
+        let bins = self.bins.as_slice();
+        // there is one more bin boundary than there are bins, and a power of two of bins
+        let mask = bins.len().checked_sub(1)?.checked_sub(1)?;
+        let bin = hash as usize & mask;
+        let start = bins[bin].to_native() as usize;
+        let end = bins[bin + 1].to_native() as usize;
+        self.entries
+            .get(start..end)?
+            .iter()
+            .find(|entry| entry.hash == hash && entry.key.borrow() == key)
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Returns a reference to the value corresponding to `key`.
+    ///
+    /// See [`ArchivedHashMap::get_key_value`] for which types `key` may have.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    /// Returns `true` if the map contains a value for `key`.
+    ///
+    /// See [`ArchivedHashMap::get_key_value`] for which types `key` may have.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get_key_value(key).is_some()
+    }
+}
+
+impl<K, V> fmt::Debug for ArchivedHashMap<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// The resolver for an [`ArchivedHashMap`].
+pub struct HashMapResolver {
+    bins: VecResolver,
+    bins_len: usize,
+    entries: VecResolver,
+    len: usize,
+}
+
+impl fmt::Debug for HashMapResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashMapResolver")
+            .field("bins_len", &self.bins_len)
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, S> Archive for HashMap<K, V, S>
+where
+    K: Archive,
+    V: Archive,
+{
+    type Archived = ArchivedHashMap<K::Archived, V::Archived>;
+    type Resolver = HashMapResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        // the map may have changed since it was serialized, so the lengths come from the resolver
+        munge!(let ArchivedHashMap { bins, entries } = out);
+        ArchivedVec::resolve_from_len(resolver.bins_len, resolver.bins, bins);
+        ArchivedVec::resolve_from_len(resolver.len, resolver.entries, entries);
+    }
+}
+
+impl<K, V, S, Sr> Serialize<Sr> for HashMap<K, V, S>
+where
+    K: Serialize<Sr> + Hash,
+    V: Serialize<Sr>,
+    Sr: Fallible + Writer + Allocator + ?Sized,
+    Sr::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Sr) -> Result<Self::Resolver, Sr::Error> {
+        let guard = self.guard();
+        let mut entries: Vec<_> = self
+            .iter(&guard)
+            .map(|(key, value)| EntryRef {
+                hash: hash_value::<K, FxHasher64>(key),
+                key,
+                value,
+            })
+            .collect();
+
+        // group the entries by bin, and record where each bin starts
+        let n = entries.len().next_power_of_two();
+        // the bin boundaries are stored as `FixedUsize`, which may be narrower than `usize`
+        if FixedUsize::try_from(n + 1).is_err() {
+            return Err(Sr::Error::new(ArchiveError::TooManyEntries));
+        }
+        let bin = |hash: u64| hash as usize & (n - 1);
+        entries.sort_unstable_by_key(|entry| bin(entry.hash));
+        let mut bins = Vec::with_capacity(n + 1);
+        let mut entry = 0;
+        for i in 0..n {
+            bins.push(entry as FixedUsize);
+            while entry < entries.len() && bin(entries[entry].hash) == i {
+                entry += 1;
+            }
+        }
+        bins.push(entry as FixedUsize);
+
+        // validation expects the out-of-line data of the fields in the order of the fields
+        Ok(HashMapResolver {
+            bins: ArchivedVec::serialize_from_slice(&bins, serializer)?,
+            bins_len: bins.len(),
+            entries: ArchivedVec::serialize_from_slice(&entries, serializer)?,
+            len: entries.len(),
+        })
+    }
+}
+
+impl<K, V, S, D> Deserialize<HashMap<K, V, S>, D> for ArchivedHashMap<K::Archived, V::Archived>
+where
+    K: Archive + Sync + Send + Hash + Ord,
+    K::Archived: Deserialize<K, D>,
+    V: Archive + Sync + Send,
+    V::Archived: Deserialize<V, D>,
+    S: BuildHasher + Clone + Default,
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<HashMap<K, V, S>, D::Error> {
+        let hash_builder = S::default();
+        let entries = self.entries.iter().map(|entry| {
+            let key: K = entry.key.deserialize(deserializer)?;
+            let value = entry.value.deserialize(deserializer)?;
+            Ok((hash_builder.hash_one(&key), key, value))
+        });
+        HashMap::try_from_hashed(hash_builder.clone(), self.len(), entries, || {
+            D::Error::new(ArchiveError::DuplicateKey)
+        })
+    }
+}
+
+/// An error that rkyv reports for a map that cannot be archived or deserialized.
+#[derive(Debug)]
+enum ArchiveError {
+    /// The map has more entries than an archive can index.
+    TooManyEntries,
+    /// The archived map holds the same key more than once.
+    DuplicateKey,
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::TooManyEntries => f.write_str("too many entries to archive"),
+            ArchiveError::DuplicateKey => f.write_str("duplicate key in archived map"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// An entry of a map that is being serialized.
+struct EntryRef<'a, K, V> {
+    hash: u64,
+    key: &'a K,
+    value: &'a V,
+}
+
+impl<K, V> Archive for EntryRef<'_, K, V>
+where
+    K: Archive,
+    V: Archive,
+{
+    type Archived = Entry<K::Archived, V::Archived>;
+    type Resolver = (K::Resolver, V::Resolver);
+
+    fn resolve(&self, (key_resolver, value_resolver): Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let Entry { hash, key, value } = out);
+        self.hash.resolve((), hash);
+        self.key.resolve(key_resolver, key);
+        self.value.resolve(value_resolver, value);
+    }
+}
+
+impl<K, V, Sr> Serialize<Sr> for EntryRef<'_, K, V>
+where
+    K: Serialize<Sr>,
+    V: Serialize<Sr>,
+    Sr: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut Sr) -> Result<Self::Resolver, Sr::Error> {
+        Ok((
+            self.key.serialize(serializer)?,
+            self.value.serialize(serializer)?,
+        ))
+    }
+}
+
+/// An archived [`HashSet`], which can be queried directly from the bytes it was written to.
+///
+/// It has the same layout as an [`ArchivedHashMap`] with `()` values.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::HashSet;
+/// use rkyv::rancor::Error;
+///
+/// let set: HashSet<String> = ["a", "b"].iter().map(|s| s.to_string()).collect();
+/// let bytes = rkyv::to_bytes::<Error>(&set)?;
+///
+/// let archived = rkyv::access::<rkyv::Archived<HashSet<String>>, Error>(&bytes)?;
+/// assert!(archived.contains("a"));
+/// assert!(!archived.contains("c"));
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Portable, CheckBytes)]
+#[rkyv(crate = ::rkyv)]
+#[bytecheck(crate = ::rkyv::bytecheck)]
+#[repr(transparent)]
+pub struct ArchivedHashSet<T> {
+    map: ArchivedHashMap<T, ()>,
+}
+
+impl<T> ArchivedHashSet<T> {
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the set contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the elements of the set, in the order of their bins.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.map.keys()
+    }
+
+    /// Returns a reference to the element in the set that is equal to `value`.
+    ///
+    /// See [`ArchivedHashMap::get_key_value`] for which types `value` may have.
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get_key_value(value).map(|(key, _)| key)
+    }
+
+    /// Returns `true` if the set contains `value`.
+    ///
+    /// See [`ArchivedHashMap::get_key_value`] for which types `value` may have.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.contains_key(value)
+    }
+}
+
+impl<T> fmt::Debug for ArchivedHashSet<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> Archive for HashSet<T, S>
+where
+    T: Archive,
+{
+    type Archived = ArchivedHashSet<T::Archived>;
+    type Resolver = HashMapResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedHashSet { map } = out);
+        self.map.resolve(resolver, map);
+    }
+}
+
+impl<T, S, Sr> Serialize<Sr> for HashSet<T, S>
+where
+    T: Serialize<Sr> + Hash,
+    Sr: Fallible + Writer + Allocator + ?Sized,
+    Sr::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Sr) -> Result<Self::Resolver, Sr::Error> {
+        self.map.serialize(serializer)
+    }
+}
+
+impl<T, S, D> Deserialize<HashSet<T, S>, D> for ArchivedHashSet<T::Archived>
+where
+    T: Archive + Sync + Send + Hash + Ord,
+    T::Archived: Deserialize<T, D>,
+    S: BuildHasher + Clone + Default,
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<HashSet<T, S>, D::Error> {
+        let map: HashMap<T, (), S> = self.map.deserialize(deserializer)?;
+        Ok(HashSet { map })
+    }
+}
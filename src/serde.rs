@@ -0,0 +1,195 @@
+use ::serde::{Deserializer, Serialize, Serializer};
+
+/// How to handle a key that appears more than once when deserializing a [`HashMap`] or
+/// [`HashSet`].
+///
+/// The `Deserialize` impls of both use [`DuplicateKeys::Error`]. The other policies are available
+/// through [`DeserializeWithPolicy`], and through [`keep_first`] and [`keep_last`] for use with
+/// `#[serde(deserialize_with = "...")]`.
+///
+/// [`HashMap`]: crate::HashMap
+/// [`HashSet`]: crate::HashSet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DuplicateKeys {
+    /// Fail with a deserialization error.
+    #[default]
+    Error,
+    /// Keep the value of the first occurrence of the key, and ignore the others.
+    KeepFirst,
+    /// Keep the value of the last occurrence of the key.
+    ///
+    /// For a set, this is the same as `KeepFirst`, since there are no values, and the first of
+    /// several equal keys is the one that is kept in either case.
+    KeepLast,
+}
+
+/// Collections that can be deserialized with a [`DuplicateKeys`] policy.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::serde::{DeserializeWithPolicy, DuplicateKeys};
+/// use flurry::HashMap;
+///
+/// let json = r#"{"a": 1, "a": 2}"#;
+/// assert!(serde_json::from_str::<HashMap<String, u32>>(json).is_err());
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(json);
+/// let map: HashMap<String, u32> =
+///     HashMap::deserialize_with_policy(&mut deserializer, DuplicateKeys::KeepLast)?;
+/// assert_eq!(map.pin().get("a"), Some(&2));
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+pub trait DeserializeWithPolicy<'de>: Sized {
+    /// Deserializes a collection, handling duplicate keys according to `duplicates`.
+    fn deserialize_with_policy<D>(
+        deserializer: D,
+        duplicates: DuplicateKeys,
+    ) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+/// Deserializes a map or set, keeping the first occurrence of each duplicate key.
+///
+/// This is meant for `#[serde(deserialize_with = "flurry::serde::keep_first")]`.
+pub fn keep_first<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeWithPolicy<'de>,
+{
+    T::deserialize_with_policy(deserializer, DuplicateKeys::KeepFirst)
+}
+
+/// Deserializes a map or set, keeping the last occurrence of each duplicate key.
+///
+/// This is meant for `#[serde(deserialize_with = "flurry::serde::keep_last")]`.
+pub fn keep_last<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeWithPolicy<'de>,
+{
+    T::deserialize_with_policy(deserializer, DuplicateKeys::KeepLast)
+}
+
+/// Collections that can be serialized in the [`Ord`] order of their keys.
+///
+/// The `Serialize` impls of maps and sets emit entries in the order of the map's table, which
+/// depends on the hasher and on the history of the map. Sorting the keys instead makes the output
+/// deterministic, at the cost of collecting and sorting references to every entry first.
+pub trait SerializeSorted {
+    /// Serializes the collection with its keys in ascending order.
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer;
+}
+
+impl<T> SerializeSorted for &T
+where
+    T: SerializeSorted + ?Sized,
+{
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        (**self).serialize_sorted(serializer)
+    }
+}
+
+/// Serializes a map or set with its keys in ascending order.
+///
+/// This is meant for `#[serde(serialize_with = "flurry::serde::sorted")]`. See also [`Sorted`].
+pub fn sorted<T, Sr>(value: &T, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+where
+    T: SerializeSorted + ?Sized,
+    Sr: Serializer,
+{
+    value.serialize_sorted(serializer)
+}
+
+/// A wrapper whose `Serialize` impl serializes a map or set with its keys in ascending order.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::serde::Sorted;
+/// use flurry::HashMap;
+///
+/// let map = HashMap::new();
+/// for (i, name) in ["c", "a", "b"].into_iter().enumerate() {
+///     map.pin().insert(name, i);
+/// }
+/// let json = serde_json::to_string(&Sorted(&map))?;
+/// assert_eq!(json, r#"{"a":1,"b":2,"c":0}"#);
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Sorted<T>(pub T);
+
+impl<T> Serialize for Sorted<T>
+where
+    T: SerializeSorted,
+{
+    fn serialize<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        self.0.serialize_sorted(serializer)
+    }
+}
+
+/// A wrapper whose `Serialize` impl writes a map or set in chunks of bins, pinning the map's
+/// collector for one chunk at a time.
+///
+/// The `Serialize` impls of maps and sets hold a single guard while the whole collection is
+/// written, so a slow writer keeps memory that has since been removed from *every* map using the
+/// same collector from being reclaimed. `Chunked` walks the table with
+/// [`HashMap::scan`](crate::HashMap::scan) instead, and enters a new guard for every chunk.
+///
+/// The output is weakly consistent, like that of an iterator: every entry that is present for
+/// the whole write is included, entries that are inserted or removed during the write may or may
+/// not be, and a key whose entry is removed and inserted again during the write may appear twice.
+/// If the map may change while it is written, deserialize the output with [`keep_last`] to accept
+/// such duplicates.
+///
+/// The number of entries is not known in advance, so formats that need the length of a map or
+/// sequence up front cannot be written this way.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::serde::Chunked;
+/// use flurry::HashMap;
+///
+/// let map: HashMap<u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+/// let json = serde_json::to_string(&Chunked::new(&map).with_chunk_size(256))?;
+///
+/// let copy: HashMap<u32, u32> = serde_json::from_str(&json)?;
+/// assert_eq!(copy, map);
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Chunked<'m, T> {
+    pub(crate) collection: &'m T,
+    pub(crate) chunk_size: usize,
+}
+
+impl<'m, T> Chunked<'m, T> {
+    /// Wraps a [`HashMap`](crate::HashMap) or [`HashSet`](crate::HashSet) to serialize it in
+    /// chunks of about 1024 entries.
+    pub fn new(collection: &'m T) -> Self {
+        Self {
+            collection,
+            chunk_size: 1024,
+        }
+    }
+
+    /// Sets the number of entries to write with each guard.
+    ///
+    /// Since a bin is never split between chunks, a chunk may hold a few more entries than this.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+}
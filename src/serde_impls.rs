@@ -1,13 +1,17 @@
+use crate::serde::{Chunked, DeserializeWithPolicy, DuplicateKeys, SerializeSorted};
 use crate::{HashMap, HashMapRef, HashSet, HashSetRef};
 use serde::{
-    de::{MapAccess, SeqAccess, Visitor},
+    de::{Error, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq},
     Deserialize, Deserializer, Serialize, Serializer,
 };
+use std::collections::BTreeSet;
 use std::fmt::{self, Formatter};
 use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 
 struct HashMapVisitor<K, V, S> {
+    duplicates: DuplicateKeys,
     key_marker: PhantomData<K>,
     value_marker: PhantomData<V>,
     hash_builder_marker: PhantomData<S>,
@@ -39,6 +43,59 @@ where
     }
 }
 
+impl<K, V, S> SerializeSorted for HashMapRef<'_, K, V, S>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+{
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        serializer.collect_map(entries)
+    }
+}
+
+impl<K, V, S> SerializeSorted for HashMap<K, V, S>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+{
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        self.pin().serialize_sorted(serializer)
+    }
+}
+
+impl<K, V, S> Serialize for Chunked<'_, HashMap<K, V, S>>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        let mut state = serializer.serialize_map(None)?;
+        let mut cursor = 0;
+        loop {
+            let guard = self.collection.guard();
+            let (next, entries) = self.collection.scan(cursor, self.chunk_size, &guard);
+            for (key, value) in entries {
+                state.serialize_entry(key, value)?;
+            }
+            cursor = next;
+            if cursor == 0 {
+                return state.end();
+            }
+        }
+    }
+}
+
 impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
 where
     K: Deserialize<'de> + Send + Sync + Hash + Clone + Ord,
@@ -49,13 +106,93 @@ where
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_map(HashMapVisitor::new())
+        Self::deserialize_with_policy(deserializer, DuplicateKeys::Error)
+    }
+
+    fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        place.update_from_deserializer(deserializer, true)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Send + Sync + Hash + Clone + Ord,
+    V: Send + Sync,
+    S: BuildHasher,
+{
+    /// Merges the entries of a serialized map into this map, while it remains in use.
+    ///
+    /// Every entry in the input is inserted into the map, replacing the value of a key that is
+    /// already present. If `remove_absent` is `true`, keys that are not in the input are removed
+    /// once the whole input has been read, unless their value changes concurrently. Readers see
+    /// the changes one at a time, as though they were made through [`HashMap::insert`] and
+    /// [`HashMap::remove`], and references they hold to untouched entries stay valid.
+    ///
+    /// Like deserializing a new map, a key that appears more than once in the input is an error.
+    /// If deserialization fails, the entries read before the failure have already been inserted,
+    /// but nothing has been removed.
+    ///
+    /// This is also what `Deserialize::deserialize_in_place` does for a `HashMap`, with
+    /// `remove_absent` set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashMap;
+    ///
+    /// let config: HashMap<String, u32> = serde_json::from_str(r#"{"workers": 4, "retries": 3}"#)?;
+    /// let workers = config.pin().get("workers").copied();
+    ///
+    /// let mut reload = serde_json::Deserializer::from_str(r#"{"workers": 8, "timeout": 30}"#);
+    /// config.update_from_deserializer(&mut reload, true)?;
+    ///
+    /// assert_eq!(workers, Some(4));
+    /// assert_eq!(config.pin().get("workers"), Some(&8));
+    /// assert_eq!(config.pin().get("timeout"), Some(&30));
+    /// assert_eq!(config.pin().get("retries"), None);
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    pub fn update_from_deserializer<'de, D>(
+        &self,
+        deserializer: D,
+        remove_absent: bool,
+    ) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        deserializer.deserialize_map(HashMapUpdateVisitor {
+            map: self,
+            remove_absent,
+        })
+    }
+}
+
+impl<'de, K, V, S> DeserializeWithPolicy<'de> for HashMap<K, V, S>
+where
+    K: Deserialize<'de> + Send + Sync + Hash + Clone + Ord,
+    V: Deserialize<'de> + Send + Sync,
+    S: Default + BuildHasher,
+{
+    fn deserialize_with_policy<D>(
+        deserializer: D,
+        duplicates: DuplicateKeys,
+    ) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(HashMapVisitor::new(duplicates))
     }
 }
 
 impl<K, V, S> HashMapVisitor<K, V, S> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(duplicates: DuplicateKeys) -> Self {
         Self {
+            duplicates,
             key_marker: PhantomData,
             value_marker: PhantomData,
             hash_builder_marker: PhantomData,
@@ -88,8 +225,18 @@ where
             let guard = map.guard();
 
             while let Some((key, value)) = access.next_entry()? {
-                if let Some(_old_value) = map.insert(key, value, &guard) {
-                    unreachable!("Serialized map held two values with the same key");
+                match self.duplicates {
+                    DuplicateKeys::Error => {
+                        if map.try_insert(key, value, &guard).is_err() {
+                            return Err(M::Error::custom("duplicate key in map"));
+                        }
+                    }
+                    DuplicateKeys::KeepFirst => {
+                        let _ = map.try_insert(key, value, &guard);
+                    }
+                    DuplicateKeys::KeepLast => {
+                        map.insert(key, value, &guard);
+                    }
                 }
             }
         }
@@ -98,6 +245,44 @@ where
     }
 }
 
+/// Like `HashMapVisitor`, but merges the entries into an existing map.
+struct HashMapUpdateVisitor<'m, K, V, S> {
+    map: &'m HashMap<K, V, S>,
+    remove_absent: bool,
+}
+
+impl<'de, K, V, S> Visitor<'de> for HashMapUpdateVisitor<'_, K, V, S>
+where
+    K: Deserialize<'de> + Send + Sync + Hash + Clone + Ord,
+    V: Deserialize<'de> + Send + Sync,
+    S: BuildHasher,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "a map")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let guard = self.map.guard();
+        // the map may hold the keys already, so duplicates are found by tracking what we have read
+        let mut seen = BTreeSet::new();
+        while let Some((key, value)) = access.next_entry::<K, V>()? {
+            if !seen.insert(key.clone()) {
+                return Err(M::Error::custom("duplicate key in map"));
+            }
+            self.map.insert(key, value, &guard);
+        }
+        if self.remove_absent {
+            self.map.retain(|key, _| seen.contains(key), &guard);
+        }
+        Ok(())
+    }
+}
+
 impl<T, S> Serialize for HashSetRef<'_, T, S>
 where
     T: Serialize,
@@ -122,6 +307,57 @@ where
     }
 }
 
+impl<T, S> SerializeSorted for HashSetRef<'_, T, S>
+where
+    T: Serialize + Ord,
+{
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        let mut values: Vec<_> = self.iter().collect();
+        values.sort_unstable();
+        serializer.collect_seq(values)
+    }
+}
+
+impl<T, S> SerializeSorted for HashSet<T, S>
+where
+    T: Serialize + Ord,
+{
+    fn serialize_sorted<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        self.pin().serialize_sorted(serializer)
+    }
+}
+
+impl<T, S> Serialize for Chunked<'_, HashSet<T, S>>
+where
+    T: Serialize,
+{
+    fn serialize<Sr>(&self, serializer: Sr) -> Result<Sr::Ok, Sr::Error>
+    where
+        Sr: Serializer,
+    {
+        let map = &self.collection.map;
+        let mut state = serializer.serialize_seq(None)?;
+        let mut cursor = 0;
+        loop {
+            let guard = map.guard();
+            let (next, entries) = map.scan(cursor, self.chunk_size, &guard);
+            for (value, ()) in entries {
+                state.serialize_element(value)?;
+            }
+            cursor = next;
+            if cursor == 0 {
+                return state.end();
+            }
+        }
+    }
+}
+
 impl<'de, T, S> Deserialize<'de> for HashSet<T, S>
 where
     T: Deserialize<'de> + Send + Sync + Hash + Clone + Ord,
@@ -131,18 +367,36 @@ where
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_seq(HashSetVisitor::new())
+        Self::deserialize_with_policy(deserializer, DuplicateKeys::Error)
+    }
+}
+
+impl<'de, T, S> DeserializeWithPolicy<'de> for HashSet<T, S>
+where
+    T: Deserialize<'de> + Send + Sync + Hash + Clone + Ord,
+    S: Default + BuildHasher,
+{
+    fn deserialize_with_policy<D>(
+        deserializer: D,
+        duplicates: DuplicateKeys,
+    ) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(HashSetVisitor::new(duplicates))
     }
 }
 
 struct HashSetVisitor<T, S> {
+    duplicates: DuplicateKeys,
     type_marker: PhantomData<T>,
     hash_builder_marker: PhantomData<S>,
 }
 
 impl<T, S> HashSetVisitor<T, S> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(duplicates: DuplicateKeys) -> Self {
         Self {
+            duplicates,
             type_marker: PhantomData,
             hash_builder_marker: PhantomData,
         }
@@ -170,7 +424,9 @@ where
             let guard = set.guard();
 
             while let Some(value) = access.next_element()? {
-                let _ = set.insert(value, &guard);
+                if !set.insert(value, &guard) && self.duplicates == DuplicateKeys::Error {
+                    return Err(A::Error::custom("duplicate element in set"));
+                }
             }
         }
 
@@ -180,6 +436,9 @@ where
 
 #[cfg(test)]
 mod test {
+    use crate::serde::{
+        keep_first, keep_last, Chunked, DeserializeWithPolicy, DuplicateKeys, Sorted,
+    };
     use crate::{HashMap, HashSet};
 
     #[test]
@@ -238,4 +497,155 @@ mod test {
 
         assert_eq!(map, deserialized);
     }
+
+    #[test]
+    fn test_map_duplicate_keys() {
+        let json = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let err = serde_json::from_str::<HashMap<String, u8>>(json).unwrap_err();
+        assert!(err.to_string().contains("duplicate key"));
+
+        let map: HashMap<String, u8> =
+            keep_first(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        assert_eq!(map.pin().get("a"), Some(&1));
+        assert_eq!(map.len(), 2);
+
+        let map: HashMap<String, u8> =
+            keep_last(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        assert_eq!(map.pin().get("a"), Some(&3));
+        assert_eq!(map.pin().get("b"), Some(&2));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_set_duplicate_keys() {
+        let json = "[1, 2, 1]";
+        let err = serde_json::from_str::<HashSet<u8>>(json).unwrap_err();
+        assert!(err.to_string().contains("duplicate element"));
+
+        for duplicates in [DuplicateKeys::KeepFirst, DuplicateKeys::KeepLast] {
+            let set = HashSet::<u8>::deserialize_with_policy(
+                &mut serde_json::Deserializer::from_str(json),
+                duplicates,
+            )
+            .unwrap();
+            assert_eq!(set, [1u8, 2].into_iter().collect::<HashSet<_>>());
+        }
+    }
+
+    #[test]
+    fn test_sorted() {
+        // maps with different insertion orders and table sizes serialize the same
+        let small: HashMap<u32, u32> = (0..100).map(|i| (i, i * 2)).collect();
+        let large: HashMap<u32, u32> = HashMap::with_capacity(1024);
+        for i in (0..100).rev() {
+            large.pin().insert(i, i * 2);
+        }
+        let json = serde_json::to_string(&Sorted(&small)).unwrap();
+        assert_eq!(json, serde_json::to_string(&Sorted(&large)).unwrap());
+        assert_eq!(json, serde_json::to_string(&Sorted(&large.pin())).unwrap());
+        let expected: std::collections::BTreeMap<u32, u32> = (0..100).map(|i| (i, i * 2)).collect();
+        assert_eq!(json, serde_json::to_string(&expected).unwrap());
+
+        let set: HashSet<u32> = [5, 3, 9, 1].into_iter().collect();
+        assert_eq!(serde_json::to_string(&Sorted(&set)).unwrap(), "[1,3,5,9]");
+        assert_eq!(
+            serde_json::to_string(&Sorted(set.pin())).unwrap(),
+            "[1,3,5,9]"
+        );
+    }
+
+    #[test]
+    fn test_chunked() {
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i * 2)).collect();
+        for chunk_size in [0, 1, 7, 1000, 5000, usize::MAX] {
+            let json =
+                serde_json::to_string(&Chunked::new(&map).with_chunk_size(chunk_size)).unwrap();
+            let copy: HashMap<u32, u32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(copy, map);
+        }
+        let empty = HashMap::<u32, u32>::new();
+        assert_eq!(serde_json::to_string(&Chunked::new(&empty)).unwrap(), "{}");
+
+        let set: HashSet<u32> = (0..1000).collect();
+        for chunk_size in [10, usize::MAX] {
+            let json =
+                serde_json::to_string(&Chunked::new(&set).with_chunk_size(chunk_size)).unwrap();
+            let copy: HashSet<u32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(copy, set);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_chunked_concurrent_writes() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        // keys 0..500 stay put, while the writer removes keys from 500 on and inserts keys from
+        // 1000 on, which makes the table grow
+        let map: Arc<HashMap<u32, u32>> = Arc::new((0..1000).map(|i| (i, i)).collect());
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+            std::thread::spawn(move || {
+                let mut i = 1000;
+                while !done.load(Ordering::SeqCst) {
+                    map.pin().insert(i, i);
+                    map.pin().remove(&(i - 500));
+                    i += 1;
+                }
+            })
+        };
+        for _ in 0..20 {
+            let json = serde_json::to_string(&Chunked::new(&*map).with_chunk_size(16)).unwrap();
+            let copy: HashMap<u32, u32> =
+                keep_last(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+            let guard = copy.guard();
+            for i in 0..500 {
+                assert_eq!(copy.get(&i, &guard), Some(&i));
+            }
+        }
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_update_from_deserializer() {
+        let map: HashMap<u8, u8> = (0..10).map(|i| (i, i)).collect();
+        let guard = map.guard();
+        let kept = map.get(&1, &guard).unwrap();
+
+        // merging leaves absent keys alone
+        map.update_from_deserializer(
+            &mut serde_json::Deserializer::from_str(r#"{"1": 10, "20": 20}"#),
+            false,
+        )
+        .unwrap();
+        assert_eq!(map.len(), 11);
+        assert_eq!(map.get(&1, &guard), Some(&10));
+        assert_eq!(map.get(&20, &guard), Some(&20));
+        // references to replaced values remain valid for the guard
+        assert_eq!(kept, &1);
+
+        // deserialize_in_place also removes absent keys
+        drop(guard);
+        let mut map = map;
+        serde::Deserialize::deserialize_in_place(
+            &mut serde_json::Deserializer::from_str(r#"{"2": 2, "3": 30}"#),
+            &mut map,
+        )
+        .unwrap();
+        let expected: HashMap<u8, u8> = [(2, 2), (3, 30)].into_iter().collect();
+        assert_eq!(map, expected);
+
+        // a duplicate key fails without removing anything
+        let err = map
+            .update_from_deserializer(
+                &mut serde_json::Deserializer::from_str(r#"{"4": 4, "4": 5}"#),
+                true,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate key"));
+        assert_eq!(map.len(), 3);
+    }
 }
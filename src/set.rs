@@ -3,11 +3,12 @@
 //! See `HashSet` for details.
 
 use crate::iter::Keys;
-use crate::reclaim::Guard;
+use crate::reclaim::{Collector, Guard};
+use crate::Comparable;
 use crate::HashMap;
-use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
 
 /// A concurrent hash set implemented as a `HashMap` where the value is `()`.
 ///
@@ -146,6 +147,34 @@ impl<T, S> HashSet<T, S> {
         }
     }
 
+    /// Associate a custom [`seize::Collector`] with this set.
+    ///
+    /// By default, the global collector is used. With this method you can use a different
+    /// collector instead. This may be desireable if you want more control over when and how memory
+    /// reclamation happens.
+    ///
+    /// Note that _all_ `Guard` references provided to access the returned set _must_ be
+    /// constructed using guards produced by `collector`.
+    ///
+    /// To share one collector between several maps and sets, use a [`MapGroup`](crate::MapGroup)
+    /// instead.
+    #[must_use]
+    pub fn with_collector(self, collector: Collector) -> Self {
+        Self {
+            map: self.map.with_collector(collector),
+        }
+    }
+
+    pub(crate) fn with_capacity_and_shared_collector(
+        capacity: usize,
+        hash_builder: S,
+        collector: Arc<Collector>,
+    ) -> Self {
+        Self {
+            map: HashMap::with_capacity_and_shared_collector(capacity, hash_builder, collector),
+        }
+    }
+
     /// Pin a `Guard` for use with this set.
     ///
     /// Keep in mind that for as long as you hold onto this `Guard`, you are preventing the
@@ -242,8 +271,7 @@ where
     #[inline]
     pub fn contains<Q>(&self, value: &Q, guard: &Guard<'_>) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.contains_key(value, guard)
     }
@@ -269,12 +297,44 @@ where
     /// ```
     pub fn get<'g, Q>(&'g self, value: &Q, guard: &'g Guard<'_>) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.get_key_value(value, guard).map(|(k, _)| k)
     }
 
+    /// Returns a clone of the element in the set, if any, that is equal to the given value.
+    ///
+    /// Unlike [`HashSet::get`], this method does not take a `Guard`. See
+    /// [`HashMap::get_cloned`](crate::HashMap::get_cloned) for how the current thread is pinned
+    /// instead.
+    ///
+    /// The value may be any borrowed form of the set's value type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the value type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set: HashSet<_> = [1, 2, 3].iter().cloned().collect();
+    /// assert_eq!(set.get_cloned(&2), Some(2));
+    /// assert_eq!(set.get_cloned(&4), None);
+    /// ```
+    pub fn get_cloned<Q>(&self, value: &Q) -> Option<T>
+    where
+        T: Clone,
+        Q: ?Sized + Hash + Comparable<T>,
+    {
+        let guard = self.guard();
+        self.map
+            .get_key_value(value, &guard)
+            .map(|(k, _)| k.clone())
+    }
+
     /// Returns `true` if `self` has no elements in common with `other`.
     ///
     /// This is equivalent to checking for an empty intersection.
@@ -437,8 +497,7 @@ where
     /// ```
     pub fn remove<Q>(&self, value: &Q, guard: &Guard<'_>) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         let removed = self.map.remove(value, guard);
         removed.is_some()
@@ -465,12 +524,60 @@ where
     /// ```
     pub fn take<'g, Q>(&'g self, value: &Q, guard: &'g Guard<'_>) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.map.remove_entry(value, guard).map(|(k, _)| k)
     }
 
+    /// Adds a value to the set without requiring a `Guard`.
+    ///
+    /// Returns `true` if the set did not have this value present. See
+    /// [`HashMap::get_cloned`](crate::HashMap::get_cloned) for how the current thread is pinned
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set = HashSet::new();
+    /// assert_eq!(set.insert_owned(2), true);
+    /// assert_eq!(set.insert_owned(2), false);
+    /// ```
+    pub fn insert_owned(&self, value: T) -> bool {
+        self.map.insert_owned(value, ()).is_none()
+    }
+
+    /// Removes a value from the set without requiring a `Guard`.
+    ///
+    /// Returns `true` if the set did have this value present. See
+    /// [`HashMap::get_cloned`](crate::HashMap::get_cloned) for how the current thread is pinned
+    /// instead.
+    ///
+    /// The value may be any borrowed form of the set's value type, but
+    /// [`Hash`] and [`Ord`] on the borrowed form *must* match those for
+    /// the value type.
+    ///
+    /// [`Ord`]: std::cmp::Ord
+    /// [`Hash`]: std::hash::Hash
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flurry::HashSet;
+    ///
+    /// let set = HashSet::new();
+    /// set.insert_owned(2);
+    /// assert_eq!(set.remove_owned(&2), true);
+    /// assert_eq!(set.remove_owned(&2), false);
+    /// ```
+    pub fn remove_owned<Q>(&self, value: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Comparable<T>,
+    {
+        self.map.remove_owned(value).is_some()
+    }
+
     /// Retains only the elements specified by the predicate.
     ///
     /// In other words, remove all elements `e` such that `f(&e)` returns `false`.
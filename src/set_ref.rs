@@ -1,7 +1,7 @@
 use crate::iter::*;
 use crate::reclaim::{Guard, GuardRef};
+use crate::Comparable;
 use crate::HashSet;
-use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
 
@@ -71,8 +71,7 @@ where
     #[inline]
     pub fn contains<Q>(&self, value: &Q) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.contains(value, &self.guard)
     }
@@ -82,8 +81,7 @@ where
     /// See also [`HashSet::get`].
     pub fn get<'g, Q>(&'g self, value: &Q) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.get(value, &self.guard)
     }
@@ -127,8 +125,7 @@ where
     /// See also [`HashSet::remove`].
     pub fn remove<Q>(&self, value: &Q) -> bool
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.remove(value, &self.guard)
     }
@@ -138,8 +135,7 @@ where
     /// See also [`HashSet::take`].
     pub fn take<'g, Q>(&'g self, value: &Q) -> Option<&'g T>
     where
-        T: Borrow<Q>,
-        Q: ?Sized + Hash + Ord,
+        Q: ?Sized + Hash + Comparable<T>,
     {
         self.set.take(value, &self.guard)
     }
@@ -0,0 +1,338 @@
+//! A map that computes missing values asynchronously, once per key.
+//!
+//! See `SingleFlight` for details.
+
+use crate::{Comparable, HashMap};
+use parking_lot::Mutex;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// A [`HashMap`] whose missing values are computed by futures, with a single computation per key.
+///
+/// When several tasks call [`get_or_insert_with_future`](SingleFlight::get_or_insert_with_future)
+/// for the same missing key, only the first one runs its loader. While it does, the map holds an
+/// in-flight placeholder for the key, and the other tasks wait for the placeholder to be replaced
+/// by the loaded value. If the loader fails, or the task running it is cancelled by dropping its
+/// future, the placeholder is removed and the waiting tasks start over, so that one of them runs
+/// its own loader.
+///
+/// Values are returned by cloning them, since references into the map cannot be held across an
+/// `.await`. Use an `Arc` for values that are expensive to clone.
+///
+/// # Examples
+///
+/// ```
+/// use flurry::SingleFlight;
+/// # use std::future::Future;
+/// # use std::pin::pin;
+/// # use std::sync::Arc;
+/// # use std::task::{Context, Poll, Wake};
+/// # struct Unpark(std::thread::Thread);
+/// # impl Wake for Unpark {
+/// #     fn wake(self: Arc<Self>) { self.0.unpark() }
+/// # }
+/// # fn block_on<F: Future>(future: F) -> F::Output {
+/// #     let mut future = pin!(future);
+/// #     let waker = Arc::new(Unpark(std::thread::current())).into();
+/// #     let mut cx = Context::from_waker(&waker);
+/// #     loop {
+/// #         match future.as_mut().poll(&mut cx) {
+/// #             Poll::Ready(output) => return output,
+/// #             Poll::Pending => std::thread::park(),
+/// #         }
+/// #     }
+/// # }
+///
+/// let cache = SingleFlight::new();
+/// block_on(async {
+///     let value = cache
+///         .get_or_insert_with_future("answer", || async { 42 })
+///         .await;
+///     assert_eq!(value, 42);
+///
+///     // the value is now in the map, so the loader does not run again
+///     let value = cache
+///         .get_or_insert_with_future("answer", || async { unreachable!() })
+///         .await;
+///     assert_eq!(value, 42);
+/// });
+/// assert_eq!(cache.get("answer"), Some(42));
+/// ```
+pub struct SingleFlight<K, V, S = crate::DefaultHashBuilder> {
+    map: HashMap<K, Slot<V>, S>,
+}
+
+enum Slot<V> {
+    Ready(V),
+    Pending(Arc<Flight>),
+}
+
+/// An in-flight computation, which tasks can wait for.
+#[derive(Default)]
+struct Flight {
+    state: Mutex<FlightState>,
+}
+
+#[derive(Default)]
+struct FlightState {
+    done: bool,
+    // the wakers of the waiting tasks. a task that stops waiting clears its slot, but keeps it
+    // so that the other tasks' indices stay valid.
+    wakers: Vec<Option<Waker>>,
+}
+
+impl Flight {
+    /// Marks the computation as done, and wakes the tasks that are waiting for it.
+    fn complete(&self) {
+        let wakers = {
+            let mut state = self.state.lock();
+            state.done = true;
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+
+    fn wait(self: Arc<Self>) -> Wait {
+        Wait {
+            flight: self,
+            index: None,
+        }
+    }
+}
+
+/// A future that resolves once a [`Flight`] is done.
+struct Wait {
+    flight: Arc<Flight>,
+    // the position of our waker in the flight's wakers, if we have registered one
+    index: Option<usize>,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        let mut state = this.flight.state.lock();
+        if state.done {
+            return Poll::Ready(());
+        }
+        match this.index {
+            Some(i) => match &mut state.wakers[i] {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                slot => *slot = Some(cx.waker().clone()),
+            },
+            None => {
+                this.index = Some(state.wakers.len());
+                state.wakers.push(Some(cx.waker().clone()));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Wait {
+    fn drop(&mut self) {
+        // a cancelled waiter must not keep its task's waker alive until the flight is done
+        if let Some(i) = self.index {
+            let mut state = self.flight.state.lock();
+            if !state.done {
+                state.wakers[i] = None;
+            }
+        }
+    }
+}
+
+impl<K, V> SingleFlight<K, V, crate::DefaultHashBuilder> {
+    /// Creates an empty `SingleFlight`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, S> Default for SingleFlight<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> SingleFlight<K, V, S> {
+    /// Creates an empty `SingleFlight` which will use `hash_builder` to hash keys.
+    ///
+    /// See also [`HashMap::with_hasher`].
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+}
+
+impl<K, V, S> SingleFlight<K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send + Clone,
+    S: BuildHasher,
+{
+    /// Returns a clone of the value for `key`, if it has been loaded.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        match self.map.pin().get(key)? {
+            Slot::Ready(value) => Some(value.clone()),
+            Slot::Pending(_) => None,
+        }
+    }
+
+    /// Inserts a loaded value for `key`, returning the previous one if there was one.
+    ///
+    /// If the key is being loaded, the result of that computation is discarded.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        match self.map.pin().insert(key, Slot::Ready(value))? {
+            Slot::Ready(value) => Some(value.clone()),
+            Slot::Pending(_) => None,
+        }
+    }
+
+    /// Removes `key`, returning its value if it had been loaded.
+    ///
+    /// If the key is being loaded, the result of that computation is discarded.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Comparable<K>,
+    {
+        match self.map.pin().remove(key)? {
+            Slot::Ready(value) => Some(value.clone()),
+            Slot::Pending(_) => None,
+        }
+    }
+
+    /// Returns a clone of the value for `key`, loading it with the future returned by `f` if it
+    /// is missing.
+    ///
+    /// If another task is already loading the key, this waits for that task to finish instead of
+    /// calling `f`. If that task is cancelled, this starts over.
+    pub async fn get_or_insert_with_future<F, Fut>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let result: Result<V, std::convert::Infallible> = self
+            .try_get_or_insert_with_future(key, || async { Ok(f().await) })
+            .await;
+        match result {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns a clone of the value for `key`, loading it with the fallible future returned by
+    /// `f` if it is missing.
+    ///
+    /// If another task is already loading the key, this waits for that task to finish instead of
+    /// calling `f`. If that task fails or is cancelled, this starts over. An error is only
+    /// returned to the task whose loader produced it, and nothing is inserted for the key.
+    pub async fn try_get_or_insert_with_future<F, Fut, E>(&self, key: K, f: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let flight = loop {
+            let in_flight = {
+                let guard = self.map.guard();
+                let current = match self.map.get(&key, &guard) {
+                    Some(current) => current,
+                    None => {
+                        let flight = Arc::new(Flight::default());
+                        let placeholder = Slot::Pending(Arc::clone(&flight));
+                        match self.map.try_insert(key.clone(), placeholder, &guard) {
+                            Ok(_) => break flight,
+                            Err(e) => e.current,
+                        }
+                    }
+                };
+                match current {
+                    Slot::Ready(value) => return Ok(value.clone()),
+                    Slot::Pending(flight) => Arc::clone(flight),
+                }
+            };
+            in_flight.wait().await;
+        };
+
+        // if the loader fails, panics or is cancelled, dropping `leader` removes the placeholder
+        let mut leader = Leader {
+            map: &self.map,
+            key,
+            flight,
+            value: None,
+        };
+        let value = f().await?;
+        leader.value = Some(value.clone());
+        Ok(value)
+    }
+}
+
+/// The task that loads a key, which replaces the key's placeholder when it is dropped.
+struct Leader<'m, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    map: &'m HashMap<K, Slot<V>, S>,
+    key: K,
+    flight: Arc<Flight>,
+    value: Option<V>,
+}
+
+impl<K, V, S> Drop for Leader<'_, K, V, S>
+where
+    K: Sync + Send + Clone + Hash + Ord,
+    V: Sync + Send,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let guard = self.map.guard();
+        let mut lock = self.map.lock_key(&self.key, &guard);
+        // the placeholder may have been replaced through `insert` or `remove` in the meantime
+        if let Some(Slot::Pending(flight)) = lock.get() {
+            if Arc::ptr_eq(flight, &self.flight) {
+                match self.value.take() {
+                    Some(value) => lock.insert(Slot::Ready(value)),
+                    None => lock.remove(),
+                };
+            }
+        }
+        drop(lock);
+        self.flight.complete();
+    }
+}
+
+impl<K, V, S> Debug for SingleFlight<K, V, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleFlight")
+            .field("map", &self.map)
+            .finish()
+    }
+}
+
+impl<V: Debug> Debug for Slot<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Slot::Ready(value) => value.fmt(f),
+            Slot::Pending(_) => f.write_str("<pending>"),
+        }
+    }
+}
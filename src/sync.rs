@@ -0,0 +1,108 @@
+//! The synchronization primitives the map is built from.
+//!
+//! Under `cfg(loom)` these are loom's mock types, so that loom can explore the interleavings of
+//! the map's own synchronization: the atomics in the table, nodes and `TreeBin`s, the bin locks,
+//! and the parking of writers waiting for readers of a `TreeBin`. The bin locks are spin locks on
+//! a loom atomic, since they must be able to be unlocked without their guard. Memory reclamation still goes
+//! through seize; see [`crate::reclaim::collector`] for how it is set up under loom.
+
+#[cfg(not(loom))]
+pub(crate) use parking_lot::{Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::hint::spin_loop;
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, AtomicI64, AtomicIsize, AtomicPtr, AtomicU8};
+#[cfg(not(loom))]
+pub(crate) use std::thread::{current, park, yield_now, Thread};
+
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicI64, AtomicIsize, AtomicPtr, AtomicU8};
+#[cfg(loom)]
+pub(crate) use loom::thread::{current, park, yield_now, Thread};
+#[cfg(loom)]
+pub(crate) use loom_mutex::{Mutex, MutexGuard};
+
+#[cfg(loom)]
+mod loom_mutex {
+    use loom::sync::atomic::{AtomicBool, Ordering};
+    use std::cell::UnsafeCell;
+    use std::fmt;
+    use std::ops::{Deref, DerefMut};
+
+    /// A spin lock with the API of `parking_lot::Mutex`.
+    ///
+    /// Loom's own mutex cannot be unlocked without its guard, which the bin locks of a
+    /// [`KeyLock`](crate::KeyLock) need, so this is built on a loom atomic instead.
+    pub(crate) struct Mutex<T> {
+        locked: AtomicBool,
+        value: UnsafeCell<T>,
+    }
+
+    // safety: the value is only accessed through a guard, and there is only ever one guard
+    unsafe impl<T: Send> Send for Mutex<T> {}
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    pub(crate) struct MutexGuard<'a, T> {
+        mutex: &'a Mutex<T>,
+    }
+
+    impl<T> Mutex<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            loop {
+                if let Some(guard) = self.try_lock() {
+                    return guard;
+                }
+                loom::thread::yield_now();
+            }
+        }
+
+        pub(crate) fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+            self.locked
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .ok()
+                .map(|_| MutexGuard { mutex: self })
+        }
+
+        pub(crate) unsafe fn force_unlock(&self) {
+            self.locked.store(false, Ordering::Release);
+        }
+    }
+
+    impl<T> fmt::Debug for Mutex<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Mutex").finish_non_exhaustive()
+        }
+    }
+
+    impl<T> Deref for MutexGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // safety: we hold the lock
+            unsafe { &*self.mutex.value.get() }
+        }
+    }
+
+    impl<T> DerefMut for MutexGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            // safety: we hold the lock
+            unsafe { &mut *self.mutex.value.get() }
+        }
+    }
+
+    impl<T> Drop for MutexGuard<'_, T> {
+        fn drop(&mut self) {
+            // safety: we hold the lock
+            unsafe { self.mutex.force_unlock() };
+        }
+    }
+}
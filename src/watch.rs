@@ -0,0 +1,237 @@
+use parking_lot::{Condvar, Mutex};
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// The subscriptions of a map, by the hash of the key they are for.
+///
+/// Writers check `subscribers` before anything else, so a map without subscribers pays only for
+/// that load.
+#[derive(Default)]
+pub(crate) struct Watchers {
+    subscribers: AtomicUsize,
+    subscriptions: Mutex<BTreeMap<u64, Vec<Arc<Subscription>>>>,
+}
+
+impl Watchers {
+    fn subscribe(&self, hash: u64) -> Arc<Subscription> {
+        let subscription = Arc::new(Subscription::default());
+        let mut subscriptions = self.subscriptions.lock();
+        subscriptions
+            .entry(hash)
+            .or_default()
+            .push(Arc::clone(&subscription));
+        // this must be visible to writers before the subscriber looks at the map. otherwise, a
+        // writer may miss the subscription even though the subscriber missed its write.
+        self.subscribers.fetch_add(1, Ordering::SeqCst);
+        subscription
+    }
+
+    fn unsubscribe(&self, hash: u64, subscription: &Arc<Subscription>) {
+        let mut subscriptions = self.subscriptions.lock();
+        if let Some(for_hash) = subscriptions.get_mut(&hash) {
+            for_hash.retain(|s| !Arc::ptr_eq(s, subscription));
+            if for_hash.is_empty() {
+                subscriptions.remove(&hash);
+            }
+        }
+        self.subscribers.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Notifies the subscribers of the keys with the given `hash`.
+    ///
+    /// Writers call this after they have released the lock on the bin they changed.
+    pub(crate) fn notify(&self, hash: u64) {
+        if self.subscribers.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let notified = match self.subscriptions.lock().get(&hash) {
+            Some(for_hash) => for_hash.clone(),
+            None => return,
+        };
+        for subscription in notified {
+            subscription.notify();
+        }
+    }
+
+    /// Notifies every subscriber.
+    pub(crate) fn notify_all(&self) {
+        if self.subscribers.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let notified: Vec<_> = self
+            .subscriptions
+            .lock()
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        for subscription in notified {
+            subscription.notify();
+        }
+    }
+}
+
+impl Debug for Watchers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchers")
+            .field("subscribers", &self.subscribers.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct Subscription {
+    state: Mutex<SubscriptionState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct SubscriptionState {
+    /// The number of notifications so far.
+    version: u64,
+    /// The task waiting for the next notification, if any.
+    waker: Option<Waker>,
+}
+
+impl Subscription {
+    fn notify(&self) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.version += 1;
+            self.changed.notify_all();
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// A subscription to changes to a key of a [`HashMap`](crate::HashMap), returned by
+/// [`HashMap::subscribe`](crate::HashMap::subscribe).
+///
+/// The receiver is notified whenever the key is inserted, updated or removed. Notifications
+/// carry no data, so read the map to find out what changed. Several changes may be reported as
+/// one notification, and a notification may occasionally be spurious, for example if another key
+/// with the same hash changed.
+///
+/// Dropping the receiver cancels the subscription.
+pub struct Receiver<'m> {
+    watchers: &'m Watchers,
+    hash: u64,
+    subscription: Arc<Subscription>,
+    seen: u64,
+}
+
+impl<'m> Receiver<'m> {
+    pub(crate) fn new(watchers: &'m Watchers, hash: u64) -> Self {
+        Self {
+            watchers,
+            hash,
+            subscription: watchers.subscribe(hash),
+            seen: 0,
+        }
+    }
+
+    /// Returns `true` if there are notifications that have not been received yet.
+    pub fn has_changed(&self) -> bool {
+        self.subscription.state.lock().version != self.seen
+    }
+
+    /// Receives pending notifications without blocking, and returns `true` if there were any.
+    pub fn try_recv(&mut self) -> bool {
+        let version = self.subscription.state.lock().version;
+        std::mem::replace(&mut self.seen, version) != version
+    }
+
+    /// Blocks the current thread until there is a notification.
+    pub fn recv(&mut self) {
+        let mut state = self.subscription.state.lock();
+        while state.version == self.seen {
+            self.subscription.changed.wait(&mut state);
+        }
+        self.seen = state.version;
+    }
+
+    /// Blocks the current thread until there is a notification, or until `timeout` has passed.
+    ///
+    /// Returns `true` if there was a notification.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.recv_deadline(deadline),
+            None => {
+                self.recv();
+                true
+            }
+        }
+    }
+
+    /// Blocks the current thread until there is a notification, or until `deadline`.
+    ///
+    /// Returns `true` if there was a notification.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> bool {
+        let mut state = self.subscription.state.lock();
+        while state.version == self.seen {
+            if self
+                .subscription
+                .changed
+                .wait_until(&mut state, deadline)
+                .timed_out()
+            {
+                break;
+            }
+        }
+        std::mem::replace(&mut self.seen, state.version) != state.version
+    }
+
+    /// Waits asynchronously until there is a notification.
+    pub fn changed(&mut self) -> Changed<'_, 'm> {
+        Changed { receiver: self }
+    }
+}
+
+impl Drop for Receiver<'_> {
+    fn drop(&mut self) {
+        self.watchers.unsubscribe(self.hash, &self.subscription);
+    }
+}
+
+impl Debug for Receiver<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("hash", &self.hash)
+            .field("has_changed", &self.has_changed())
+            .finish()
+    }
+}
+
+/// A future that resolves once a [`Receiver`] is notified, returned by [`Receiver::changed`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Changed<'r, 'm> {
+    receiver: &'r mut Receiver<'m>,
+}
+
+impl Future for Changed<'_, '_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let receiver = &mut *self.receiver;
+        let mut state = receiver.subscription.state.lock();
+        if state.version != receiver.seen {
+            receiver.seen = state.version;
+            return Poll::Ready(());
+        }
+        match state.waker {
+            Some(ref waker) if waker.will_wake(cx.waker()) => {}
+            _ => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
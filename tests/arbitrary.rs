@@ -0,0 +1,50 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use flurry::{HashMap, HashSet};
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::RandomState;
+
+fn random_bytes(seed: u64) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let len = rng.gen_range(0..1024);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn map_matches_std() {
+    // the same input yields the same entries, with later duplicates of a key winning
+    for seed in 0..100 {
+        let bytes = random_bytes(seed);
+        let map =
+            HashMap::<u8, u16, RandomState>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        let expected = std::collections::HashMap::<u8, u16, RandomState>::arbitrary(
+            &mut Unstructured::new(&bytes),
+        )
+        .unwrap();
+        assert_eq!(map, HashMap::from_std(expected));
+
+        let map = HashMap::<u8, u16, RandomState>::arbitrary_take_rest(Unstructured::new(&bytes))
+            .unwrap();
+        let expected = std::collections::HashMap::<u8, u16, RandomState>::arbitrary_take_rest(
+            Unstructured::new(&bytes),
+        )
+        .unwrap();
+        assert_eq!(map, HashMap::from_std(expected));
+    }
+}
+
+#[test]
+fn set_matches_std() {
+    for seed in 0..100 {
+        let bytes = random_bytes(seed);
+        let set = HashSet::<u16>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        let expected =
+            std::collections::HashSet::<u16>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        assert_eq!(set.len(), expected.len());
+        let guard = set.guard();
+        for value in &expected {
+            assert!(set.contains(value, &guard));
+        }
+    }
+}
@@ -482,6 +482,194 @@ fn clone_map_filled() {
     assert_ne!(&map, &cloned_map);
 }
 
+#[test]
+fn clone_map_resized() {
+    let map = HashMap::<usize, usize>::new();
+    {
+        let guard = map.guard();
+        for i in 0..1000 {
+            map.insert(i, i + 1, &guard);
+        }
+        for i in (0..1000).step_by(3) {
+            map.remove(&i, &guard);
+        }
+    }
+    let cloned_map = map.clone();
+    assert_eq!(map.len(), cloned_map.len());
+    assert_eq!(&map, &cloned_map);
+
+    // the clone keeps growing like any other map
+    let guard = cloned_map.guard();
+    for i in 1000..2000 {
+        cloned_map.insert(i, i + 1, &guard);
+    }
+    assert_eq!(cloned_map.len(), map.len() + 1000);
+    assert_eq!(cloned_map.get(&1999, &guard), Some(&2000));
+}
+
+#[test]
+fn from_std() {
+    let std_map: std::collections::HashMap<_, _> = (0..1000).map(|i| (i, i.to_string())).collect();
+    let map = HashMap::from(std_map.clone());
+    assert_eq!(map.len(), 1000);
+    let guard = map.guard();
+    for (k, v) in &std_map {
+        assert_eq!(map.get(k, &guard), Some(v));
+    }
+
+    // the map keeps working like any other map
+    assert_eq!(
+        map.insert(0, String::from("zero"), &guard),
+        Some(&String::from("0"))
+    );
+    map.insert(1000, String::from("1000"), &guard);
+    assert_eq!(map.len(), 1001);
+}
+
+#[test]
+fn from_std_empty() {
+    let map = HashMap::from_std(std::collections::HashMap::<usize, usize>::new());
+    assert!(map.is_empty());
+    map.pin().insert(1, 1);
+    assert_eq!(map.pin().get(&1), Some(&1));
+}
+
+#[test]
+fn into_std() {
+    let map = HashMap::<usize, String>::new();
+    assert!(map.clone().into_std().is_empty());
+    {
+        let guard = map.guard();
+        for i in 0..1000 {
+            map.insert(i, i.to_string(), &guard);
+        }
+    }
+    let std_map = map.into_std();
+    assert_eq!(std_map.len(), 1000);
+    assert!((0..1000).all(|i| std_map[&i] == i.to_string()));
+}
+
+#[test]
+fn into_std_drops_each_value_once() {
+    let dropped = Arc::new(0);
+    let map = HashMap::new();
+    for i in 0..100 {
+        map.pin().insert(i, Arc::clone(&dropped));
+    }
+    assert_eq!(Arc::strong_count(&dropped), 101);
+    let std_map = map.into_std();
+    assert_eq!(Arc::strong_count(&dropped), 101);
+    drop(std_map);
+    assert_eq!(Arc::strong_count(&dropped), 1);
+}
+
+#[test]
+fn with_hash() {
+    let map = HashMap::<String, usize>::new();
+    let guard = map.guard();
+    for i in 0..100 {
+        let key = i.to_string();
+        let hash = map.hash_key(&key);
+        assert_eq!(hash, map.hash_key(key.as_str()));
+        assert_eq!(map.insert_with_hash(hash, key, i, &guard), None);
+    }
+    assert_eq!(map.len(), 100);
+
+    for i in 0..100 {
+        let key = i.to_string();
+        let hash = map.hash_key(key.as_str());
+        // entries inserted with a hash can be found without one, and vice versa
+        assert_eq!(map.get(key.as_str(), &guard), Some(&i));
+        assert_eq!(map.get_with_hash(hash, key.as_str(), &guard), Some(&i));
+        assert_eq!(
+            map.insert_with_hash(hash, key.clone(), i + 1, &guard),
+            Some(&i)
+        );
+    }
+
+    for i in 0..50 {
+        let key = i.to_string();
+        let hash = map.hash_key(&key);
+        assert_eq!(
+            map.remove_with_hash(hash, key.as_str(), &guard),
+            Some(&(i + 1))
+        );
+        assert_eq!(map.remove_with_hash(hash, key.as_str(), &guard), None);
+        assert_eq!(map.get_with_hash(hash, key.as_str(), &guard), None);
+    }
+    assert_eq!(map.len(), 50);
+}
+
+#[test]
+fn find_by_hash() {
+    let map = HashMap::<(String, usize), usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.find_by_hash(0, |_| true, &guard), None);
+    for i in 0..100 {
+        map.insert((i.to_string(), i), i, &guard);
+    }
+
+    for i in 0..100 {
+        let s = i.to_string();
+        let hash = map.hash_key(&(s.as_str(), i));
+        let (key, value) = map
+            .find_by_hash(hash, |(k, j)| k == &s && *j == i, &guard)
+            .unwrap();
+        assert_eq!(key, &(s.clone(), i));
+        assert_eq!(value, &i);
+
+        // a matching predicate does not help if the hash is for another key
+        assert_eq!(map.find_by_hash(hash, |(_, j)| *j != i, &guard), None);
+    }
+    let hash = map.hash_key(&("100", 100));
+    assert_eq!(map.find_by_hash(hash, |_| true, &guard), None);
+}
+
+/// A borrowed form of `(String, usize)`.
+#[derive(Hash)]
+struct Pair<'a>(&'a str, usize);
+
+impl flurry::Equivalent<(String, usize)> for Pair<'_> {
+    fn equivalent(&self, key: &(String, usize)) -> bool {
+        self.0 == key.0 && self.1 == key.1
+    }
+}
+
+impl flurry::Comparable<(String, usize)> for Pair<'_> {
+    fn compare(&self, key: &(String, usize)) -> std::cmp::Ordering {
+        (self.0, self.1).cmp(&(key.0.as_str(), key.1))
+    }
+}
+
+#[test]
+fn equivalent_lookups() {
+    let map = HashMap::<(String, usize), usize>::new();
+    let guard = map.guard();
+    for i in 0..100 {
+        map.insert((i.to_string(), i), i, &guard);
+    }
+
+    for i in 0..100 {
+        let s = i.to_string();
+        assert!(map.contains_key(&Pair(&s, i), &guard));
+        assert_eq!(map.get(&Pair(&s, i), &guard), Some(&i));
+        assert_eq!(map.get(&Pair(&s, i + 1), &guard), None);
+        assert_eq!(
+            map.get_key_value(&Pair(&s, i), &guard),
+            Some((&(s.clone(), i), &i))
+        );
+    }
+
+    assert_eq!(map.remove(&Pair("7", 7), &guard), Some(&7));
+    assert_eq!(map.remove(&Pair("7", 7), &guard), None);
+    assert_eq!(
+        map.compute_if_present(&Pair("8", 8), |_, v| Some(v + 1), &guard),
+        Some(&9)
+    );
+    assert_eq!(map.pin()[&Pair("9", 9)], 9);
+    assert_eq!(map.len(), 99);
+}
+
 #[test]
 fn default() {
     let map: HashMap<usize, usize> = Default::default();
@@ -619,3 +807,646 @@ fn retain_force_some() {
     assert_eq!(map.len(), 5);
     assert_eq!(map, expected_map);
 }
+
+#[test]
+fn owned_get_insert_remove() {
+    let map = HashMap::<usize, String>::new();
+    assert_eq!(map.insert_owned(42, String::from("a")), None);
+    assert_eq!(map.get_cloned(&42), Some(String::from("a")));
+    assert_eq!(
+        map.insert_owned(42, String::from("b")),
+        Some(String::from("a"))
+    );
+    assert_eq!(map.with_value(&42, |v| v.len()), Some(1));
+    assert_eq!(map.remove_owned(&42), Some(String::from("b")));
+    assert_eq!(map.get_cloned(&42), None);
+    assert_eq!(map.with_value(&42, |v| v.len()), None);
+    assert_eq!(map.remove_owned(&42), None);
+}
+
+#[test]
+fn owned_inside_guard() {
+    // the owned methods must also work while the thread is already pinned
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    assert_eq!(map.get_cloned(&1), Some(1));
+    assert_eq!(map.insert_owned(1, 2), Some(1));
+    assert_eq!(map.get(&1, &guard), Some(&2));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_owned() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    let key = t * 1000 + i;
+                    assert_eq!(map.insert_owned(key, i), None);
+                    assert_eq!(map.get_cloned(&key), Some(i));
+                }
+                for i in 0..1000 {
+                    let key = t * 1000 + i;
+                    assert_eq!(map.remove_owned(&key), Some(i));
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert!(map.is_empty());
+}
+
+#[test]
+fn scope() {
+    let map = HashMap::<usize, usize>::new();
+    map.scope(|s| {
+        assert_eq!(s.insert(1, 1), None);
+        assert_eq!(s.insert(1, 2), Some(&1));
+        assert_eq!(s.try_insert(2, 2), Ok(&2));
+        assert!(s.contains_key(&2));
+        assert_eq!(s.compute_if_present(&2, |_, v| Some(v + 1)), Some(&3));
+        assert_eq!(s.get_key_value(&2), Some((&2, &3)));
+        assert_eq!(s.len(), 2);
+        s.refresh();
+        assert_eq!(s.remove(&1), Some(&2));
+        assert_eq!(s.remove_entry(&2), Some((&2, &3)));
+        assert!(s.is_empty());
+    });
+
+    map.pin().insert(3, 3);
+    let keys: Vec<_> = map.scope(|s| s.keys().copied().collect());
+    assert_eq!(keys, vec![3]);
+}
+
+#[test]
+fn scope_guard_is_usable_with_map() {
+    let map = HashMap::<usize, usize>::new();
+    map.scope(|s| {
+        s.insert(1, 1);
+        assert_eq!(map.get(&1, s.guard()), Some(&1));
+    });
+}
+
+#[test]
+fn scan_empty() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.scan(0, 10, &guard), (0, vec![]));
+}
+
+#[test]
+fn scan_in_batches() {
+    let map: HashMap<usize, usize> = (0..1000).map(|i| (i, i)).collect();
+    let mut keys = Vec::new();
+    let mut cursor = 0;
+    let mut calls = 0;
+    loop {
+        let (next, batch) = {
+            let map = map.pin();
+            let (next, batch) = map.scan(cursor, 64);
+            (
+                next,
+                batch.into_iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>(),
+            )
+        };
+        calls += 1;
+        keys.extend(batch.into_iter().map(|(k, v)| {
+            assert_eq!(k, v);
+            k
+        }));
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+    assert!(calls > 1);
+    keys.sort_unstable();
+    assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn scan_everything_at_once() {
+    let map: HashMap<usize, usize> = (0..1000).map(|i| (i, i)).collect();
+    let guard = map.guard();
+    let (next, batch) = map.scan(0, usize::MAX, &guard);
+    assert_eq!(next, 0);
+    let mut keys: Vec<_> = batch.into_iter().map(|(&k, _)| k).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn scan_across_resizes() {
+    let map = HashMap::<usize, usize>::new();
+    for i in 0..32 {
+        map.pin().insert(i, i);
+    }
+
+    let mut keys = Vec::new();
+    let mut cursor = 0;
+    let mut next_key = 32;
+    loop {
+        let guard = map.guard();
+        let (next, batch) = map.scan(cursor, 4, &guard);
+        keys.extend(batch.into_iter().map(|(&k, _)| k));
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+        drop(guard);
+
+        if next_key >= 1024 {
+            continue;
+        }
+        // grow the table in between calls
+        for _ in 0..64 {
+            map.pin().insert(next_key, next_key);
+            next_key += 1;
+        }
+    }
+
+    // every key present for the full walk is returned exactly once, since the table only grew
+    for i in 0..32 {
+        assert_eq!(keys.iter().filter(|&&k| k == i).count(), 1, "key {}", i);
+    }
+}
+
+#[test]
+fn get_many() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.get_many(&[&1, &2], &guard), vec![None, None]);
+    assert_eq!(map.get_many_array([&1, &2], &guard), [None, None]);
+
+    for i in 0..1000 {
+        map.insert(i, i * 2, &guard);
+    }
+    let keys: Vec<_> = (0..2000).step_by(7).collect();
+    let refs: Vec<_> = keys.iter().collect();
+    let expected: Vec<_> = keys.iter().map(|k| map.get(k, &guard)).collect();
+    assert_eq!(map.get_many(&refs, &guard), expected);
+    assert_eq!(
+        map.get_many_array([&0, &999, &1000], &guard),
+        [Some(&0), Some(&1998), None]
+    );
+    assert_eq!(map.get_many::<usize>(&[], &guard), vec![]);
+}
+
+#[test]
+fn get_many_borrowed() {
+    let map = HashMap::<String, usize>::new();
+    let guard = map.guard();
+    map.insert(String::from("a"), 1, &guard);
+    map.insert(String::from("b"), 2, &guard);
+    assert_eq!(
+        map.get_many(&["a", "c", "b"], &guard),
+        vec![Some(&1), None, Some(&2)]
+    );
+}
+
+#[test]
+fn insert_many() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    assert_eq!(map.insert_many(Vec::new(), &guard), vec![]);
+
+    let replaced = map.insert_many((0..1000).map(|i| (i, i)), &guard);
+    assert!(replaced.iter().all(Option::is_none));
+    assert_eq!(map.len(), 1000);
+
+    // overwrite half of the keys, and repeat some keys within the batch
+    let replaced = map.insert_many(
+        (500..1500)
+            .map(|i| (i, i + 1))
+            .chain(std::iter::once((1499, 0))),
+        &guard,
+    );
+    assert_eq!(replaced.len(), 1001);
+    for (i, old) in (500..1500).zip(&replaced) {
+        if i < 1000 {
+            assert_eq!(*old, Some(&i));
+        } else {
+            assert_eq!(*old, None);
+        }
+    }
+    assert_eq!(replaced[1000], Some(&1500));
+    assert_eq!(map.len(), 1500);
+
+    for i in 0..1499 {
+        let expected = if i < 500 { i } else { i + 1 };
+        assert_eq!(map.get(&i, &guard), Some(&expected));
+    }
+    assert_eq!(map.get(&1499, &guard), Some(&0));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_insert_many() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                // threads overlap in half of their keys
+                for batch in 0..10 {
+                    let start = t * 500 + batch * 100;
+                    map.insert_many((start..start + 100).map(|i| (i, t)), &guard);
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert_eq!(map.len(), 2500);
+    let guard = map.guard();
+    for i in 0..2500 {
+        assert!(map.get(&i, &guard).is_some());
+    }
+}
+
+#[test]
+fn transaction() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    map.insert(1, 10, &guard);
+    map.insert(2, 20, &guard);
+
+    let sum = map.transaction(
+        &[1, 2, 3],
+        |entries| {
+            assert_eq!(entries[0].key(), &1);
+            assert_eq!(entries[0].get(), Some(&10));
+            assert_eq!(entries[2].get(), None);
+            let sum = entries.iter().filter_map(|e| e.get()).sum::<usize>();
+            entries[0].remove();
+            entries[1].insert(21);
+            entries[2].insert(30);
+            assert_eq!(entries[0].get(), None);
+            assert_eq!(entries[1].get(), Some(&21));
+            Ok::<_, ()>(sum)
+        },
+        &guard,
+    );
+    assert_eq!(sum, Ok(30));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&1, &guard), None);
+    assert_eq!(map.get(&2, &guard), Some(&21));
+    assert_eq!(map.get(&3, &guard), Some(&30));
+
+    // untouched entries are left alone
+    assert_eq!(
+        map.pin().transaction(&[2, 4], |entries| {
+            assert_eq!(entries[1].get(), None);
+            Ok::<_, ()>(())
+        }),
+        Ok(())
+    );
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(&2, &guard), Some(&21));
+
+    assert_eq!(map.transaction(&[], |_| Ok::<_, ()>(1), &guard), Ok(1));
+}
+
+#[test]
+fn transaction_abort() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    map.insert(1, 10, &guard);
+
+    let result = map.transaction(
+        &[1, 2],
+        |entries| {
+            entries[0].insert(11);
+            entries[1].insert(20);
+            Err("abort")
+        },
+        &guard,
+    );
+    assert_eq!(result, Err::<(), _>("abort"));
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get(&1, &guard), Some(&10));
+    assert_eq!(map.get(&2, &guard), None);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.transaction(
+            &[1, 2],
+            |entries| -> Result<(), ()> {
+                entries[0].remove();
+                panic!("oops");
+            },
+            &guard,
+        )
+    }));
+    assert!(result.is_err());
+    assert_eq!(map.get(&1, &guard), Some(&10));
+
+    // the bins are unlocked (and no longer reserved) after an aborted transaction
+    map.insert(2, 20, &guard);
+    map.insert(1, 11, &guard);
+    assert_eq!(map.get(&2, &guard), Some(&20));
+    assert_eq!(map.get(&1, &guard), Some(&11));
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn transaction_panic_while_committing() {
+    /// A key that panics when the key 99 is cloned.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+    struct Key(usize);
+
+    impl Clone for Key {
+        fn clone(&self) -> Self {
+            assert_ne!(self.0, 99, "cannot clone key 99");
+            Key(self.0)
+        }
+    }
+
+    // keys hash to themselves, so the bin of key 99 is committed after the others
+    let map = HashMap::<Key, usize, Keyed>::default();
+    let guard = map.guard();
+    map.insert(Key(0), 0, &guard);
+
+    // the new keys are cloned before any of the changes are made
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.transaction(
+            &[Key(0), Key(1), Key(99)],
+            |entries| {
+                entries[0].remove();
+                entries[1].insert(1);
+                entries[2].insert(99);
+                Ok::<_, ()>(())
+            },
+            &guard,
+        )
+    }));
+    assert!(result.is_err());
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get(&Key(0), &guard), Some(&0));
+    assert_eq!(map.get(&Key(1), &guard), None);
+
+    // the bins are unlocked again
+    map.insert(Key(1), 1, &guard);
+    assert_eq!(map.remove(&Key(0), &guard), Some(&0));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "transaction keys must be distinct")]
+fn transaction_duplicate_keys() {
+    let map = HashMap::<usize, usize>::new();
+    let _ = map.pin().transaction(&[1, 2, 1], |_| Ok::<_, ()>(()));
+}
+
+#[test]
+fn transaction_one_bucket() {
+    let map = HashMap::<usize, usize, _>::with_hasher(ZeroHashBuilder);
+    let guard = map.guard();
+    for i in 0..10 {
+        map.insert(i, i, &guard);
+    }
+
+    let keys: Vec<_> = (5..15).collect();
+    map.transaction(
+        &keys,
+        |entries| {
+            for entry in entries.iter_mut() {
+                match entry.get() {
+                    Some(&v) => entry.insert(v * 10),
+                    None => entry.insert(*entry.key()),
+                }
+            }
+            Ok::<_, ()>(())
+        },
+        &guard,
+    )
+    .unwrap();
+    assert_eq!(map.len(), 15);
+    for i in 0..15 {
+        let expected = if (5..10).contains(&i) { i * 10 } else { i };
+        assert_eq!(map.get(&i, &guard), Some(&expected));
+    }
+}
+
+#[test]
+fn concurrent_transactions() {
+    const ACCOUNTS: usize = 16;
+    const BALANCE: usize = 1000;
+
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    for i in 0..ACCOUNTS {
+        map.pin().insert(i, BALANCE);
+    }
+
+    let movers: Vec<_> = (0..4)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let guard = map.guard();
+                for i in 0..2000 {
+                    let from = (t * 7 + i * 3) % ACCOUNTS;
+                    let to = (from + 1 + i % (ACCOUNTS - 1)) % ACCOUNTS;
+                    let _ = map.transaction(
+                        &[from, to],
+                        |entries| {
+                            let from = *entries[0].get().unwrap();
+                            let to = *entries[1].get().unwrap();
+                            let amount = i % 50;
+                            if from < amount {
+                                return Err(());
+                            }
+                            entries[0].insert(from - amount);
+                            entries[1].insert(to + amount);
+                            Ok(())
+                        },
+                        &guard,
+                    );
+                }
+            })
+        })
+        .collect();
+    // grow the map underneath the transactions to force resizes
+    let grower = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            let guard = map.guard();
+            for i in ACCOUNTS..ACCOUNTS + 5000 {
+                map.insert(i, 0, &guard);
+            }
+        })
+    };
+    for t in movers {
+        t.join().unwrap();
+    }
+    grower.join().unwrap();
+
+    let guard = map.guard();
+    let total: usize = (0..ACCOUNTS).map(|i| *map.get(&i, &guard).unwrap()).sum();
+    assert_eq!(total, ACCOUNTS * BALANCE);
+    assert_eq!(map.len(), ACCOUNTS + 5000);
+}
+
+#[test]
+fn lock_key() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+
+    // locking an absent key without inserting it leaves the map unchanged
+    let lock = map.lock_key(&1, &guard);
+    assert_eq!(lock.key(), &1);
+    assert_eq!(lock.get(), None);
+    drop(lock);
+    assert!(map.is_empty());
+    assert_eq!(map.insert(2, 2, &guard), None);
+
+    let mut lock = map.lock_key(&1, &guard);
+    assert_eq!(lock.insert(10), None);
+    assert_eq!(lock.get(), Some(&10));
+    assert_eq!(lock.insert(11), Some(&10));
+    drop(lock);
+    assert_eq!(map.get(&1, &guard), Some(&11));
+    assert_eq!(map.len(), 2);
+
+    assert_eq!(map.pin().lock_key(&1).remove(), Some(&11));
+    assert_eq!(map.get(&1, &guard), None);
+    assert_eq!(map.get(&2, &guard), Some(&2));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn lock_key_blocks_writers() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let guard = map.guard();
+    let mut lock = map.lock_key(&1, &guard);
+
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            map.pin().insert(1, 2);
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    // the writer is waiting for the lock, but lookups are not
+    assert_eq!(map.get(&1, &guard), None);
+    lock.insert(1);
+    assert_eq!(map.get(&1, &guard), Some(&1));
+    drop(lock);
+
+    writer.join().unwrap();
+    assert_eq!(map.get(&1, &guard), Some(&2));
+}
+
+/// Uses the keys as their hashes, so that the tests can tell which keys share a bin.
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl std::hash::Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unimplemented!("the tests only use usize keys")
+    }
+
+    fn write_usize(&mut self, key: usize) {
+        self.0 = key as u64;
+    }
+}
+
+type Keyed = std::hash::BuildHasherDefault<KeyHasher>;
+
+#[test]
+fn lock_key_across_resize() {
+    let map = Arc::new(HashMap::<usize, usize, Keyed>::default());
+    let guard = map.guard();
+    let mut lock = map.lock_key(&0, &guard);
+
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            map.pin().insert(0, 2);
+        })
+    };
+    // grow the map while the lock is held. the odd keys never share a bin with 0, so the
+    // resizes do not wait for the lock, but move it into the new tables
+    let grower = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            let guard = map.guard();
+            for i in (1..10000).step_by(2) {
+                map.insert(i, i, &guard);
+            }
+        })
+    };
+    grower.join().unwrap();
+    assert_eq!(map.len(), 5000);
+
+    // the writer still waits for the lock in the new table
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(!writer.is_finished());
+    assert_eq!(lock.get(), None);
+    lock.insert(1);
+    assert_eq!(map.get(&0, &guard), Some(&1));
+    drop(lock);
+
+    writer.join().unwrap();
+    assert_eq!(map.get(&0, &guard), Some(&2));
+    for i in (1..10000).step_by(2) {
+        assert_eq!(map.get(&i, &guard), Some(&i));
+    }
+}
+
+#[test]
+fn lock_key_holder_grows_map() {
+    let map = HashMap::<usize, usize, Keyed>::default();
+    let guard = map.guard();
+    map.insert(0, 0, &guard);
+    let mut lock = map.lock_key(&0, &guard);
+
+    // the holder of the lock can start resizes, since they do not wait for the lock
+    for i in (1..10000).step_by(2) {
+        map.insert(i, i, &guard);
+        if i % 1000 == 1 {
+            lock.insert(i);
+        }
+    }
+    assert_eq!(lock.remove(), Some(&9001));
+    assert_eq!(lock.insert(0), None);
+    drop(lock);
+
+    assert_eq!(map.len(), 5001);
+    assert_eq!(map.get(&0, &guard), Some(&0));
+    map.insert(0, 1, &guard);
+    assert_eq!(map.get(&0, &guard), Some(&1));
+}
+
+#[test]
+fn forgotten_key_lock() {
+    // keys hash to themselves, so 1 and 7 are in different bins.
+    // a forgotten lock leaves its bin locked, and a reservation behind if the key is absent
+    let map = HashMap::<usize, usize, Keyed>::default();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    std::mem::forget(map.lock_key(&1, &guard));
+    std::mem::forget(map.lock_key(&7, &guard));
+    drop(guard);
+    drop(map);
+
+    let map = HashMap::<usize, usize, Keyed>::default();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    std::mem::forget(map.lock_key(&7, &guard));
+    drop(guard);
+    assert_eq!(map.into_std().into_iter().collect::<Vec<_>>(), [(1, 1)]);
+}
@@ -0,0 +1,161 @@
+use flurry::feed::{ChangeEvent, ChangeKind};
+use flurry::HashMap;
+use rand::{thread_rng, Rng};
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<ChangeEvent<usize, usize>>>>;
+
+fn recorded_map() -> (HashMap<usize, usize>, Log) {
+    let log = Log::default();
+    let map = HashMap::new().with_change_sink({
+        let log = Arc::clone(&log);
+        move |event: ChangeEvent<&usize, &usize>| log.lock().unwrap().push(event.cloned())
+    });
+    (map, log)
+}
+
+fn take_sorted(log: &Log) -> Vec<ChangeEvent<usize, usize>> {
+    let mut events = std::mem::take(&mut *log.lock().unwrap());
+    events.sort_by_key(|event| event.seq);
+    for (i, event) in events.iter().enumerate() {
+        assert_eq!(event.seq, i as u64, "sequence numbers have gaps");
+    }
+    events
+}
+
+#[test]
+fn records_changes() {
+    let (map, log) = recorded_map();
+    let guard = map.guard();
+    map.insert(1, 1, &guard);
+    map.insert(1, 2, &guard);
+    assert!(map.try_insert(1, 3, &guard).is_err());
+    map.compute_if_present(&1, |_, v| Some(v + 1), &guard);
+    map.remove(&1, &guard);
+    // removing an absent key does not change anything
+    map.remove(&1, &guard);
+    map.insert_many([(2, 2)], &guard);
+    map.insert(3, 3, &guard);
+    map.transaction(
+        &[2, 4],
+        |entries| {
+            entries[0].remove();
+            entries[1].insert(4);
+            Ok::<_, ()>(())
+        },
+        &guard,
+    )
+    .unwrap();
+    map.lock_key(&3, &guard).insert(30);
+    map.clear(&guard);
+
+    let mut events: Vec<_> = take_sorted(&log)
+        .into_iter()
+        .map(|e| (e.kind, e.key, e.old, e.new))
+        .collect();
+    // the changes of a transaction, and of a clear, are sequenced in the order of their bins
+    events[6..8].sort_by_key(|&(_, key, _, _)| key);
+    events[9..].sort_by_key(|&(_, key, _, _)| key);
+    assert_eq!(
+        events,
+        [
+            (ChangeKind::Insert, 1, None, Some(1)),
+            (ChangeKind::Replace, 1, Some(1), Some(2)),
+            (ChangeKind::Replace, 1, Some(2), Some(3)),
+            (ChangeKind::Remove, 1, Some(3), None),
+            (ChangeKind::Insert, 2, None, Some(2)),
+            (ChangeKind::Insert, 3, None, Some(3)),
+            (ChangeKind::Remove, 2, Some(2), None),
+            (ChangeKind::Insert, 4, None, Some(4)),
+            (ChangeKind::Replace, 3, Some(3), Some(30)),
+            (ChangeKind::Clear, 3, Some(30), None),
+            (ChangeKind::Clear, 4, Some(4), None),
+        ]
+    );
+}
+
+#[test]
+fn records_key_lock_changes_right_away() {
+    let (map, log) = recorded_map();
+    let guard = map.guard();
+    let mut lock = map.lock_key(&1, &guard);
+    lock.insert(1);
+    lock.insert(2);
+    // the changes are recorded while the key is still locked, even if the lock is never released
+    std::mem::forget(lock);
+
+    let events: Vec<_> = take_sorted(&log)
+        .into_iter()
+        .map(|e| (e.kind, e.key, e.old, e.new))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            (ChangeKind::Insert, 1, None, Some(1)),
+            (ChangeKind::Replace, 1, Some(1), Some(2)),
+        ]
+    );
+}
+
+#[test]
+fn replays_concurrent_changes() {
+    const THREADS: usize = 8;
+    const OPS: usize = 2000;
+    const KEYS: usize = 64;
+
+    let (map, log) = recorded_map();
+    let map = Arc::new(map);
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let mut rng = thread_rng();
+                let guard = map.guard();
+                for _ in 0..OPS {
+                    let key = rng.gen_range(0..KEYS);
+                    let value = rng.gen();
+                    match rng.gen_range(0..100) {
+                        0..=39 => {
+                            map.insert(key, value, &guard);
+                        }
+                        40..=59 => {
+                            map.remove(&key, &guard);
+                        }
+                        60..=69 => {
+                            map.compute_if_present(&key, |_, v| Some(v ^ value), &guard);
+                        }
+                        70..=79 => {
+                            map.insert_many((key..key + 4).map(|k| (k, value)), &guard);
+                        }
+                        80..=89 => {
+                            let keys = [key, (key + 7) % KEYS];
+                            let _ = map.transaction(
+                                &keys,
+                                |entries| {
+                                    entries[0].remove();
+                                    entries[1].insert(value);
+                                    Ok::<_, ()>(())
+                                },
+                                &guard,
+                            );
+                        }
+                        90..=98 => {
+                            map.lock_key(&key, &guard).insert(value);
+                        }
+                        _ => map.clear(&guard),
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let follower = HashMap::new();
+    let guard = follower.guard();
+    for event in take_sorted(&log) {
+        follower.apply_event(event, &guard);
+    }
+    assert_eq!(*map, follower);
+}
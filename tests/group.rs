@@ -0,0 +1,116 @@
+use flurry::*;
+use std::sync::Arc;
+
+#[test]
+fn shared_guard() {
+    let group = MapGroup::new();
+    let map = group.map::<usize, usize>();
+    let set = group.set::<usize>();
+
+    let guard = group.guard();
+    map.insert(1, 2, &guard);
+    set.insert(1, &guard);
+    assert_eq!(map.get(&1, &guard), Some(&2));
+    assert!(set.contains(&1, &guard));
+}
+
+#[test]
+fn member_guard_works_for_other_members() {
+    let group = MapGroup::new();
+    let a = group.map::<usize, usize>();
+    let b = group
+        .clone()
+        .map_with_capacity_and_hasher(32, DefaultHashBuilder::default());
+
+    let guard = a.guard();
+    a.insert(1, 1, &guard);
+    b.insert(2, 2, &guard);
+    assert_eq!(b.get(&2, &guard), Some(&2));
+    assert_eq!(a.get(&1, &b.guard()), Some(&1));
+}
+
+#[test]
+#[should_panic]
+fn foreign_guard_still_panics() {
+    let group = MapGroup::new();
+    let map = group.map::<usize, usize>();
+    let other = HashMap::<usize, usize>::new();
+    map.insert(1, 1, &other.guard());
+}
+
+#[test]
+fn set_with_collector() {
+    let set = HashSet::<usize>::new().with_collector(seize::Collector::new());
+    let guard = set.guard();
+    set.insert(1, &guard);
+    assert!(set.contains(&1, &guard));
+}
+
+#[test]
+fn member_outlives_group() {
+    let map = {
+        let group = MapGroup::new();
+        group.map::<usize, String>()
+    };
+    let guard = map.guard();
+    map.insert(1, String::from("a"), &guard);
+    map.insert(1, String::from("b"), &guard);
+    assert_eq!(map.get(&1, &guard).map(String::as_str), Some("b"));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn concurrent_members() {
+    let group = MapGroup::new();
+    let map = Arc::new(group.map::<usize, usize>());
+    let set = Arc::new(group.set::<usize>());
+
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let group = group.clone();
+            let map = Arc::clone(&map);
+            let set = Arc::clone(&set);
+            std::thread::spawn(move || {
+                let guard = group.guard();
+                for i in 0..1000 {
+                    let key = t * 1000 + i;
+                    map.insert(key, i, &guard);
+                    set.insert(key, &guard);
+                    assert!(map.remove(&key, &guard).is_some());
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert!(map.is_empty());
+    assert_eq!(set.len(), 4000);
+}
+
+#[test]
+fn clone_stays_in_group() {
+    let group = MapGroup::new();
+    let map = group.map::<usize, usize>();
+    let set = group.set::<usize>();
+    let guard = group.guard();
+    map.insert(1, 1, &guard);
+    set.insert(1, &guard);
+
+    let map_clone = map.clone();
+    let set_clone = set.clone();
+    map_clone.insert(2, 2, &guard);
+    set_clone.insert(2, &guard);
+    assert_eq!(map_clone.get(&1, &guard), Some(&1));
+    assert!(set_clone.contains(&1, &guard));
+    assert_eq!(map.get(&2, &guard), None);
+}
+
+#[test]
+#[should_panic]
+fn clone_of_ungrouped_map_has_its_own_collector() {
+    let map = HashMap::<usize, usize>::new();
+    let clone = map.clone();
+    clone.insert(1, 1, &map.guard());
+}
@@ -0,0 +1,261 @@
+#![cfg(loom)]
+//! Model checks of the map's synchronization with [loom](https://docs.rs/loom).
+//!
+//! Run them with
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --test loom --release
+//! ```
+//!
+//! By default the models are explored with at most two preemptions per execution, which finds
+//! most bugs in a reasonable amount of time. Set `LOOM_MAX_PREEMPTIONS` to explore more.
+
+use flurry::HashMap;
+use loom::thread;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+/// Uses the keys as their hashes, so that the models are deterministic.
+#[derive(Default)]
+struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, key: u64) {
+        self.0 = key;
+    }
+}
+
+type Keyed = BuildHasherDefault<KeyHasher>;
+
+/// Places every key in bin 0 of a table with 64 bins, the smallest table whose bins are turned
+/// into trees. When that table grows, the even keys stay in bin 0 and the odd keys move to bin 64.
+#[derive(Default)]
+struct BinZeroHasher(u64);
+
+impl Hasher for BinZeroHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, key: u64) {
+        self.0 = key * 64;
+    }
+}
+
+type BinZero = BuildHasherDefault<BinZeroHasher>;
+
+fn model<F>(f: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let mut builder = loom::model::Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(2);
+    }
+    // filling a tree bin or moving a table touches many atomics before any thread is spawned
+    builder.max_branches = 100_000;
+    builder.check(f);
+}
+
+/// A map with two bins and one entry, which grows with the next insert.
+fn small() -> Arc<HashMap<u64, u64, Keyed>> {
+    let map = HashMap::with_capacity_and_hasher(1, Keyed::default());
+    map.pin().insert(0, 0);
+    Arc::new(map)
+}
+
+/// A map with a single bin of `n` colliding keys, which is a tree for `n > 8`.
+fn colliding(n: u64) -> Arc<HashMap<u64, u64, BinZero>> {
+    let map = HashMap::with_capacity_and_hasher(32, BinZero::default());
+    {
+        let guard = map.guard();
+        for i in 0..n {
+            map.insert(i, i, &guard);
+        }
+    }
+    Arc::new(map)
+}
+
+fn assert_contains<S>(map: &HashMap<u64, u64, S>, keys: impl IntoIterator<Item = u64>)
+where
+    S: std::hash::BuildHasher,
+{
+    let guard = map.guard();
+    let mut len = 0;
+    for key in keys {
+        assert_eq!(map.get(&key, &guard), Some(&key), "key {} is missing", key);
+        len += 1;
+    }
+    assert_eq!(map.len(), len);
+}
+
+/// Runs `a` and `b` on two threads of the model.
+fn concurrently<T, A, B>(map: &Arc<T>, a: A, b: B)
+where
+    T: Send + Sync + 'static,
+    A: FnOnce(&T) + Send + 'static,
+    B: FnOnce(&T) + Send + 'static,
+{
+    let a = {
+        let map = Arc::clone(map);
+        thread::spawn(move || a(&map))
+    };
+    let b = {
+        let map = Arc::clone(map);
+        thread::spawn(move || b(&map))
+    };
+    a.join().unwrap();
+    b.join().unwrap();
+}
+
+#[test]
+fn get_during_transfer() {
+    model(|| {
+        let map = small();
+        concurrently(
+            &map,
+            |map| {
+                map.pin().insert(1, 1);
+            },
+            // the entry is found whether its bin has been moved to the new table yet or not
+            |map| assert_eq!(map.pin().get(&0), Some(&0)),
+        );
+        assert_contains(&map, 0..2);
+    });
+}
+
+#[test]
+fn insert_during_transfer() {
+    model(|| {
+        let map = small();
+        concurrently(
+            &map,
+            |map| {
+                map.pin().insert(1, 1);
+            },
+            |map| {
+                map.pin().insert(2, 2);
+            },
+        );
+        assert_contains(&map, 0..3);
+    });
+}
+
+#[test]
+fn help_transfer() {
+    model(|| {
+        let map = small();
+        // one thread grows the table, and the other finds a moved bin or sees the ongoing resize
+        // in `add_count`, and helps to finish it before inserting into the new table
+        concurrently(
+            &map,
+            |map| map.pin().reserve(8),
+            |map| {
+                map.pin().insert(1, 1);
+            },
+        );
+        assert_contains(&map, 0..2);
+    });
+}
+
+#[test]
+fn treeify_during_get() {
+    model(|| {
+        let map = colliding(8);
+        concurrently(
+            &map,
+            // the ninth key turns the bin into a tree
+            |map| {
+                map.pin().insert(8, 8);
+            },
+            |map| assert_eq!(map.pin().get(&3), Some(&3)),
+        );
+        assert_contains(&map, 0..9);
+    });
+}
+
+#[test]
+fn untreeify_during_get() {
+    model(|| {
+        let map = colliding(9);
+        concurrently(
+            &map,
+            // growing the table splits the tree into two bins that are too small to stay trees
+            |map| map.pin().reserve(64),
+            |map| {
+                assert_eq!(map.pin().get(&4), Some(&4));
+                assert_eq!(map.pin().get(&5), Some(&5));
+            },
+        );
+        assert_contains(&map, 0..9);
+    });
+}
+
+#[test]
+fn tree_bin_lock_handoff() {
+    model(|| {
+        let map = colliding(9);
+        concurrently(
+            &map,
+            // the writer restructures the tree under the root lock, and has to wait for (or park
+            // until woken by) a reader that is traversing the tree
+            |map| {
+                map.pin().insert(9, 9);
+            },
+            |map| assert_eq!(map.pin().get(&7), Some(&7)),
+        );
+        assert_contains(&map, 0..10);
+    });
+}
+
+#[test]
+fn tree_bin_remove_during_get() {
+    model(|| {
+        let map = colliding(9);
+        concurrently(
+            &map,
+            |map| assert_eq!(map.pin().remove(&0), Some(&0)),
+            |map| assert_eq!(map.pin().get(&8), Some(&8)),
+        );
+        assert_contains(&map, 1..9);
+    });
+}
+
+#[test]
+fn key_lock_during_transfer() {
+    model(|| {
+        let map = small();
+        concurrently(
+            &map,
+            // the resize moves the bin of 0 whether or not it is locked, and takes the lock
+            // along into the new table
+            |map| {
+                map.pin().insert(1, 1);
+            },
+            |map| {
+                let map = map.pin();
+                let mut lock = map.lock_key(&0);
+                assert_eq!(lock.insert(2), Some(&0));
+            },
+        );
+        let guard = map.guard();
+        assert_eq!(map.get(&0, &guard), Some(&2));
+        assert_eq!(map.get(&1, &guard), Some(&1));
+    });
+}
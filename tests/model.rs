@@ -0,0 +1,418 @@
+//! Randomized tests that check `HashMap` against a model.
+//!
+//! `sequential_*` applies random sequences of every public operation to a map and to a
+//! `std::collections::HashMap`, and compares each result. `concurrent_*` runs random operations
+//! from several threads, records when each one started and returned, and then checks that the
+//! history is linearizable with respect to the same model.
+//!
+//! Both run with the default hasher, and with a hasher that gives groups of keys the same hash,
+//! so that bins grow long enough to be turned into `TreeBin`s, and back into linked bins as the
+//! table is resized.
+
+use flurry::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Barrier};
+
+/// A hasher that gives the same hash to every `COLLIDING` consecutive keys.
+#[derive(Default)]
+struct CollidingHasher(u64);
+
+const COLLIDING: u64 = 16;
+
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, key: u64) {
+        self.0 = key / COLLIDING;
+    }
+}
+
+type Colliding = BuildHasherDefault<CollidingHasher>;
+
+/// An operation on a map.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Insert(u64, u64),
+    TryInsert(u64, u64),
+    /// Adds the value to the current one, or removes the entry if the current value is even.
+    ComputeIfPresent(u64, u64),
+    Remove(u64),
+    Get(u64),
+    /// Keeps the entries whose key is not a multiple of the given number.
+    Retain(u64),
+    Clear,
+    Reserve(usize),
+    Iter,
+    Len,
+}
+
+fn compute(value: u64, delta: u64) -> Option<u64> {
+    if value & 1 == 0 {
+        None
+    } else {
+        Some(value.wrapping_add(delta))
+    }
+}
+
+/// Picks a random operation on keys in `0..keys`, where `value` is a value no other operation
+/// inserts.
+fn random_op(rng: &mut impl Rng, keys: u64, value: u64) -> Op {
+    let key = rng.gen_range(0..keys);
+    match rng.gen_range(0..100) {
+        0..=29 => Op::Insert(key, value),
+        30..=39 => Op::TryInsert(key, value),
+        40..=49 => Op::ComputeIfPresent(key, value),
+        50..=69 => Op::Remove(key),
+        70..=89 => Op::Get(key),
+        90..=92 => Op::Retain(rng.gen_range(2..5)),
+        93 => Op::Clear,
+        94..=95 => Op::Reserve(rng.gen_range(0..2 * keys as usize)),
+        96..=97 => Op::Iter,
+        _ => Op::Len,
+    }
+}
+
+/// The observable result of an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    None,
+    Value(Option<u64>),
+    TryInsert(Result<u64, u64>),
+    /// The entries yielded by an iteration, sorted.
+    Entries(Vec<(u64, u64)>),
+    Len(usize),
+}
+
+fn apply<S: BuildHasher>(map: &HashMap<u64, u64, S>, op: Op) -> Outcome {
+    let guard = map.guard();
+    match op {
+        Op::Insert(key, value) => Outcome::Value(map.insert(key, value, &guard).copied()),
+        Op::TryInsert(key, value) => Outcome::TryInsert(
+            map.try_insert(key, value, &guard)
+                .copied()
+                .map_err(|e| *e.current),
+        ),
+        Op::ComputeIfPresent(key, delta) => Outcome::Value(
+            map.compute_if_present(&key, |_, &v| compute(v, delta), &guard)
+                .copied(),
+        ),
+        Op::Remove(key) => Outcome::Value(map.remove(&key, &guard).copied()),
+        Op::Get(key) => Outcome::Value(map.get(&key, &guard).copied()),
+        Op::Retain(divisor) => {
+            map.retain(|&key, _| key % divisor != 0, &guard);
+            Outcome::None
+        }
+        Op::Clear => {
+            map.clear(&guard);
+            Outcome::None
+        }
+        Op::Reserve(additional) => {
+            map.reserve(additional, &guard);
+            Outcome::None
+        }
+        Op::Iter => {
+            let mut entries: Vec<_> = map.iter(&guard).map(|(&k, &v)| (k, v)).collect();
+            entries.sort_unstable();
+            Outcome::Entries(entries)
+        }
+        Op::Len => Outcome::Len(map.len()),
+    }
+}
+
+fn apply_model(model: &mut std::collections::HashMap<u64, u64>, op: Op) -> Outcome {
+    match op {
+        Op::Insert(key, value) => Outcome::Value(model.insert(key, value)),
+        Op::TryInsert(key, value) => Outcome::TryInsert(match model.get(&key) {
+            Some(&current) => Err(current),
+            None => {
+                model.insert(key, value);
+                Ok(value)
+            }
+        }),
+        Op::ComputeIfPresent(key, delta) => {
+            let new = model.get(&key).and_then(|&v| compute(v, delta));
+            match new {
+                Some(new) => model.insert(key, new),
+                None => model.remove(&key),
+            };
+            Outcome::Value(new)
+        }
+        Op::Remove(key) => Outcome::Value(model.remove(&key)),
+        Op::Get(key) => Outcome::Value(model.get(&key).copied()),
+        Op::Retain(divisor) => {
+            model.retain(|&key, _| key % divisor != 0);
+            Outcome::None
+        }
+        Op::Clear => {
+            model.clear();
+            Outcome::None
+        }
+        Op::Reserve(_) => Outcome::None,
+        Op::Iter => {
+            let mut entries: Vec<_> = model.iter().map(|(&k, &v)| (k, v)).collect();
+            entries.sort_unstable();
+            Outcome::Entries(entries)
+        }
+        Op::Len => Outcome::Len(model.len()),
+    }
+}
+
+fn check_sequential<S: BuildHasher + Default>() {
+    let (runs, ops, keys) = if cfg!(miri) {
+        (2, 100, 32)
+    } else {
+        (20, 5000, 512)
+    };
+    for seed in 0..runs {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let map = HashMap::<u64, u64, S>::default();
+        let mut model = std::collections::HashMap::new();
+        for i in 0..ops {
+            let op = random_op(&mut rng, keys, i);
+            let expected = apply_model(&mut model, op);
+            assert_eq!(
+                apply(&map, op),
+                expected,
+                "seed {}: operation {} ({:?}) disagrees with the model",
+                seed,
+                i,
+                op
+            );
+        }
+        let guard = map.guard();
+        let entries: BTreeMap<_, _> = map.iter(&guard).map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(entries, model.into_iter().collect(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn sequential_default_hasher() {
+    check_sequential::<flurry::DefaultHashBuilder>();
+}
+
+#[test]
+fn sequential_colliding_hasher() {
+    check_sequential::<Colliding>();
+}
+
+/// An operation as seen by a single key: the part of `op` and its outcome that concern that key.
+#[derive(Debug, Clone, Copy)]
+enum KeyOp {
+    Insert(u64, Option<u64>),
+    TryInsert(u64, Result<u64, u64>),
+    ComputeIfPresent(u64, Option<u64>),
+    Remove(Option<u64>),
+    Get(Option<u64>),
+    /// The key was seen with this value by an iteration.
+    Seen(u64),
+    /// `clear` removes every key at some point while it runs.
+    Clear,
+    /// `retain` removes a key it does not keep, unless the key's value changes in the meantime.
+    MaybeRemove,
+}
+
+/// How many more times `clear` and `retain` may remove a key while they run.
+///
+/// Neither is atomic: when `clear` finds that the table is being resized, it starts over in the
+/// new table, and `retain` may be shown a key twice by its iterator. Either way, a key that is
+/// inserted again after its bin was visited may be removed again.
+const REVISITS: usize = 2;
+
+impl KeyOp {
+    /// Returns the state of the key after this operation, if the operation can have had its
+    /// outcome when applied to `state`.
+    ///
+    /// `MaybeRemove` has two possible successors, and is handled by the caller.
+    fn step(self, state: Option<u64>) -> Option<Option<u64>> {
+        match self {
+            KeyOp::Insert(value, old) => (old == state).then_some(Some(value)),
+            KeyOp::TryInsert(value, Ok(inserted)) => {
+                (state.is_none() && inserted == value).then_some(Some(value))
+            }
+            KeyOp::TryInsert(_, Err(current)) => (state == Some(current)).then_some(state),
+            KeyOp::ComputeIfPresent(delta, new) => {
+                (state.and_then(|v| compute(v, delta)) == new).then_some(new)
+            }
+            KeyOp::Remove(old) => (old == state).then_some(None),
+            KeyOp::Get(value) => (value == state).then_some(state),
+            KeyOp::Seen(value) => (state == Some(value)).then_some(state),
+            KeyOp::Clear => Some(None),
+            KeyOp::MaybeRemove => unreachable!(),
+        }
+    }
+}
+
+/// A key's operation, together with the clock readings from right before it was called and
+/// right after it returned.
+#[derive(Debug, Clone, Copy)]
+struct Event {
+    op: KeyOp,
+    call: u64,
+    ret: u64,
+}
+
+/// Splits `op` into the operations it performs on each key in `0..keys`.
+fn key_ops(op: Op, outcome: Outcome, keys: u64) -> Vec<(u64, KeyOp)> {
+    match (op, outcome) {
+        (Op::Insert(key, value), Outcome::Value(old)) => vec![(key, KeyOp::Insert(value, old))],
+        (Op::TryInsert(key, value), Outcome::TryInsert(result)) => {
+            vec![(key, KeyOp::TryInsert(value, result))]
+        }
+        (Op::ComputeIfPresent(key, delta), Outcome::Value(new)) => {
+            vec![(key, KeyOp::ComputeIfPresent(delta, new))]
+        }
+        (Op::Remove(key), Outcome::Value(old)) => vec![(key, KeyOp::Remove(old))],
+        (Op::Get(key), Outcome::Value(value)) => vec![(key, KeyOp::Get(value))],
+        (Op::Retain(divisor), _) => (0..keys)
+            .filter(|key| key % divisor == 0)
+            .flat_map(|key| (0..1 + REVISITS).map(move |_| (key, KeyOp::MaybeRemove)))
+            .collect(),
+        (Op::Clear, _) => (0..keys)
+            .flat_map(|key| {
+                std::iter::once((key, KeyOp::Clear))
+                    .chain((0..REVISITS).map(move |_| (key, KeyOp::MaybeRemove)))
+            })
+            .collect(),
+        // iteration is not atomic, so it only tells us that each entry it yields was present at
+        // some point while it ran (and a key that is removed and inserted again while the
+        // iteration is in its bin may be yielded with both values)
+        (Op::Iter, Outcome::Entries(entries)) => entries
+            .into_iter()
+            .map(|(key, value)| (key, KeyOp::Seen(value)))
+            .collect(),
+        // neither is `len`, and `reserve` has no observable effect
+        (Op::Len, _) | (Op::Reserve(_), _) => Vec::new(),
+        (op, outcome) => unreachable!("{:?} cannot have outcome {:?}", op, outcome),
+    }
+}
+
+/// Returns `true` if the events on a single key can be ordered so that each one happens between
+/// its call and its return, and so that they have their outcomes when applied in that order to a
+/// key that starts out absent.
+///
+/// Since linearizability is local, a history of operations that each touch a single key is
+/// linearizable exactly if the history of every key is.
+fn linearizable(events: &[Event]) -> bool {
+    assert!(events.len() <= 128, "too many events on a single key");
+    fn search(
+        events: &[Event],
+        done: u128,
+        state: Option<u64>,
+        seen: &mut HashSet<(u128, Option<u64>)>,
+    ) -> bool {
+        if done.count_ones() as usize == events.len() {
+            return true;
+        }
+        if !seen.insert((done, state)) {
+            return false;
+        }
+        // an event can go next if every event that returned before it was called has gone
+        let pending = |i: usize| done & (1 << i) == 0;
+        let first_return = (0..events.len())
+            .filter(|&i| pending(i))
+            .map(|i| events[i].ret)
+            .min()
+            .unwrap();
+        (0..events.len())
+            .filter(|&i| pending(i) && events[i].call < first_return)
+            .any(|i| {
+                let done = done | (1 << i);
+                match events[i].op {
+                    KeyOp::MaybeRemove => {
+                        search(events, done, None, seen) || search(events, done, state, seen)
+                    }
+                    op => op
+                        .step(state)
+                        .is_some_and(|state| search(events, done, state, seen)),
+                }
+            })
+    }
+    search(events, 0, None, &mut HashSet::new())
+}
+
+fn check_concurrent<S: BuildHasher + Default + Send + Sync + 'static>() {
+    const THREADS: u64 = 4;
+    let (runs, ops, keys) = if cfg!(miri) { (1, 10, 8) } else { (50, 60, 32) };
+    let clock = Arc::new(AtomicU64::new(0));
+    for seed in 0..runs {
+        let map = Arc::new(HashMap::<u64, u64, S>::default());
+        let barrier = Arc::new(Barrier::new(THREADS as usize));
+        let threads: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (map, barrier, clock) = (map.clone(), barrier.clone(), clock.clone());
+                std::thread::spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(seed * THREADS + t);
+                    let ops: Vec<_> = (0..ops)
+                        .map(|i| random_op(&mut rng, keys, (t << 32) | i))
+                        .collect();
+                    barrier.wait();
+                    ops.into_iter()
+                        .map(|op| {
+                            let call = clock.fetch_add(1, Ordering::SeqCst);
+                            let outcome = apply(&map, op);
+                            let ret = clock.fetch_add(1, Ordering::SeqCst);
+                            (op, outcome, call, ret)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let mut histories = vec![Vec::new(); keys as usize];
+        for thread in threads {
+            for (op, outcome, call, ret) in thread.join().unwrap() {
+                for (key, op) in key_ops(op, outcome, keys) {
+                    histories[key as usize].push(Event { op, call, ret });
+                }
+            }
+        }
+        for (key, events) in histories.iter().enumerate() {
+            assert!(
+                linearizable(events),
+                "seed {}: the history of key {} is not linearizable: {:#?}",
+                seed,
+                key,
+                events
+            );
+        }
+    }
+}
+
+#[test]
+fn concurrent_default_hasher() {
+    check_concurrent::<flurry::DefaultHashBuilder>();
+}
+
+#[test]
+fn concurrent_colliding_hasher() {
+    check_concurrent::<Colliding>();
+}
+
+#[test]
+fn linearizability_check_rejects_stale_reads() {
+    let event = |op, call, ret| Event { op, call, ret };
+    // a read that starts after an insert returned must see it
+    let stale = [
+        event(KeyOp::Insert(1, None), 0, 1),
+        event(KeyOp::Get(None), 2, 3),
+    ];
+    assert!(!linearizable(&stale));
+    // but a read that overlaps the insert may or may not
+    let overlapping = [
+        event(KeyOp::Insert(1, None), 0, 2),
+        event(KeyOp::Get(None), 1, 3),
+    ];
+    assert!(linearizable(&overlapping));
+}
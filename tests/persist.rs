@@ -0,0 +1,361 @@
+#![cfg(feature = "persist")]
+
+use flurry::{DurableHashMap, HashMap};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// A directory that is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "flurry-persist-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Copies the files in this directory into a new one.
+    fn copy(&self) -> Self {
+        let copy = Self::new();
+        for entry in fs::read_dir(&self.0).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, copy.0.join(path.file_name().unwrap())).unwrap();
+        }
+        copy
+    }
+
+    fn files_with_extension(&self, extension: &str) -> Vec<PathBuf> {
+        let mut files: Vec<_> = fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some(extension.as_ref()))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+type Contents = BTreeMap<u64, String>;
+
+fn contents(map: &HashMap<u64, String>) -> Contents {
+    map.pin().iter().map(|(k, v)| (*k, v.clone())).collect()
+}
+
+fn open(dir: &Path) -> DurableHashMap<u64, String> {
+    DurableHashMap::open(dir)
+        .unwrap()
+        .with_checkpoint_threshold(None)
+}
+
+/// Applies a random change to `map`, and returns its contents afterwards.
+fn random_change(map: &HashMap<u64, String>, rng: &mut impl Rng) -> Contents {
+    let mref = map.pin();
+    let key = rng.gen_range(0..32);
+    match rng.gen_range(0..10) {
+        0..=5 => {
+            mref.insert(key, format!("value {}", rng.gen::<u16>()));
+        }
+        6..=8 => {
+            mref.remove(&key);
+        }
+        _ => {
+            mref.compute_if_present(&key, |_, v| Some(format!("{} updated", v)));
+        }
+    }
+    contents(map)
+}
+
+#[test]
+fn reopen() {
+    let dir = TempDir::new();
+    let map = open(&dir.0);
+    map.pin().insert(1, String::from("one"));
+    map.pin().insert(2, String::from("two"));
+    map.pin().insert(1, String::from("uno"));
+    map.pin().remove(&2);
+    map.sync().unwrap();
+    let expected = contents(&map);
+    drop(map);
+
+    let map = open(&dir.0);
+    assert_eq!(contents(&map), expected);
+    map.pin().clear();
+    map.pin().insert(3, String::from("three"));
+    let expected = contents(&map);
+    drop(map);
+
+    // dropping the map flushes the log even without a sync
+    let map = open(&dir.0);
+    assert_eq!(contents(&map), expected);
+}
+
+#[test]
+fn checkpoint_replaces_log() {
+    let dir = TempDir::new();
+    let map = open(&dir.0);
+    for i in 0..100 {
+        map.pin().insert(i, i.to_string());
+    }
+    map.checkpoint().unwrap();
+    for i in 50..150 {
+        map.pin().remove(&i);
+    }
+    let expected = contents(&map);
+    drop(map);
+
+    // the log written before the checkpoint is gone
+    assert_eq!(dir.files_with_extension("checkpoint").len(), 1);
+    assert_eq!(dir.files_with_extension("log").len(), 1);
+    let map = open(&dir.0);
+    assert_eq!(contents(&map), expected);
+}
+
+#[test]
+fn sync_checkpoints_past_threshold() {
+    let dir = TempDir::new();
+    let map = DurableHashMap::<u64, String>::open(&dir.0)
+        .unwrap()
+        .with_checkpoint_threshold(Some(1024));
+    map.pin().insert(1, String::from("one"));
+    map.sync().unwrap();
+    assert!(dir.files_with_extension("checkpoint").is_empty());
+
+    for i in 0..100 {
+        map.pin().insert(i, i.to_string());
+    }
+    map.sync().unwrap();
+    assert_eq!(dir.files_with_extension("checkpoint").len(), 1);
+    let expected = contents(&map);
+    drop(map);
+    assert_eq!(contents(&open(&dir.0)), expected);
+}
+
+#[test]
+fn truncated_log_recovers_a_prefix() {
+    const CHANGES: usize = 300;
+    const CHECKPOINT_AT: usize = 100;
+
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    let dir = TempDir::new();
+    let map = open(&dir.0);
+    let mut states = vec![Contents::new()];
+    for i in 0..CHANGES {
+        if i == CHECKPOINT_AT {
+            map.checkpoint().unwrap();
+        }
+        states.push(random_change(&map, &mut rng));
+    }
+    drop(map);
+
+    let log = dir.files_with_extension("log").pop().unwrap();
+    let len = fs::metadata(&log).unwrap().len();
+    // every offset past the segment's header is a place where a crash may have cut it short
+    let mut offsets: Vec<u64> = (0..50).map(|_| rng.gen_range(8..=len)).collect();
+    offsets.extend([8, len - 1, len]);
+    offsets.sort_unstable();
+
+    let mut previous = CHECKPOINT_AT;
+    for offset in offsets {
+        let crashed = dir.copy();
+        let log = crashed.0.join(log.file_name().unwrap());
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&log)
+            .unwrap()
+            .set_len(offset)
+            .unwrap();
+
+        let map = open(&crashed.0);
+        let recovered = contents(&map);
+        // the recovered map is the state after some prefix of the changes, no earlier than the
+        // checkpoint, and longer logs recover longer prefixes
+        let prefix = (previous..=CHANGES)
+            .find(|&i| states[i] == recovered)
+            .unwrap_or_else(|| panic!("truncating at {} recovered a state that never was", offset));
+        previous = prefix;
+        if offset == len {
+            assert_eq!(prefix, CHANGES);
+        }
+
+        // the torn write is discarded, so the map keeps working after recovery
+        map.pin().insert(1000, String::from("after the crash"));
+        let expected = contents(&map);
+        drop(map);
+        assert_eq!(contents(&open(&crashed.0)), expected);
+    }
+}
+
+#[test]
+fn corrupt_record_recovers_a_prefix() {
+    let mut rng = StdRng::seed_from_u64(0xbad);
+    let dir = TempDir::new();
+    let map = open(&dir.0);
+    let mut states = vec![Contents::new()];
+    for _ in 0..100 {
+        states.push(random_change(&map, &mut rng));
+    }
+    drop(map);
+
+    let log = dir.files_with_extension("log").pop().unwrap();
+    let mut bytes = fs::read(&log).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xff;
+    fs::write(&log, bytes).unwrap();
+
+    let recovered = contents(&open(&dir.0));
+    let prefix = states.iter().position(|state| *state == recovered).unwrap();
+    assert!(prefix < 100);
+}
+
+#[test]
+fn sync_while_key_is_locked() {
+    let dir = TempDir::new();
+    let map = Arc::new(open(&dir.0));
+    let guard = map.guard();
+    let mut lock = map.lock_key(&1, &guard);
+    lock.insert(String::from("one"));
+
+    // changes made through the lock are logged right away, so sync does not wait for the lock
+    let (tx, rx) = mpsc::channel();
+    {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            map.pin().insert(2, String::from("two"));
+            tx.send(map.sync()).unwrap();
+        });
+    }
+    rx.recv_timeout(Duration::from_secs(10))
+        .expect("sync stalled while a key was locked")
+        .unwrap();
+    lock.insert(String::from("uno"));
+    drop(lock);
+    drop(guard);
+
+    let expected = contents(&map);
+    drop(map);
+    assert_eq!(contents(&open(&dir.0)), expected);
+}
+
+#[test]
+fn concurrent_changes_and_checkpoints() {
+    const THREADS: u64 = 4;
+    const CHANGES: u64 = 2000;
+
+    let dir = TempDir::new();
+    let map = Arc::new(open(&dir.0));
+    let done = Arc::new(AtomicBool::new(false));
+    let checkpointer = {
+        let (map, done) = (Arc::clone(&map), Arc::clone(&done));
+        std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                map.checkpoint().unwrap();
+            }
+        })
+    };
+    let writers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(t);
+                for i in 0..CHANGES {
+                    let key = rng.gen_range(0..256);
+                    if rng.gen_bool(0.7) {
+                        map.pin().insert(key, format!("{} {}", t, i));
+                    } else {
+                        map.pin().remove(&key);
+                    }
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    done.store(true, Ordering::SeqCst);
+    checkpointer.join().unwrap();
+    map.sync().unwrap();
+
+    let expected = contents(&map);
+    drop(map);
+    assert_eq!(contents(&open(&dir.0)), expected);
+}
+
+/// A value that panics when `Fragile(0)` is serialized.
+#[derive(Clone, Debug, PartialEq)]
+struct Fragile(u64);
+
+impl Serialize for Fragile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        assert_ne!(self.0, 0, "cannot serialize Fragile(0)");
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fragile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Fragile)
+    }
+}
+
+#[test]
+fn serialize_panic() {
+    let dir = TempDir::new();
+    let map = Arc::new(
+        DurableHashMap::<u64, Fragile>::open(&dir.0)
+            .unwrap()
+            .with_checkpoint_threshold(None),
+    );
+    map.pin().insert(1, Fragile(1));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        map.pin().insert(2, Fragile(0));
+    }));
+    assert!(result.is_err());
+    map.pin().insert(3, Fragile(3));
+
+    // the change that could not be logged fails the sync, rather than holding back the ones
+    // after it forever
+    let (tx, rx) = mpsc::channel();
+    {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || tx.send(map.sync().is_ok()).unwrap());
+    }
+    let synced = rx
+        .recv_timeout(Duration::from_secs(10))
+        .expect("sync stalled on a change that could not be serialized");
+    assert!(!synced);
+
+    // a checkpoint stands in for the log again
+    map.pin().remove(&2);
+    map.checkpoint().unwrap();
+    map.pin().insert(4, Fragile(4));
+    map.sync().unwrap();
+    drop(map);
+
+    let map = DurableHashMap::<u64, Fragile>::open(&dir.0).unwrap();
+    let mut recovered: Vec<_> = map.pin().iter().map(|(k, v)| (*k, v.clone())).collect();
+    recovered.sort_unstable_by_key(|&(k, _)| k);
+    assert_eq!(
+        recovered,
+        [(1, Fragile(1)), (3, Fragile(3)), (4, Fragile(4))]
+    );
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "proptest")]
+
+use flurry::{HashMap, HashSet};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestError, TestRunner};
+use std::collections::hash_map::RandomState;
+
+proptest! {
+    #[test]
+    fn generated_map_has_size_in_range(map in flurry::proptest::hash_map(any::<u16>(), any::<u8>(), 5..50)) {
+        prop_assert!((5..50).contains(&map.len()));
+    }
+
+    #[test]
+    fn generated_set_has_size_in_range(set in flurry::proptest::hash_set(any::<u16>(), 5..50)) {
+        prop_assert!((5..50).contains(&set.len()));
+    }
+
+    #[test]
+    fn arbitrary_with_custom_hasher(map in any::<HashMap<String, u32, RandomState>>()) {
+        let guard = map.guard();
+        for (key, value) in map.iter(&guard) {
+            prop_assert_eq!(map.get(key, &guard), Some(value));
+        }
+    }
+}
+
+#[test]
+fn map_shrinks_to_minimal_failing_case() {
+    let mut runner = TestRunner::new(Config::default());
+    let result = runner.run(&any::<HashMap<u8, u8>>(), |map| {
+        prop_assert!(map.pin().keys().all(|&key| key < 100));
+        Ok(())
+    });
+    match result {
+        Err(TestError::Fail(_, map)) => {
+            // every other entry is removed, and the failing key is shrunk to the boundary
+            let entries: Vec<_> = map.pin().iter().map(|(&k, &v)| (k, v)).collect();
+            assert_eq!(entries, [(100, 0)]);
+        }
+        result => panic!("expected a failing case, got {:?}", result),
+    }
+}
+
+#[test]
+fn set_shrinks_to_minimal_failing_case() {
+    let mut runner = TestRunner::new(Config::default());
+    let result = runner.run(&any::<HashSet<u8>>(), |set| {
+        prop_assert!(set.len() < 3);
+        Ok(())
+    });
+    match result {
+        Err(TestError::Fail(_, set)) => {
+            let mut elements: Vec<_> = set.pin().iter().copied().collect();
+            elements.sort_unstable();
+            assert_eq!(elements, [0, 1, 2]);
+        }
+        result => panic!("expected a failing case, got {:?}", result),
+    }
+}
@@ -0,0 +1,140 @@
+#![cfg(feature = "rkyv")]
+
+use flurry::rkyv::ArchiveHashBuilder;
+use flurry::{HashMap, HashSet};
+use rkyv::rancor::Error;
+use rkyv::Archived;
+
+fn archive<T>(value: &T) -> rkyv::util::AlignedVec
+where
+    T: for<'a> rkyv::Serialize<
+        rkyv::api::high::HighSerializer<
+            rkyv::util::AlignedVec,
+            rkyv::ser::allocator::ArenaHandle<'a>,
+            Error,
+        >,
+    >,
+{
+    rkyv::to_bytes::<Error>(value).unwrap()
+}
+
+#[test]
+fn query_archived_map() {
+    let map: HashMap<String, u64> = (0..1000).map(|i| (i.to_string(), i * 2)).collect();
+    let bytes = archive(&map);
+    let archived = rkyv::access::<Archived<HashMap<String, u64>>, Error>(&bytes).unwrap();
+
+    assert_eq!(archived.len(), 1000);
+    for i in 0..1000 {
+        let key = i.to_string();
+        assert_eq!(
+            archived.get(key.as_str()).map(|v| v.to_native()),
+            Some(i * 2)
+        );
+        let (k, _) = archived.get_key_value(key.as_str()).unwrap();
+        assert_eq!(k.as_str(), key);
+    }
+    for i in 1000..1100 {
+        assert!(!archived.contains_key(i.to_string().as_str()));
+    }
+
+    let mut entries: Vec<_> = archived
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_native()))
+        .collect();
+    entries.sort_unstable_by_key(|&(_, v)| v);
+    assert_eq!(
+        entries,
+        (0..1000)
+            .map(|i| (i.to_string(), i * 2))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn query_archived_empty_map() {
+    let map: HashMap<u32, u32> = HashMap::new();
+    let bytes = archive(&map);
+    let archived = rkyv::access::<Archived<HashMap<u32, u32>>, Error>(&bytes).unwrap();
+    assert!(archived.is_empty());
+    assert!(archived.get(&0.into()).is_none());
+
+    let copy: HashMap<u32, u32> = rkyv::deserialize::<_, Error>(archived).unwrap();
+    assert!(copy.is_empty());
+    copy.pin().insert(1, 1);
+    assert_eq!(copy.pin().get(&1), Some(&1));
+}
+
+#[test]
+fn rehydrate_with_any_hasher() {
+    let map: HashMap<u32, String> = (0..500).map(|i| (i, i.to_string())).collect();
+    let bytes = archive(&map);
+    let archived = rkyv::access::<Archived<HashMap<u32, String>>, Error>(&bytes).unwrap();
+
+    let rehashed: HashMap<u32, String> = rkyv::deserialize::<_, Error>(archived).unwrap();
+    assert_eq!(rehashed, map);
+
+    // the keys are hashed again, so the map works with the hasher the archive uses as well
+    let reused: HashMap<u32, String, ArchiveHashBuilder> =
+        rkyv::deserialize::<_, Error>(archived).unwrap();
+    assert_eq!(reused.len(), map.len());
+    let guard = reused.guard();
+    for i in 0..500 {
+        assert_eq!(reused.get(&i, &guard), Some(&i.to_string()));
+    }
+
+    // the rehydrated map keeps working as it grows
+    for i in 500..2000 {
+        reused.insert(i, i.to_string(), &guard);
+    }
+    for i in 0..2000 {
+        assert_eq!(reused.remove(&i, &guard), Some(&i.to_string()));
+    }
+    assert!(reused.is_empty());
+}
+
+#[test]
+fn archive_set() {
+    let set: HashSet<String> = (0..100).map(|i| i.to_string()).collect();
+    let bytes = archive(&set);
+    let archived = rkyv::access::<Archived<HashSet<String>>, Error>(&bytes).unwrap();
+
+    assert_eq!(archived.len(), 100);
+    assert!(archived.contains("42"));
+    assert_eq!(archived.get("42").map(|s| s.as_str()), Some("42"));
+    assert!(!archived.contains("100"));
+
+    let copy: HashSet<String, ArchiveHashBuilder> =
+        rkyv::deserialize::<_, Error>(archived).unwrap();
+    assert_eq!(copy.len(), set.len());
+    let guard = copy.guard();
+    for i in 0..100 {
+        assert!(copy.contains(&i.to_string(), &guard));
+    }
+}
+
+#[test]
+fn truncated_archive_is_rejected() {
+    let map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+    let bytes = archive(&map);
+    let mut truncated = rkyv::util::AlignedVec::<16>::new();
+    truncated.extend_from_slice(&bytes[..bytes.len() / 2]);
+    assert!(rkyv::access::<Archived<HashMap<u32, u32>>, Error>(&truncated).is_err());
+}
+
+#[test]
+fn duplicate_keys_are_rejected() {
+    let map: HashMap<u32, u32> = [(0x1111_1111, 1), (0x2222_2222, 2)].into_iter().collect();
+    let mut bytes = archive(&map);
+    // overwrite the second key with the first, which keeps the archive valid
+    let at = bytes
+        .windows(4)
+        .position(|w| w == 0x2222_2222u32.to_le_bytes())
+        .unwrap();
+    bytes[at..at + 4].copy_from_slice(&0x1111_1111u32.to_le_bytes());
+
+    let archived = rkyv::access::<Archived<HashMap<u32, u32>>, Error>(&bytes).unwrap();
+    assert_eq!(archived.len(), 2);
+    assert!(rkyv::deserialize::<HashMap<u32, u32>, Error>(archived).is_err());
+    assert!(rkyv::deserialize::<HashMap<u32, u32, ArchiveHashBuilder>, Error>(archived).is_err());
+}
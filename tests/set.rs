@@ -317,3 +317,14 @@ fn from_iter_empty() {
 
     assert_eq!(set.len(), 0)
 }
+
+#[test]
+fn owned_insert_and_remove() {
+    let set = HashSet::<usize>::new();
+    assert!(set.insert_owned(42));
+    assert!(!set.insert_owned(42));
+    assert_eq!(set.get_cloned(&42), Some(42));
+    assert!(set.remove_owned(&42));
+    assert!(!set.remove_owned(&42));
+    assert_eq!(set.get_cloned(&42), None);
+}
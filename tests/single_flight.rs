@@ -0,0 +1,243 @@
+use flurry::SingleFlight;
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::task::{Context, Poll, Wake, Waker};
+
+/// A waker that unparks the thread that is blocked on its future.
+struct Unpark(std::thread::Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Arc::new(Unpark(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// A waker that records whether it has been woken.
+#[derive(Default)]
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+fn poll_once<F: Future>(future: Pin<&mut F>, flag: &Arc<Flag>) -> Poll<F::Output> {
+    let waker = Waker::from(Arc::clone(flag));
+    future.poll(&mut Context::from_waker(&waker))
+}
+
+/// A future that is pending until `open` is set.
+struct Gate<'a> {
+    open: &'a AtomicBool,
+}
+
+impl Future for Gate<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.open.load(Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn loads_once() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let loads = AtomicUsize::new(0);
+    let load = || async {
+        loads.fetch_add(1, Ordering::SeqCst);
+        42
+    };
+    assert_eq!(block_on(cache.get_or_insert_with_future(1, load)), 42);
+    assert_eq!(block_on(cache.get_or_insert_with_future(1, load)), 42);
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.get(&1), Some(42));
+
+    assert_eq!(cache.remove(&1), Some(42));
+    assert_eq!(cache.get(&1), None);
+    assert_eq!(block_on(cache.get_or_insert_with_future(1, load)), 42);
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn concurrent_callers_share_one_load() {
+    const THREADS: usize = 8;
+
+    let cache = Arc::new(SingleFlight::<usize, String>::new());
+    let loads = Arc::new(AtomicUsize::new(0));
+    let open = Arc::new(AtomicBool::new(false));
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let (cache, loads, open, barrier) = (
+                Arc::clone(&cache),
+                Arc::clone(&loads),
+                Arc::clone(&open),
+                Arc::clone(&barrier),
+            );
+            std::thread::spawn(move || {
+                barrier.wait();
+                block_on(cache.get_or_insert_with_future(7, || async {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    Gate { open: &open }.await;
+                    String::from("seven")
+                }))
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    // give every thread the chance to find the load in flight
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    open.store(true, Ordering::SeqCst);
+    for t in threads {
+        assert_eq!(t.join().unwrap(), "seven");
+    }
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.get(&7).as_deref(), Some("seven"));
+}
+
+#[test]
+fn error_removes_placeholder() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let result = block_on(cache.try_get_or_insert_with_future(1, || async { Err("failed") }));
+    assert_eq!(result, Err("failed"));
+    assert_eq!(cache.get(&1), None);
+
+    let result = block_on(cache.try_get_or_insert_with_future(1, || async { Ok::<_, ()>(1) }));
+    assert_eq!(result, Ok(1));
+    assert_eq!(cache.get(&1), Some(1));
+}
+
+#[test]
+fn waiters_retry_after_error() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let open = AtomicBool::new(false);
+    let (leader_flag, waiter_flag) = (Arc::default(), Arc::default());
+
+    let mut leader = pin!(cache.try_get_or_insert_with_future(1, || async {
+        Gate { open: &open }.await;
+        Err("failed")
+    }));
+    assert!(poll_once(leader.as_mut(), &leader_flag).is_pending());
+
+    let mut waiter = pin!(cache.try_get_or_insert_with_future(1, || async { Ok::<_, &str>(2) }));
+    assert!(poll_once(waiter.as_mut(), &waiter_flag).is_pending());
+    assert!(!waiter_flag.0.load(Ordering::SeqCst));
+
+    open.store(true, Ordering::SeqCst);
+    assert_eq!(
+        poll_once(leader.as_mut(), &leader_flag),
+        Poll::Ready(Err("failed"))
+    );
+    // the failed load wakes the waiter, which then runs its own loader
+    assert!(waiter_flag.0.load(Ordering::SeqCst));
+    assert_eq!(poll_once(waiter.as_mut(), &waiter_flag), Poll::Ready(Ok(2)));
+    assert_eq!(cache.get(&1), Some(2));
+}
+
+#[test]
+fn cancellation_removes_placeholder() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let open = AtomicBool::new(false);
+    let (leader_flag, waiter_flag) = (Arc::default(), Arc::default());
+
+    let mut leader = Box::pin(cache.get_or_insert_with_future(1, || async {
+        Gate { open: &open }.await;
+        1
+    }));
+    assert!(poll_once(leader.as_mut(), &leader_flag).is_pending());
+
+    let mut waiter = pin!(cache.get_or_insert_with_future(1, || async { 2 }));
+    assert!(poll_once(waiter.as_mut(), &waiter_flag).is_pending());
+
+    drop(leader);
+    assert!(waiter_flag.0.load(Ordering::SeqCst));
+    assert_eq!(cache.get(&1), None);
+    assert_eq!(poll_once(waiter.as_mut(), &waiter_flag), Poll::Ready(2));
+    assert_eq!(cache.get(&1), Some(2));
+}
+
+#[test]
+fn cancelled_waiter_releases_waker() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let open = AtomicBool::new(false);
+    let (leader_flag, waiter_flag) = (Arc::default(), Arc::default());
+
+    let mut leader = pin!(cache.get_or_insert_with_future(1, || async {
+        Gate { open: &open }.await;
+        1
+    }));
+    assert!(poll_once(leader.as_mut(), &leader_flag).is_pending());
+
+    let mut waiter = Box::pin(cache.get_or_insert_with_future(1, || async { 2 }));
+    assert!(poll_once(waiter.as_mut(), &waiter_flag).is_pending());
+    assert_eq!(Arc::strong_count(&waiter_flag), 2);
+
+    // the flight is still going, but no longer holds on to the waiter's waker
+    drop(waiter);
+    assert_eq!(Arc::strong_count(&waiter_flag), 1);
+
+    open.store(true, Ordering::SeqCst);
+    assert_eq!(poll_once(leader.as_mut(), &leader_flag), Poll::Ready(1));
+    assert!(!waiter_flag.0.load(Ordering::SeqCst));
+}
+
+#[test]
+fn insert_during_load_wins() {
+    let cache = SingleFlight::<usize, usize>::new();
+    let open = AtomicBool::new(false);
+    let flag = Arc::default();
+
+    let mut leader = pin!(cache.get_or_insert_with_future(1, || async {
+        Gate { open: &open }.await;
+        1
+    }));
+    assert!(poll_once(leader.as_mut(), &flag).is_pending());
+    assert_eq!(cache.insert(1, 2), None);
+
+    open.store(true, Ordering::SeqCst);
+    assert_eq!(poll_once(leader.as_mut(), &flag), Poll::Ready(1));
+    assert_eq!(cache.get(&1), Some(2));
+}
+
+#[test]
+fn futures_are_send() {
+    fn assert_send<T: Send>(_: &T) {}
+    let cache = SingleFlight::<usize, usize>::new();
+    assert_send(&cache.get_or_insert_with_future(1, || async { 1 }));
+    assert_send(&cache.try_get_or_insert_with_future(1, || async { Ok::<_, ()>(1) }));
+
+    // checked for every key, value and loader that are `Send`, not just the ones above
+    #[allow(dead_code)]
+    fn generic<K, V, S, F, Fut>(cache: &SingleFlight<K, V, S>, key: K, f: F)
+    where
+        K: Sync + Send + Clone + Hash + Ord,
+        V: Sync + Send + Clone,
+        S: BuildHasher + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = V> + Send,
+    {
+        assert_send(&cache.get_or_insert_with_future(key, f));
+    }
+}
@@ -0,0 +1,163 @@
+use flurry::HashMap;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::time::Duration;
+
+/// A waker that unparks the thread that is blocked on its future.
+struct Unpark(std::thread::Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Arc::new(Unpark(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[test]
+fn notified_by_writes() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    let mut changes = map.subscribe(&1);
+    assert!(!changes.has_changed());
+
+    map.insert(1, 1, &guard);
+    assert!(changes.try_recv());
+    assert!(!changes.try_recv());
+
+    // a failed try_insert does not change anything
+    assert!(map.try_insert(1, 2, &guard).is_err());
+    assert!(!changes.try_recv());
+
+    map.compute_if_present(&1, |_, v| Some(v + 1), &guard);
+    assert!(changes.try_recv());
+    map.remove(&1, &guard);
+    assert!(changes.try_recv());
+    // removing an absent key does not change anything
+    map.remove(&1, &guard);
+    assert!(!changes.try_recv());
+
+    map.insert_many([(1, 1), (2, 2)], &guard);
+    assert!(changes.try_recv());
+    map.transaction(
+        &[1],
+        |entries| {
+            entries[0].insert(10);
+            Ok::<_, ()>(())
+        },
+        &guard,
+    )
+    .unwrap();
+    assert!(changes.try_recv());
+    map.lock_key(&1, &guard).insert(11);
+    assert!(changes.try_recv());
+    map.clear(&guard);
+    assert!(changes.try_recv());
+}
+
+#[test]
+fn not_notified_by_other_keys() {
+    let map = HashMap::<usize, usize>::new();
+    let guard = map.guard();
+    let mref = map.pin();
+    let mut changes = mref.subscribe(&1);
+    for i in 2..100 {
+        map.insert(i, i, &guard);
+    }
+    map.remove(&2, &guard);
+    assert!(!changes.try_recv());
+}
+
+#[test]
+fn recv_blocks_until_change() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let mut changes = map.subscribe(&1);
+    assert!(!changes.recv_timeout(Duration::from_millis(10)));
+
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            map.pin().insert(1, 1);
+        })
+    };
+    changes.recv();
+    assert_eq!(map.pin().get(&1), Some(&1));
+    writer.join().unwrap();
+}
+
+#[test]
+fn wait_until_present() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    assert!(!map.wait_until_present(&1, Duration::from_millis(10)));
+
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            let guard = map.guard();
+            // changes to other keys do not end the wait
+            for i in 2..10 {
+                map.insert(i, i, &guard);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+            map.insert(1, 1, &guard);
+        })
+    };
+    assert!(map.wait_until_present(&1, Duration::from_secs(60)));
+    writer.join().unwrap();
+
+    // present keys return immediately
+    assert!(map.wait_until_present(&1, Duration::ZERO));
+}
+
+#[test]
+fn wait_until_present_async() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            map.pin().insert(1, 1);
+        })
+    };
+    block_on(map.wait_until_present_async(&1));
+    assert_eq!(map.pin().get(&1), Some(&1));
+    writer.join().unwrap();
+}
+
+#[test]
+fn changed_async() {
+    let map = Arc::new(HashMap::<usize, usize>::new());
+    let mut changes = map.subscribe(&1);
+    let writer = {
+        let map = Arc::clone(&map);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            map.pin().insert(1, 1);
+        })
+    };
+    block_on(changes.changed());
+    assert!(!changes.has_changed());
+    writer.join().unwrap();
+}
+
+#[test]
+fn futures_are_send() {
+    fn assert_send<T: Send>(_: &T) {}
+    let map = HashMap::<usize, usize>::new();
+    assert_send(&map.wait_until_present_async(&1));
+    let mut changes = map.subscribe(&1);
+    assert_send(&changes.changed());
+}